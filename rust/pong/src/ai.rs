@@ -0,0 +1,262 @@
+use crate::model::Ball;
+use std::collections::VecDeque;
+
+/// The range the center of the ball moves in vertically, it bounces off `top` and `bottom`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Court {
+    pub top: f64,
+    pub bottom: f64,
+}
+
+/// How well the computer plays
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    /// seconds until a change of the ball is seen
+    pub reaction: f64,
+    /// how many wall bounces are foreseen, beyond that the paddle just follows the ball
+    pub bounces: u32,
+    /// in pixels per second
+    pub max_speed: f64,
+    /// the paddle aims up to this many pixels away from where the ball arrives
+    pub error: f64,
+}
+
+impl Difficulty {
+    pub const EASY: Difficulty = Difficulty {
+        reaction: 0.3,
+        bounces: 0,
+        max_speed: 90.0,
+        error: 30.0,
+    };
+    pub const NORMAL: Difficulty = Difficulty {
+        reaction: 0.15,
+        bounces: 1,
+        max_speed: 130.0,
+        error: 18.0,
+    };
+    pub const HARD: Difficulty = Difficulty {
+        reaction: 0.05,
+        bounces: 4,
+        max_speed: 200.0,
+        error: 8.0,
+    };
+}
+
+/// Where the ball crosses `x` and how often it bounces off the walls until then, `None` if it
+/// moves away from `x`. Changes of the ball's speed are not foreseen.
+pub fn predict(ball: Ball, x: f64, court: Court) -> Option<(f64, u32)> {
+    let time = (x - ball.position[0]) / ball.velocity[0];
+    if !time.is_finite() || time < 0.0 {
+        return None;
+    }
+    let height = court.bottom - court.top;
+    if height <= 0.0 {
+        return Some((court.top, 0));
+    }
+    // the ball flies straight through mirrored copies of the court, each one is a bounce
+    let y = ball.position[1] + ball.velocity[1] * time - court.top;
+    let bounces = (y / height).floor().abs() as u32;
+    let y = y.rem_euclid(2.0 * height);
+    let y = if y > height { 2.0 * height - y } else { y };
+    Some((court.top + y, bounces))
+}
+
+/// The vertical velocity of the paddle at `paddle` for the next `dt` seconds, given the ball as
+/// the computer sees it. `offset` is its deliberate error. While the ball moves away the paddle
+/// returns to the middle. Without time to move, e.g. `dt` of 0, the paddle stands still.
+pub fn paddle_velocity(
+    seen: Ball,
+    paddle: [f64; 2],
+    court: Court,
+    difficulty: &Difficulty,
+    offset: f64,
+    dt: f64,
+) -> f64 {
+    let [x, y] = paddle;
+    let target = match predict(seen, x, court) {
+        None => (court.top + court.bottom) / 2.0,
+        Some((y, bounces)) if bounces <= difficulty.bounces => y + offset,
+        Some(_) => seen.position[1] + offset,
+    };
+    let target = target.clamp(court.top, court.bottom);
+    if dt <= 0.0 {
+        return 0.0;
+    }
+    ((target - y) / dt).clamp(-difficulty.max_speed, difficulty.max_speed)
+}
+
+/// A computer player. It remembers the ball to see it late and picks a new error every time the
+/// ball comes towards it, from a seeded generator so replays play out the same.
+pub struct Ai {
+    difficulty: Difficulty,
+    time: f64,
+    /// the ball over the last reaction time, oldest first
+    seen: VecDeque<(f64, Ball)>,
+    offset: f64,
+    approaching: bool,
+    random: u64,
+}
+
+impl Ai {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Ai {
+        Ai {
+            difficulty,
+            time: 0.0,
+            seen: VecDeque::new(),
+            offset: 0.0,
+            approaching: false,
+            // xorshift never leaves 0
+            random: seed.max(1),
+        }
+    }
+
+    /// Watches the ball for `dt` seconds and returns the vertical velocity of the paddle at
+    /// `paddle`
+    pub fn update(&mut self, ball: Ball, paddle: [f64; 2], court: Court, dt: f64) -> f64 {
+        self.time += dt;
+        self.seen.push_back((self.time, ball));
+        while self.seen.len() > 1 && self.seen[1].0 <= self.time - self.difficulty.reaction {
+            self.seen.pop_front();
+        }
+        let seen = self.seen[0].1;
+
+        let approaching = (paddle[0] - seen.position[0]) * seen.velocity[0] > 0.0;
+        if approaching && !self.approaching {
+            self.offset = (self.next_random() * 2.0 - 1.0) * self.difficulty.error;
+        }
+        self.approaching = approaching;
+        paddle_velocity(seen, paddle, court, &self.difficulty, self.offset, dt)
+    }
+
+    /// in `[0, 1)`
+    fn next_random(&mut self) -> f64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        (self.random >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COURT: Court = Court {
+        top: 27.0,
+        bottom: 373.0,
+    };
+
+    /// xorshift in `[low, high)`
+    fn random(state: &mut u64, low: f64, high: f64) -> f64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        low + (*state >> 11) as f64 / (1u64 << 53) as f64 * (high - low)
+    }
+
+    /// Follows the ball from wall to wall until it reaches `x`
+    fn simulate(ball: Ball, x: f64, court: Court) -> Option<(f64, u32)> {
+        let [vx, mut vy] = ball.velocity;
+        let mut time = (x - ball.position[0]) / vx;
+        if !time.is_finite() || time < 0.0 {
+            return None;
+        }
+        let (mut y, mut bounces) = (ball.position[1], 0);
+        loop {
+            let wall_time = match vy {
+                vy if vy > 0.0 => (court.bottom - y) / vy,
+                vy if vy < 0.0 => (court.top - y) / vy,
+                _ => f64::INFINITY,
+            };
+            if wall_time >= time {
+                return Some((y + vy * time, bounces));
+            }
+            y = if vy > 0.0 { court.bottom } else { court.top };
+            vy = -vy;
+            time -= wall_time;
+            bounces += 1;
+        }
+    }
+
+    #[test]
+    fn predict_matches_simulation() {
+        let mut state = 7;
+        for _ in 0..2000 {
+            let ball = Ball {
+                position: [
+                    random(&mut state, 30.0, 570.0),
+                    random(&mut state, COURT.top, COURT.bottom),
+                ],
+                velocity: [
+                    random(&mut state, -300.0, 300.0),
+                    random(&mut state, -600.0, 600.0),
+                ],
+            };
+            let x = random(&mut state, 29.0, 571.0);
+            match (predict(ball, x, COURT), simulate(ball, x, COURT)) {
+                (Some((y, bounces)), Some((expected_y, expected_bounces))) => {
+                    assert!(
+                        (y - expected_y).abs() < 1e-6,
+                        "{:?} to {}: {} != {}",
+                        ball,
+                        x,
+                        y,
+                        expected_y
+                    );
+                    assert_eq!(bounces, expected_bounces, "{:?} to {}", ball, x);
+                }
+                (None, None) => {}
+                (predicted, simulated) => {
+                    panic!("{:?} to {}: {:?} != {:?}", ball, x, predicted, simulated)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn paddle_speed_is_limited() {
+        let ball = Ball {
+            position: [300.0, 350.0],
+            velocity: [-100.0, 0.0],
+        };
+        let easy = Difficulty::EASY;
+        // the ball arrives far below the paddle
+        let velocity = paddle_velocity(ball, [29.0, 50.0], COURT, &easy, 0.0, 1.0 / 120.0);
+        assert_eq!(velocity, easy.max_speed);
+        let velocity = paddle_velocity(ball, [29.0, 50.0], COURT, &easy, 0.0, 10.0);
+        assert_eq!(velocity, 30.0);
+        // close to the target the paddle slows down instead of overshooting it
+        let velocity = paddle_velocity(ball, [29.0, 349.5], COURT, &easy, 0.0, 0.01);
+        assert!((velocity - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn paddle_returns_to_the_middle() {
+        let ball = Ball {
+            position: [300.0, 100.0],
+            velocity: [100.0, 50.0],
+        };
+        let middle = (COURT.top + COURT.bottom) / 2.0;
+        for paddle in [40.0, middle, 360.0] {
+            let velocity =
+                paddle_velocity(ball, [29.0, paddle], COURT, &Difficulty::HARD, 10.0, 10.0);
+            assert!((velocity - (middle - paddle) / 10.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn paddle_without_time_stands_still() {
+        let ball = Ball {
+            position: [300.0, 100.0],
+            velocity: [-100.0, 50.0],
+        };
+        for paddle in [100.0, 200.0] {
+            assert_eq!(
+                paddle_velocity(ball, [29.0, paddle], COURT, &Difficulty::NORMAL, 0.0, 0.0),
+                0.0
+            );
+        }
+        let mut ai = Ai::new(Difficulty::NORMAL, 1);
+        assert_eq!(ai.update(ball, [29.0, 200.0], COURT, 0.0), 0.0);
+    }
+}
@@ -1,124 +1,529 @@
+mod ai;
+mod model;
+mod net;
+
+use ai::{Ai, Court, Difficulty};
+use model::{Ball, Event, Match, Rules, Side, BALL_RADIUS, MARGIN, PADDLE_HEIGHT};
+use net::{NetError, Session, DOWN, UP};
 use std::ops::Neg;
-use vn_engine::color::{BLACK, BLUE, GREEN, RED, VIOLET, WHITE};
-use vn_engine::engine::{VNEngineState, VNEngine, VNERunner};
+use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use vn_engine::audio::Audio;
+use vn_engine::audio::{PlayOptions, Sound, Tone, Wave, SAMPLE_RATE};
+use vn_engine::color::{BlendMode, RGBA, BLACK, BLUE, GREEN, RED, VIOLET, WHITE};
+use vn_engine::engine::{FixedTimestep, VNEngineState, VNEngine};
+use vn_engine::font::Font;
 use vn_engine::Keycode;
 use vn_engine::opengl::OpenGLRenderer;
 use vn_engine::render::{PixelPosition, VNERenderer};
+use vn_engine::replay::InputLog;
+use vn_engine::scene::{Scene, SceneCommand, SceneManager};
+use vn_engine::target::RenderTarget;
+use vn_engine::ui::{Ui, UiState};
+
+const PADDLE_SPEED: f64 = 150.0;
+const FADE_DURATION: f64 = 0.6;
+/// frames until the input of a network game is used, one frame is 1/120 of a second
+const INPUT_DELAY: u32 = 6;
+
+fn draw_centered_text(renderer: &mut dyn VNERenderer, engine: &VNEngineState, y: i32, text: &str, color: RGBA, scale: u32) {
+    let (width, _) = Font::builtin().text_size(text, scale);
+    renderer.draw_text(PixelPosition { x: (engine.width as i32 - width as i32) / 2, y }, text, color, scale);
+}
 
-struct State {
-    ball_pos: [f64; 2],
-    left_paddle: f64,
-    right_paddle: f64,
-    ball_velocity: [f64; 2],
-    stop: bool,
+/// The title screen, the computer plays against itself behind it
+struct Title {
+    demo: Game,
 }
 
-impl VNERunner for State {
-    fn setup(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
-        renderer.set_title(format!("Pong! FPS: {}", 1.0 / engine.delta).as_str());
+impl Title {
+    fn new(engine: &VNEngineState) -> Title {
+        Title { demo: Game::new(Mode::Attract, engine) }
     }
+}
 
-    fn tick(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
-        const BALL_RADIUS: u32 = 2;
-        const PADDLE_HEIGHT: u32 = 50;
-        const PADDLE_SPEED: f64 = 150.0;
-        const SPEED_INCREASE: f64 = 10.0;
+impl Scene for Title {
+    fn setup(&mut self, _engine: &VNEngineState, renderer: &mut dyn VNERenderer) {
+        renderer.set_title("Pong!");
+    }
 
-        renderer.clear_screen(BLACK);
-        renderer.set_title(format!("Pong! FPS: {}", 1.0 / engine.delta).as_str());
+    fn tick(&mut self, engine: &VNEngineState, dt: f64) -> SceneCommand {
+        let mode = if engine.input().key_pressed(Keycode::Space) {
+            Mode::TwoPlayers
+        } else if engine.input().key_pressed(Keycode::Key1) {
+            Mode::Computer(Difficulty::EASY)
+        } else if engine.input().key_pressed(Keycode::Key2) {
+            Mode::Computer(Difficulty::NORMAL)
+        } else if engine.input().key_pressed(Keycode::Key3) {
+            Mode::Computer(Difficulty::HARD)
+        } else {
+            // the demo only ever ends with a won match
+            if let SceneCommand::Push(_) = self.demo.tick(engine, dt) {
+                self.demo = Game::new(Mode::Attract, engine);
+            }
+            return SceneCommand::None;
+        };
+        SceneCommand::Replace(Box::new(Game::new(mode, engine))).fade(BLACK, FADE_DURATION)
+    }
 
-        let left_edge = 25;
-        let right_edge = engine.width - 25;
-        let top_edge = 25;
-        let bottom_edge = engine.height - 25;
+    fn render(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, alpha: f64) {
+        self.demo.render(engine, renderer, alpha);
+        draw_banner(renderer, engine, "PONG", "press space for two players");
+        let y = engine.height as i32 * 2 / 3 + 16;
+        draw_centered_text(renderer, engine, y, "or 1, 2 or 3 to play against the computer", WHITE, 1);
+    }
+}
+
+/// Who moves the paddles
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Mode {
+    TwoPlayers,
+    /// the computer plays left, the human right
+    Computer(Difficulty),
+    /// the computer plays both sides on the title screen, without sound or pausing
+    Attract,
+    /// against another machine, this one plays the side
+    Network(Side),
+}
+
+enum Player {
+    Human { up: Keycode, down: Keycode },
+    Computer(Ai),
+}
+
+impl Player {
+    /// The vertical velocity of the paddle at `paddle` for the next `dt` seconds
+    fn paddle_velocity(&mut self, engine: &VNEngineState, ball: Ball, paddle: [f64; 2], court: Court, speed: f64, dt: f64) -> f64 {
+        match self {
+            Player::Human { up, down } => {
+                let mut velocity = 0.0;
+                if engine.input().key_down(*up) {
+                    velocity -= speed;
+                }
+                if engine.input().key_down(*down) {
+                    velocity += speed;
+                }
+                velocity
+            }
+            Player::Computer(ai) => ai.update(ball, paddle, court, dt),
+        }
+    }
+}
+
+/// The input of this machine in a network game, either player's keys move the own paddle
+fn network_input(engine: &VNEngineState) -> u8 {
+    let input = engine.input();
+    let mut bits = 0;
+    if input.key_down(Keycode::W) || input.key_down(Keycode::Up) {
+        bits |= UP;
+    }
+    if input.key_down(Keycode::S) || input.key_down(Keycode::Down) {
+        bits |= DOWN;
+    }
+    bits
+}
+
+/// The vertical velocity of a paddle for the input of a network game
+fn input_velocity(input: u8, speed: f64) -> f64 {
+    match (input & UP != 0, input & DOWN != 0) {
+        (true, false) => -speed,
+        (false, true) => speed,
+        _ => 0.0,
+    }
+}
+
+struct Game {
+    mode: Mode,
+    left_player: Player,
+    right_player: Player,
+    model: Match,
+    /// the background with the field, it never changes so it is only drawn once
+    field: Option<RenderTarget>,
+    paddle_speed: f64,
+    ui: UiState,
+    sounds: Sounds,
+    session: Option<Session>,
+    /// seconds since the inputs of the other machine stopped arriving
+    waiting: f64,
+}
+
+impl Game {
+    fn new(mode: Mode, engine: &VNEngineState) -> Game {
+        let left_keys = Player::Human { up: Keycode::W, down: Keycode::S };
+        let right_keys = Player::Human { up: Keycode::Up, down: Keycode::Down };
+        let (left_player, right_player) = match mode {
+            // in a network game the session moves the paddles
+            Mode::TwoPlayers | Mode::Network(_) => (left_keys, right_keys),
+            Mode::Computer(difficulty) => (Player::Computer(Ai::new(difficulty, 1)), right_keys),
+            Mode::Attract => (
+                Player::Computer(Ai::new(Difficulty::NORMAL, 1)),
+                Player::Computer(Ai::new(Difficulty::NORMAL, 2)),
+            ),
+        };
+        Game {
+            mode,
+            left_player,
+            right_player,
+            model: Match::new(engine.width, engine.height, Rules::default()),
+            field: None,
+            paddle_speed: PADDLE_SPEED,
+            ui: UiState::default(),
+            sounds: Sounds::new(),
+            session: None,
+            waiting: 0.0,
+        }
+    }
+
+    /// A game against the other side of `session`
+    fn networked(session: Session, engine: &VNEngineState) -> Game {
+        let mode = Mode::Network(session.side());
+        Game { session: Some(session), ..Game::new(mode, engine) }
+    }
+
+    /// Plays `sound` panned to where `x` is on the screen
+    fn play_sound(&self, engine: &VNEngineState, sound: &Arc<Sound>, x: f64) {
+        if self.mode == Mode::Attract {
+            return;
+        }
+        if let Some(audio) = engine.audio() {
+            let pan = (x / engine.width as f64 * 2.0 - 1.0) as f32;
+            audio.play(sound, PlayOptions { pan, ..PlayOptions::default() });
+        }
+    }
+
+    /// Ends a network game, the other machine is told if it is still there
+    fn disconnect(&mut self, error: NetError) -> SceneCommand {
+        self.session = None;
+        SceneCommand::Push(Box::new(Disconnected { reason: error.to_string() }))
+    }
+}
+
+/// the sounds of the game, synthesized once
+struct Sounds {
+    paddle: Arc<Sound>,
+    wall: Arc<Sound>,
+    miss: Arc<Sound>,
+}
+
+impl Sounds {
+    fn new() -> Sounds {
+        let miss = Tone { end_frequency: 200.0, ..Tone::new(Wave::Noise, 2000.0, 0.5) };
+        Sounds {
+            paddle: Arc::new(Tone::beep(440.0, 0.06).render(SAMPLE_RATE)),
+            wall: Arc::new(Tone::beep(220.0, 0.05).render(SAMPLE_RATE)),
+            miss: Arc::new(miss.render(SAMPLE_RATE)),
+        }
+    }
+}
+
+fn draw_field(engine: &VNEngineState) -> RenderTarget {
+    let mut field = RenderTarget::new(engine.width, engine.height);
+    let top_left = PixelPosition { x: MARGIN, y: MARGIN };
+    let bottom_right = PixelPosition { x: engine.width as i32 - MARGIN, y: engine.height as i32 - MARGIN };
+    field.clear_screen(BLACK);
+    field.fill_rectangle(top_left, bottom_right, WHITE);
+    field.draw_rectangle(top_left, bottom_right, VIOLET);
+    field
+}
+
+impl Scene for Game {
+    fn tick(&mut self, engine: &VNEngineState, dt: f64) -> SceneCommand {
+        if self.session.is_none() && self.mode != Mode::Attract {
+            // clicks are only seen by the first step of a frame, not by render
+            self.ui.collect_input(engine.input());
+
+            if engine.input().key_pressed(Keycode::P) || engine.input().key_pressed(Keycode::Escape) {
+                return SceneCommand::Push(Box::new(Pause));
+            }
+            if engine.input().key_pressed(Keycode::R) {
+                return SceneCommand::Replace(Box::new(Game::new(self.mode, engine))).fade(BLACK, FADE_DURATION);
+            }
+        }
+
+        // the computer aims where the center of the ball is when it bounces off a paddle
+        let (ball, court) = (self.model.ball(), self.model.court());
+        let velocities = match &mut self.session {
+            Some(session) => {
+                if engine.input().key_pressed(Keycode::Escape) {
+                    return SceneCommand::ReplaceAll(Box::new(Title::new(engine))).fade(BLACK, FADE_DURATION);
+                }
+                // both machines step with the same inputs or not at all
+                match session.advance(network_input(engine)) {
+                    Ok(Some(inputs)) => {
+                        self.waiting = 0.0;
+                        inputs.map(|input| input_velocity(input, self.paddle_speed))
+                    }
+                    Ok(None) => {
+                        self.waiting += dt;
+                        return SceneCommand::None;
+                    }
+                    Err(error) => return self.disconnect(error),
+                }
+            }
+            None => {
+                let left = [self.model.paddle_x(Side::Left), self.model.paddle(Side::Left)];
+                let right = [self.model.paddle_x(Side::Right), self.model.paddle(Side::Right)];
+                [
+                    self.left_player.paddle_velocity(engine, ball, left, court, self.paddle_speed, dt),
+                    self.right_player.paddle_velocity(engine, ball, right, court, self.paddle_speed, dt),
+                ]
+            }
+        };
+
+        let events = self.model.step(velocities, dt);
+        if let Some(session) = &mut self.session {
+            if let Err(error) = session.check_state(self.model.state_hash()) {
+                return self.disconnect(error);
+            }
+        }
+        for event in events {
+            // a point puts the ball back in the middle, the sound plays where it left the field
+            let x = match event {
+                Event::Point(_) | Event::Won(_) => ball.position[0],
+                _ => self.model.ball().position[0],
+            };
+            match event {
+                Event::WallBounce => self.play_sound(engine, &self.sounds.wall, x),
+                Event::PaddleHit(_) => self.play_sound(engine, &self.sounds.paddle, x),
+                Event::Point(_) => self.play_sound(engine, &self.sounds.miss, x),
+                Event::Won(winner) => {
+                    self.play_sound(engine, &self.sounds.miss, x);
+                    // the other side may still need inputs for the last frames
+                    let session = self.session.take();
+                    let waiting = session.is_some();
+                    return SceneCommand::Push(Box::new(MatchOver { mode: self.mode, winner, session, waiting }));
+                }
+            }
+        }
+        SceneCommand::None
+    }
+
+    fn render(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, alpha: f64) {
+        let left_edge = self.model.left_edge();
+        let right_edge = self.model.right_edge();
 
         // draw field
-        renderer.fill_rectangle(PixelPosition { x: left_edge, y: top_edge }, PixelPosition { x: right_edge, y: bottom_edge }, WHITE);
-        renderer.draw_rectangle(PixelPosition { x: left_edge, y: top_edge }, PixelPosition { x: right_edge, y: bottom_edge }, VIOLET);
+        let field = self.field.get_or_insert_with(|| draw_field(engine));
+        renderer.draw_target(field, PixelPosition { x: 0, y: 0 }, BlendMode::Replace);
+
+        renderer.draw_text(PixelPosition { x: left_edge, y: 8 }, &format!("FPS: {:.0}", 1.0 / engine.delta), WHITE, 1);
+        let score = format!("{}  {}", self.model.score(Side::Left), self.model.score(Side::Right));
+        draw_centered_text(renderer, engine, 6, &score, WHITE, 2);
 
         // draw paddles
         renderer.fill_rectangle(
-            PixelPosition { x: left_edge - BALL_RADIUS, y: self.left_paddle as u32 - PADDLE_HEIGHT / 2},
-            PixelPosition { x: left_edge + BALL_RADIUS, y: self.left_paddle as u32 + PADDLE_HEIGHT / 2},
+            PixelPosition { x: left_edge - BALL_RADIUS, y: self.model.paddle(Side::Left) as i32 - PADDLE_HEIGHT / 2},
+            PixelPosition { x: left_edge + BALL_RADIUS, y: self.model.paddle(Side::Left) as i32 + PADDLE_HEIGHT / 2},
             GREEN,
         );
         renderer.fill_rectangle(
-            PixelPosition { x: right_edge - BALL_RADIUS, y: self.right_paddle as u32 - PADDLE_HEIGHT / 2},
-            PixelPosition { x: right_edge + BALL_RADIUS, y: self.right_paddle as u32 + PADDLE_HEIGHT / 2},
+            PixelPosition { x: right_edge - BALL_RADIUS, y: self.model.paddle(Side::Right) as i32 - PADDLE_HEIGHT / 2},
+            PixelPosition { x: right_edge + BALL_RADIUS, y: self.model.paddle(Side::Right) as i32 + PADDLE_HEIGHT / 2},
             GREEN,
         );
 
-        // language = draw ball
-        let [x, y] = self.ball_pos;
-        let [x, y] = [x as u32, y as u32];
+        // draw ball between the last two simulated positions
+        let [px, py] = self.model.previous_ball();
+        let [x, y] = self.model.ball().position;
+        let [x, y] = [(px + (x - px) * alpha) as i32, (py + (y - py) * alpha) as i32];
         renderer.fill_rectangle(PixelPosition { x: x - BALL_RADIUS, y: y - BALL_RADIUS }, PixelPosition { x: x + BALL_RADIUS , y: y + BALL_RADIUS }, RED);
 
-        // updates
-        if !self.stop {
-            // update ball position
-            self.ball_pos[0] += self.ball_velocity[0] * engine.delta;
-            self.ball_pos[1] += self.ball_velocity[1] * engine.delta;
-
-            // update paddle positions
-            if engine.keyboad().contains(&Keycode::Up) {
-                self.right_paddle -= PADDLE_SPEED * engine.delta;
-            }
-            if engine.keyboad().contains(&Keycode::Down) {
-                self.right_paddle += PADDLE_SPEED * engine.delta;
+        if let Mode::Network(_) = self.mode {
+            // a short wait happens all the time, only a long one is worth a note
+            if self.waiting > 0.25 {
+                draw_centered_text(renderer, engine, engine.height as i32 / 2 + 20, "waiting for the other player", BLACK, 1);
             }
+            return;
+        }
+        if self.mode == Mode::Attract {
+            return;
+        }
+        let position = PixelPosition { x: right_edge - 160, y: 4 };
+        let mut ui = Ui::new(&mut self.ui, engine.input(), renderer, position, 160);
+        ui.panel("tuning", |ui| {
+            ui.slider("paddle speed", &mut self.paddle_speed, 50.0..=400.0);
+            ui.slider("speed increase", &mut self.model.rules.speed_increase, 0.0..=50.0);
+        });
+    }
+}
 
-            // update paddle positions
-            if engine.keyboad().contains(&Keycode::W) {
-                self.left_paddle -= PADDLE_SPEED * engine.delta;
-            }
-            if engine.keyboad().contains(&Keycode::S) {
-                self.left_paddle += PADDLE_SPEED * engine.delta;
-            }
+/// Dims the game below and shows `text`
+fn draw_banner(renderer: &mut dyn VNERenderer, engine: &VNEngineState, title: &str, text: &str) {
+    let mode = renderer.blend_mode();
+    renderer.set_blend_mode(BlendMode::Alpha);
+    renderer.fill_rectangle(PixelPosition { x: 0, y: 0 }, PixelPosition { x: engine.width as i32 - 1, y: engine.height as i32 - 1 }, RGBA { a: 0.6, ..BLACK });
+    renderer.set_blend_mode(mode);
+    draw_centered_text(renderer, engine, engine.height as i32 / 3, title, WHITE, 3);
+    draw_centered_text(renderer, engine, engine.height as i32 * 2 / 3, text, WHITE, 1);
+}
 
-            // bounce bottom
-            if y + BALL_RADIUS >= bottom_edge {
-                self.ball_velocity[1] = -self.ball_velocity[1].abs();
-            }
+struct Pause;
 
-            // bounce top
-            if y - BALL_RADIUS <= top_edge {
-                self.ball_velocity[1] = self.ball_velocity[1].abs();
-            }
+impl Scene for Pause {
+    fn tick(&mut self, engine: &VNEngineState, _dt: f64) -> SceneCommand {
+        if engine.input().key_pressed(Keycode::P) || engine.input().key_pressed(Keycode::Escape) {
+            return SceneCommand::Pop;
+        }
+        SceneCommand::None
+    }
 
-            // bounce right
-            if x + BALL_RADIUS >= right_edge - BALL_RADIUS && y <= self.right_paddle as u32 + PADDLE_HEIGHT / 2 && y >= self.right_paddle as u32 - PADDLE_HEIGHT / 2 {
-                self.ball_velocity[0] = -self.ball_velocity[0].abs();
-                self.ball_pos[0] = right_edge as f64 - BALL_RADIUS as f64 * 2.0 - 1.0;
-            }
+    fn render(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, _alpha: f64) {
+        draw_banner(renderer, engine, "PAUSED", "press p to resume");
+    }
 
-            // bounce left
-            if x - BALL_RADIUS <= left_edge + BALL_RADIUS && y <= self.left_paddle as u32 + PADDLE_HEIGHT / 2 && y >= self.left_paddle as u32 - PADDLE_HEIGHT / 2 {
-                self.ball_velocity[0] = self.ball_velocity[0].abs();
-                self.ball_pos[0] = left_edge as f64 + BALL_RADIUS as f64 * 2.0 + 1.0;
-            }
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// The network game ended early, `reason` tells why
+struct Disconnected {
+    reason: String,
+}
+
+impl Scene for Disconnected {
+    fn tick(&mut self, engine: &VNEngineState, _dt: f64) -> SceneCommand {
+        if engine.input().key_pressed(Keycode::Space) || engine.input().key_pressed(Keycode::Escape) {
+            return SceneCommand::ReplaceAll(Box::new(Title::new(engine))).fade(BLACK, FADE_DURATION);
+        }
+        SceneCommand::None
+    }
+
+    fn render(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, _alpha: f64) {
+        draw_banner(renderer, engine, "DISCONNECTED", &self.reason);
+        draw_centered_text(renderer, engine, engine.height as i32 * 2 / 3 + 16, "press space to leave", WHITE, 1);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
 
-            // game over
-            if self.ball_pos[0] as u32 + BALL_RADIUS > right_edge || self.ball_pos[0] as u32 - BALL_RADIUS < left_edge {
-                println!("{}", self.ball_pos[0]);
-                self.stop = true;
+/// Shows who won, playing again keeps the mode. A network game can only be left, once the other
+/// side has all inputs.
+struct MatchOver {
+    mode: Mode,
+    winner: Side,
+    session: Option<Session>,
+    /// the other side still misses inputs of this one
+    waiting: bool,
+}
+
+impl MatchOver {
+    fn sending(&mut self) -> bool {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => return false,
+        };
+        match session.finish() {
+            Ok(finished) => !finished,
+            // the result stands either way
+            Err(error) => {
+                eprintln!("Failed to finish the network game: {}", error);
+                self.session = None;
+                false
             }
+        }
+    }
+}
 
-            // accelerate ball
-            let ratio = self.ball_velocity[0] / (self.ball_velocity[0] + self.ball_velocity[1]);
-            self.ball_velocity[0] += ratio * self.ball_velocity[0].signum() * SPEED_INCREASE * engine.delta;
-            self.ball_velocity[1] += (1.0 - ratio) * self.ball_velocity[1].signum() * SPEED_INCREASE * engine.delta;
+impl Scene for MatchOver {
+    fn tick(&mut self, engine: &VNEngineState, _dt: f64) -> SceneCommand {
+        self.waiting = self.sending();
+        if self.waiting {
+            return SceneCommand::None;
+        }
+        if let Mode::Network(_) = self.mode {
+            if engine.input().key_pressed(Keycode::Space) || engine.input().key_pressed(Keycode::Escape) {
+                return SceneCommand::ReplaceAll(Box::new(Title::new(engine))).fade(BLACK, FADE_DURATION);
+            }
+        } else if engine.input().key_pressed(Keycode::Space) || engine.input().key_pressed(Keycode::R) {
+            return SceneCommand::ReplaceAll(Box::new(Game::new(self.mode, engine))).fade(BLACK, FADE_DURATION);
         }
+        SceneCommand::None
+    }
+
+    fn render(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, _alpha: f64) {
+        let title = match (self.mode, self.winner) {
+            (Mode::Computer(_), Side::Left) => "COMPUTER WINS",
+            (Mode::Computer(_), Side::Right) => "YOU WIN",
+            (Mode::Network(side), winner) if side == winner => "YOU WIN",
+            (Mode::Network(_), _) => "YOU LOSE",
+            (_, Side::Left) => "LEFT WINS",
+            (_, Side::Right) => "RIGHT WINS",
+        };
+        let text = match self.mode {
+            Mode::Network(_) if self.waiting => "waiting for the other player",
+            Mode::Network(_) => "press space to leave",
+            _ => "press space to play again",
+        };
+        draw_banner(renderer, engine, title, text);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
     }
 }
 
+/// Waits for the other machine on `port`
+fn host(port: &str, delay: &str) -> Session {
+    let port = port.parse().expect("Failed to parse the port!");
+    let delay = delay.parse().expect("Failed to parse the input delay!");
+    println!("Waiting for a player on port {}", port);
+    Session::host(port, delay).expect("Failed to host the game!")
+}
+
+/// `pong --record session.vnin` logs the input of a session, `pong --replay session.vnin` plays it
+/// back exactly. `pong --host 7777` and `pong --join localhost:7777` play over the network, the
+/// host can set the input delay in frames with `--delay 6`.
 fn main() {
-    let mut state = State {
-        right_paddle: 200.0,
-        left_paddle: 200.0,
-        ball_pos: [300.0, 200.0],
-        ball_velocity: [100.0, 20.0],
-        stop: false,
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    // the other machine is found before the window opens
+    let session = match args[..] {
+        ["--host", port] => Some(host(port, &INPUT_DELAY.to_string())),
+        ["--host", port, "--delay", delay] => Some(host(port, delay)),
+        ["--join", address] => {
+            println!("Joining {}", address);
+            Some(Session::join(address).expect("Failed to join the game!"))
+        }
+        _ => None,
     };
+    let networked = session.is_some();
+    if let Some(session) = &session {
+        println!("Playing {:?} against {}", session.side(), session.peer());
+    }
+
     let mut engine = VNEngine::new_opengl(600, 400, 4);
-    engine.run(&mut state);
+    let first: Box<dyn Scene> = match session {
+        Some(session) => Box::new(Game::networked(session, engine.engine_state())),
+        None => Box::new(Title::new(engine.engine_state())),
+    };
+    let mut scenes = SceneManager::new(first);
+    engine.set_fixed_timestep(Some(FixedTimestep::new(1.0 / 120.0)));
+    engine.set_frame_rate_cap(Some(144.0));
+    engine.set_resizable(true);
+    // the other machine does not wait
+    engine.set_pause_on_focus_loss(!networked);
+    // the engine only reaches the audio device on linux
+    #[cfg(target_os = "linux")]
+    match Audio::device() {
+        Ok(audio) => engine.set_audio(Some(audio)),
+        Err(error) => eprintln!("Playing without sound: {:?}", error),
+    }
+    match args[..] {
+        ["--record", path] => {
+            engine.record_input();
+            engine.run(&mut scenes);
+            let log = engine.input_log().expect("Failed to record the input!");
+            log.save(path).expect("Failed to save the input log!");
+        }
+        ["--replay", path] => {
+            engine.replay_input(InputLog::load(path).expect("Failed to load the input log!"));
+            engine.run(&mut scenes);
+        }
+        [] => engine.run(&mut scenes),
+        _ if networked => engine.run(&mut scenes),
+        _ => eprintln!("usage: pong [--record <file> | --replay <file> | --host <port> [--delay <frames>] | --join <address>]"),
+    }
 }
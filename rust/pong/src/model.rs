@@ -0,0 +1,439 @@
+use crate::ai::Court;
+
+pub const BALL_RADIUS: i32 = 2;
+pub const PADDLE_HEIGHT: i32 = 50;
+/// space between the window border and the field
+pub const MARGIN: i32 = 25;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ball {
+    pub position: [f64; 2],
+    /// in pixels per second
+    pub velocity: [f64; 2],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rules {
+    /// a side wins with at least this many points and `win_by` more than the other side
+    pub points_to_win: u32,
+    pub win_by: u32,
+    /// seconds the ball rests in the middle before it is served
+    pub serve_delay: f64,
+    /// horizontal and vertical speed of a served ball
+    pub serve_velocity: [f64; 2],
+    /// how much faster the ball gets per second
+    pub speed_increase: f64,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            points_to_win: 11,
+            win_by: 2,
+            serve_delay: 1.0,
+            serve_velocity: [100.0, 20.0],
+            speed_increase: 10.0,
+        }
+    }
+}
+
+/// What happened during [Match::step]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    WallBounce,
+    PaddleHit(Side),
+    /// the side scored
+    Point(Side),
+    /// the side scored the last point of the match, nothing moves anymore
+    Won(Side),
+}
+
+/// A match of pong on a field of the given size, without input or drawing
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub rules: Rules,
+    width: u32,
+    height: u32,
+    ball: Ball,
+    previous_ball: [f64; 2],
+    /// vertical centers, left then right
+    paddles: [f64; 2],
+    scores: [u32; 2],
+    serve: Option<Serve>,
+    winner: Option<Side>,
+}
+
+/// the ball waits `delay` seconds in the middle, then flies towards `receiver`
+#[derive(Copy, Clone, Debug)]
+struct Serve {
+    receiver: Side,
+    delay: f64,
+}
+
+impl Match {
+    /// The first serve goes to the right
+    pub fn new(width: u32, height: u32, rules: Rules) -> Match {
+        let center = [width as f64 / 2.0, height as f64 / 2.0];
+        Match {
+            rules,
+            width,
+            height,
+            ball: Ball {
+                position: center,
+                velocity: [0.0, 0.0],
+            },
+            previous_ball: center,
+            paddles: [center[1]; 2],
+            scores: [0, 0],
+            serve: Some(Serve {
+                receiver: Side::Right,
+                delay: rules.serve_delay,
+            }),
+            winner: None,
+        }
+    }
+
+    pub fn ball(&self) -> Ball {
+        self.ball
+    }
+
+    /// the ball before the last step, to draw it in between
+    pub fn previous_ball(&self) -> [f64; 2] {
+        self.previous_ball
+    }
+
+    /// vertical center of the paddle
+    pub fn paddle(&self, side: Side) -> f64 {
+        self.paddles[side.index()]
+    }
+
+    /// where the center of the ball is when it bounces off the paddle
+    pub fn paddle_x(&self, side: Side) -> f64 {
+        match side {
+            Side::Left => (self.left_edge() + BALL_RADIUS * 2) as f64,
+            Side::Right => (self.right_edge() - BALL_RADIUS * 2) as f64,
+        }
+    }
+
+    pub fn score(&self, side: Side) -> u32 {
+        self.scores[side.index()]
+    }
+
+    /// the range of the ball's center between the walls
+    pub fn court(&self) -> Court {
+        Court {
+            top: (self.top_edge() + BALL_RADIUS) as f64,
+            bottom: (self.bottom_edge() - BALL_RADIUS) as f64,
+        }
+    }
+
+    pub fn left_edge(&self) -> i32 {
+        MARGIN
+    }
+
+    pub fn right_edge(&self) -> i32 {
+        self.width as i32 - MARGIN
+    }
+
+    pub fn top_edge(&self) -> i32 {
+        MARGIN
+    }
+
+    pub fn bottom_edge(&self) -> i32 {
+        self.height as i32 - MARGIN
+    }
+
+    /// A hash of everything that changes during the match, equal on all machines that simulated
+    /// the same steps
+    pub fn state_hash(&self) -> u64 {
+        let serve = self.serve.map_or([0.0, -1.0], |serve| {
+            [serve.delay, serve.receiver.index() as f64]
+        });
+        let mut data = Vec::new();
+        for value in [self.ball.position, self.ball.velocity, self.paddles, serve] {
+            for value in value {
+                data.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+        }
+        for value in [
+            self.scores[0],
+            self.scores[1],
+            self.winner.map_or(2, |side| side.index() as u32),
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // FNV-1a, unlike the hashers of std it is the same in every build
+        data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Advances the match by `dt` seconds with the paddles moving at `paddle_velocities`, left
+    /// then right, in pixels per second
+    pub fn step(&mut self, paddle_velocities: [f64; 2], dt: f64) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.winner.is_some() {
+            return events;
+        }
+        self.previous_ball = self.ball.position;
+
+        // update paddle positions
+        let half_paddle = (PADDLE_HEIGHT / 2) as f64;
+        let (top, bottom) = (self.top_edge() as f64, self.bottom_edge() as f64);
+        for (paddle, velocity) in self.paddles.iter_mut().zip(paddle_velocities) {
+            *paddle = (*paddle + velocity * dt).clamp(top + half_paddle, bottom - half_paddle);
+        }
+
+        if let Some(serve) = &mut self.serve {
+            serve.delay -= dt;
+            if serve.delay <= 0.0 {
+                let [x, y] = self.rules.serve_velocity;
+                // alternate between serving up and down
+                let total = self.scores[0] + self.scores[1];
+                self.ball.velocity = [
+                    if serve.receiver == Side::Left { -x } else { x },
+                    if total.is_multiple_of(2) { y } else { -y },
+                ];
+                self.serve = None;
+            }
+            return events;
+        }
+
+        // update ball position
+        self.ball.position[0] += self.ball.velocity[0] * dt;
+        self.ball.position[1] += self.ball.velocity[1] * dt;
+        let [x, y] = self.ball.position;
+        let court = self.court();
+
+        // bounce bottom
+        if y >= court.bottom && self.ball.velocity[1] > 0.0 {
+            self.ball.velocity[1] = -self.ball.velocity[1];
+            events.push(Event::WallBounce);
+        }
+
+        // bounce top
+        if y <= court.top && self.ball.velocity[1] < 0.0 {
+            self.ball.velocity[1] = -self.ball.velocity[1];
+            events.push(Event::WallBounce);
+        }
+
+        // bounce off the paddles, once the ball reached them however fast it is
+        for side in [Side::Left, Side::Right] {
+            let paddle_x = self.paddle_x(side);
+            let reached = match side {
+                Side::Left => x <= paddle_x && self.ball.velocity[0] < 0.0,
+                Side::Right => x >= paddle_x && self.ball.velocity[0] > 0.0,
+            };
+            if reached && (y - self.paddle(side)).abs() <= half_paddle {
+                self.ball.velocity[0] = -self.ball.velocity[0];
+                self.ball.position[0] = paddle_x;
+                events.push(Event::PaddleHit(side));
+            }
+        }
+
+        // the ball left the field
+        let (x, radius) = (self.ball.position[0], BALL_RADIUS as f64);
+        let scorer = if x + radius > self.right_edge() as f64 {
+            Some(Side::Left)
+        } else if x - radius < self.left_edge() as f64 {
+            Some(Side::Right)
+        } else {
+            None
+        };
+        if let Some(scorer) = scorer {
+            events.push(self.score_point(scorer));
+            return events;
+        }
+
+        // accelerate ball
+        let [vx, vy] = self.ball.velocity;
+        let ratio = vx.abs() / (vx.abs() + vy.abs());
+        let increase = self.rules.speed_increase * dt;
+        self.ball.velocity[0] += ratio * vx.signum() * increase;
+        self.ball.velocity[1] += (1.0 - ratio) * vy.signum() * increase;
+        events
+    }
+
+    /// Gives `scorer` a point and either ends the match or serves towards the other side
+    fn score_point(&mut self, scorer: Side) -> Event {
+        self.scores[scorer.index()] += 1;
+        let (score, other) = (self.score(scorer), self.score(scorer.other()));
+        let center = [self.width as f64 / 2.0, self.height as f64 / 2.0];
+        self.ball = Ball {
+            position: center,
+            velocity: [0.0, 0.0],
+        };
+        self.previous_ball = center;
+
+        if score >= self.rules.points_to_win && score >= other + self.rules.win_by {
+            self.winner = Some(scorer);
+            return Event::Won(scorer);
+        }
+        self.serve = Some(Serve {
+            receiver: scorer.other(),
+            delay: self.rules.serve_delay,
+        });
+        Event::Point(scorer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 1.0 / 120.0;
+
+    /// Sends the ball past the paddle of the other side of `scorer`, far from it, and steps until
+    /// the point is over
+    fn play_point(game: &mut Match, scorer: Side) -> Event {
+        game.serve = None;
+        let (x, vx) = match scorer {
+            Side::Left => (game.right_edge() as f64 - 10.0, 200.0),
+            Side::Right => (game.left_edge() as f64 + 10.0, -200.0),
+        };
+        game.ball = Ball {
+            position: [x, game.top_edge() as f64 + 10.0],
+            velocity: [vx, 0.0],
+        };
+        for _ in 0..120 {
+            for event in game.step([0.0, 0.0], DT) {
+                if let Event::Point(_) | Event::Won(_) = event {
+                    return event;
+                }
+            }
+        }
+        panic!("no point was scored");
+    }
+
+    /// Waits for the serve and returns the velocity of the served ball
+    fn serve(game: &mut Match) -> [f64; 2] {
+        assert_eq!(game.ball().velocity, [0.0, 0.0]);
+        game.step([0.0, 0.0], game.rules.serve_delay);
+        game.ball().velocity
+    }
+
+    #[test]
+    fn serves_go_to_the_side_that_lost_the_point() {
+        let mut game = Match::new(600, 400, Rules::default());
+        assert_eq!(serve(&mut game), [100.0, 20.0]);
+
+        assert_eq!(
+            play_point(&mut game, Side::Right),
+            Event::Point(Side::Right)
+        );
+        assert_eq!(game.ball().position, [300.0, 200.0]);
+        assert_eq!(serve(&mut game), [-100.0, -20.0]);
+
+        assert_eq!(play_point(&mut game, Side::Left), Event::Point(Side::Left));
+        assert_eq!(serve(&mut game), [100.0, 20.0]);
+
+        assert_eq!(play_point(&mut game, Side::Left), Event::Point(Side::Left));
+        assert_eq!(serve(&mut game), [100.0, -20.0]);
+        assert_eq!((game.score(Side::Left), game.score(Side::Right)), (2, 1));
+    }
+
+    #[test]
+    fn matches_are_won_by_two() {
+        let mut game = Match::new(600, 400, Rules::default());
+        game.scores = [10, 10];
+        assert_eq!(play_point(&mut game, Side::Left), Event::Point(Side::Left));
+        assert_eq!(
+            play_point(&mut game, Side::Right),
+            Event::Point(Side::Right)
+        );
+        assert_eq!(
+            play_point(&mut game, Side::Right),
+            Event::Point(Side::Right)
+        );
+        assert_eq!(play_point(&mut game, Side::Right), Event::Won(Side::Right));
+        assert_eq!((game.score(Side::Left), game.score(Side::Right)), (11, 13));
+
+        let mut game = Match::new(600, 400, Rules::default());
+        game.scores = [10, 0];
+        assert_eq!(play_point(&mut game, Side::Left), Event::Won(Side::Left));
+    }
+
+    #[test]
+    fn nothing_moves_after_the_match() {
+        let mut game = Match::new(600, 400, Rules::default());
+        game.scores = [0, 10];
+        assert_eq!(play_point(&mut game, Side::Right), Event::Won(Side::Right));
+        let hash = game.state_hash();
+        for _ in 0..240 {
+            assert_eq!(game.step([100.0, -100.0], DT), vec![]);
+        }
+        assert_eq!(game.state_hash(), hash);
+        assert_eq!(game.ball().velocity, [0.0, 0.0]);
+        assert_eq!(game.paddle(Side::Left), 200.0);
+    }
+
+    #[test]
+    fn paddles_stay_on_the_field() {
+        let mut game = Match::new(600, 400, Rules::default());
+        let half_paddle = (PADDLE_HEIGHT / 2) as f64;
+        game.step([-1e6, 1e6], DT);
+        assert_eq!(
+            game.paddle(Side::Left),
+            game.top_edge() as f64 + half_paddle
+        );
+        assert_eq!(
+            game.paddle(Side::Right),
+            game.bottom_edge() as f64 - half_paddle
+        );
+        game.step([1e6, 0.0], DT);
+        assert_eq!(
+            game.paddle(Side::Left),
+            game.bottom_edge() as f64 - half_paddle
+        );
+        assert_eq!(
+            game.paddle(Side::Right),
+            game.bottom_edge() as f64 - half_paddle
+        );
+    }
+
+    #[test]
+    fn equal_steps_hash_equal() {
+        let velocities = |step: u32| match step % 90 {
+            0..=29 => [150.0, -150.0],
+            30..=59 => [0.0, 150.0],
+            _ => [-150.0, 0.0],
+        };
+        let mut first = Match::new(600, 400, Rules::default());
+        let mut second = first.clone();
+        for step in 0..5000 {
+            assert_eq!(
+                first.step(velocities(step), DT),
+                second.step(velocities(step), DT)
+            );
+            assert_eq!(first.state_hash(), second.state_hash());
+        }
+
+        let hash = first.state_hash();
+        second.step([1.0, 0.0], DT);
+        first.step([0.0, 0.0], DT);
+        assert_ne!(first.state_hash(), second.state_hash());
+        assert_ne!(first.state_hash(), hash);
+    }
+}
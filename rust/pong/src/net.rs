@@ -0,0 +1,593 @@
+use crate::model::Side;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// bits of the input of one frame
+pub const UP: u8 = 1;
+pub const DOWN: u8 = 2;
+
+/// frames between comparisons of the game state
+const HASH_INTERVAL: u32 = 60;
+/// the connection is lost after this long without a packet from the other side
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// how often a joining player repeats its hello
+const RETRY: Duration = Duration::from_millis(250);
+/// at most this many inputs go into one packet
+const MAX_INPUTS: u32 = 128;
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    /// nothing arrived from the other side for a while
+    Timeout,
+    /// the other side left
+    Disconnected,
+    /// both sides computed a different game state for the frame
+    Desync(u32),
+}
+
+impl From<io::Error> for NetError {
+    fn from(value: io::Error) -> Self {
+        NetError::Io(value)
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(error) => write!(f, "network error: {}", error),
+            NetError::Timeout => write!(f, "the other player stopped answering"),
+            NetError::Disconnected => write!(f, "the other player left"),
+            NetError::Desync(frame) => write!(f, "the games went out of sync at frame {}", frame),
+        }
+    }
+}
+
+/// A game between two machines in lockstep: only inputs are exchanged, and a frame is simulated
+/// once the inputs of both sides for it are there. Local input is used `delay` frames after it
+/// was sampled, so it usually arrived on the other side by then and nobody waits.
+pub struct Session {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    side: Side,
+    delay: u32,
+    /// the next frame to simulate
+    frame: u32,
+    /// own inputs the other side may still need or that were not simulated yet
+    local: BTreeMap<u32, u8>,
+    /// the next frame local input is sampled for
+    next_local: u32,
+    /// the other side has all own inputs before this frame
+    peer_ack: u32,
+    remote: BTreeMap<u32, u8>,
+    /// all remote inputs before this frame arrived
+    next_remote: u32,
+    /// state hashes waiting for the one of the other side for the same frame
+    hashes: BTreeMap<u32, u64>,
+    remote_hashes: BTreeMap<u32, u64>,
+    last_received: Instant,
+    /// the other side said bye, its inputs that already arrived are still played
+    peer_left: bool,
+    closed: bool,
+}
+
+impl Session {
+    /// Waits until a player joins on `port`, the host plays left and decides the input delay
+    pub fn host(port: u16, delay: u32) -> Result<Session, NetError> {
+        Session::accept(UdpSocket::bind(("0.0.0.0", port))?, delay)
+    }
+
+    /// [Session::host] on a bound socket
+    fn accept(socket: UdpSocket, delay: u32) -> Result<Session, NetError> {
+        let mut buffer = [0; 512];
+        loop {
+            let (size, peer) = socket.recv_from(&mut buffer)?;
+            match Message::decode(&buffer[..size]) {
+                Some(Message::Hello) => {
+                    socket.send_to(&Message::Welcome { delay }.encode(), peer)?;
+                    return Session::new(socket, peer, Side::Left, delay);
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Joins the game hosted at `address`, playing right
+    pub fn join(address: impl ToSocketAddrs) -> Result<Session, NetError> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        let host = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unknown host"))?;
+        socket.set_read_timeout(Some(RETRY))?;
+        let start = Instant::now();
+        let mut buffer = [0; 512];
+        while start.elapsed() < TIMEOUT {
+            socket.send_to(&Message::Hello.encode(), host)?;
+            let (size, peer) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if is_retry(&error) => continue,
+                Err(error) => return Err(error.into()),
+            };
+            if let Some(Message::Welcome { delay }) = Message::decode(&buffer[..size]) {
+                return Session::new(socket, peer, Side::Right, delay);
+            }
+        }
+        Err(NetError::Timeout)
+    }
+
+    fn new(
+        socket: UdpSocket,
+        peer: SocketAddr,
+        side: Side,
+        delay: u32,
+    ) -> Result<Session, NetError> {
+        socket.set_nonblocking(true)?;
+        Ok(Session {
+            socket,
+            peer,
+            side,
+            delay,
+            frame: 0,
+            // nobody moves before the first input arrives
+            local: (0..delay).map(|frame| (frame, 0)).collect(),
+            next_local: delay,
+            peer_ack: 0,
+            remote: BTreeMap::new(),
+            next_remote: 0,
+            hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            last_received: Instant::now(),
+            peer_left: false,
+            closed: false,
+        })
+    }
+
+    /// the paddle of this machine
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Sends `input` for a later frame and returns the inputs of both sides for the next frame,
+    /// left then right, or `None` if the other side's have not arrived yet
+    pub fn advance(&mut self, input: u8) -> Result<Option<[u8; 2]>, NetError> {
+        self.receive()?;
+        while self.next_local <= self.frame + self.delay {
+            self.local.insert(self.next_local, input);
+            self.next_local += 1;
+        }
+        self.send_inputs()?;
+
+        let remote = match self.remote.remove(&self.frame) {
+            Some(remote) => remote,
+            None if self.peer_left => return Err(NetError::Disconnected),
+            None if self.last_received.elapsed() > TIMEOUT => return Err(NetError::Timeout),
+            None => return Ok(None),
+        };
+        let own = self.local[&self.frame];
+        self.frame += 1;
+        let needed = self.frame.min(self.peer_ack);
+        self.local.retain(|frame, _| *frame >= needed);
+        Ok(Some(match self.side {
+            Side::Left => [own, remote],
+            Side::Right => [remote, own],
+        }))
+    }
+
+    /// Compares the hash of the state after the last simulated frame with the other side's,
+    /// every few frames
+    pub fn check_state(&mut self, hash: u64) -> Result<(), NetError> {
+        // nothing was simulated yet
+        if self.frame == 0 {
+            return Ok(());
+        }
+        let frame = self.frame - 1;
+        if !frame.is_multiple_of(HASH_INTERVAL) {
+            return Ok(());
+        }
+        self.send(&Message::Hash { frame, hash })?;
+        match self.remote_hashes.remove(&frame) {
+            Some(remote) if remote != hash => Err(NetError::Desync(frame)),
+            Some(_) => Ok(()),
+            None => {
+                self.hashes.insert(frame, hash);
+                Ok(())
+            }
+        }
+    }
+
+    /// Keeps sending after the last frame, e.g. while the result is shown, as the other side may
+    /// still miss inputs of this one. True once it has all of them or left, from then on
+    /// [Session::close] does not cut it short.
+    pub fn finish(&mut self) -> Result<bool, NetError> {
+        self.receive()?;
+        if self.peer_left {
+            return Ok(true);
+        }
+        // the acknowledgement of the other side's inputs goes along
+        self.send_inputs()?;
+        if self.peer_ack >= self.frame {
+            return Ok(true);
+        }
+        if self.last_received.elapsed() > TIMEOUT {
+            return Err(NetError::Timeout);
+        }
+        Ok(false)
+    }
+
+    /// Tells the other side that this one leaves, also happens when the session is dropped
+    pub fn close(&mut self) {
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        // without an answer there is no telling whether it arrived
+        for _ in 0..3 {
+            let _ = self.send(&Message::Bye);
+        }
+    }
+
+    fn receive(&mut self) -> Result<(), NetError> {
+        let mut buffer = [0; 512];
+        loop {
+            let (size, peer) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if is_retry(&error) => return Ok(()),
+                Err(error) => return Err(error.into()),
+            };
+            let message = match Message::decode(&buffer[..size]) {
+                Some(message) if peer == self.peer => message,
+                _ => continue,
+            };
+            self.last_received = Instant::now();
+            match message {
+                // the welcome got lost
+                Message::Hello => self.send(&Message::Welcome { delay: self.delay })?,
+                Message::Welcome { .. } => {}
+                Message::Inputs { ack, first, inputs } => {
+                    // nothing beyond the sampled inputs can have arrived, the packet is bad
+                    if ack > self.next_local {
+                        continue;
+                    }
+                    self.peer_ack = self.peer_ack.max(ack);
+                    let frames = first..first.saturating_add(inputs.len() as u32);
+                    for (frame, input) in frames.zip(inputs) {
+                        if frame >= self.next_remote {
+                            self.remote.insert(frame, input);
+                        }
+                    }
+                    while self.remote.contains_key(&self.next_remote) {
+                        self.next_remote += 1;
+                    }
+                }
+                Message::Hash { frame, hash } => {
+                    // older hashes of this side will never be answered, theirs got lost
+                    self.hashes.retain(|own, _| *own >= frame);
+                    match self.hashes.remove(&frame) {
+                        Some(own) if own != hash => return Err(NetError::Desync(frame)),
+                        Some(_) => {}
+                        None => {
+                            self.remote_hashes.insert(frame, hash);
+                        }
+                    }
+                }
+                Message::Bye => self.peer_left = true,
+            }
+        }
+    }
+
+    /// Sends every input the other side does not have yet, so a lost packet is made up for by
+    /// the next one
+    fn send_inputs(&mut self) -> Result<(), NetError> {
+        let first = self.peer_ack;
+        let last = self.next_local.min(first.saturating_add(MAX_INPUTS));
+        let inputs = (first..last).map(|frame| self.local[&frame]).collect();
+        let ack = self.next_remote;
+        self.send(&Message::Inputs { ack, first, inputs })
+    }
+
+    fn send(&self, message: &Message) -> Result<(), NetError> {
+        match self.socket.send_to(&message.encode(), self.peer) {
+            Ok(_) => Ok(()),
+            // the other side is not listening (yet), the timeout notices if it stays that way
+            Err(error) if is_retry(&error) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn is_retry(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+////////////
+// FORMAT //
+////////////
+
+// All numbers are little endian:
+//
+//   "VNPG" version:u8 tag:u8 followed by the fields of the message
+//   inputs:  ack:u32 first:u32 count:u8 input:u8*
+
+const MAGIC: &[u8; 4] = b"VNPG";
+const VERSION: u8 = 1;
+
+const HELLO: u8 = 0;
+const WELCOME: u8 = 1;
+const INPUTS: u8 = 2;
+const HASH: u8 = 3;
+const BYE: u8 = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Message {
+    Hello,
+    Welcome {
+        delay: u32,
+    },
+    /// inputs from frame `first` on, `ack` is the first frame missing from the other side
+    Inputs {
+        ack: u32,
+        first: u32,
+        inputs: Vec<u8>,
+    },
+    Hash {
+        frame: u32,
+        hash: u64,
+    },
+    Bye,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        match self {
+            Message::Hello => data.push(HELLO),
+            Message::Welcome { delay } => {
+                data.push(WELCOME);
+                data.extend_from_slice(&delay.to_le_bytes());
+            }
+            Message::Inputs { ack, first, inputs } => {
+                data.push(INPUTS);
+                data.extend_from_slice(&ack.to_le_bytes());
+                data.extend_from_slice(&first.to_le_bytes());
+                data.push(inputs.len() as u8);
+                data.extend_from_slice(inputs);
+            }
+            Message::Hash { frame, hash } => {
+                data.push(HASH);
+                data.extend_from_slice(&frame.to_le_bytes());
+                data.extend_from_slice(&hash.to_le_bytes());
+            }
+            Message::Bye => data.push(BYE),
+        }
+        data
+    }
+
+    /// `None` for anything that is not a message of this version, e.g. from other programs
+    fn decode(data: &[u8]) -> Option<Message> {
+        if data.len() < 6 || &data[..4] != MAGIC || data[4] != VERSION {
+            return None;
+        }
+        let fields = &data[6..];
+        let u32_at = |offset: usize| {
+            let bytes = fields.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?))
+        };
+        Some(match data[5] {
+            HELLO => Message::Hello,
+            WELCOME => Message::Welcome { delay: u32_at(0)? },
+            INPUTS => {
+                let count = *fields.get(8)? as usize;
+                Message::Inputs {
+                    ack: u32_at(0)?,
+                    first: u32_at(4)?,
+                    inputs: fields.get(9..9 + count)?.to_vec(),
+                }
+            }
+            HASH => Message::Hash {
+                frame: u32_at(0)?,
+                hash: u64::from_le_bytes(fields.get(4..12)?.try_into().ok()?),
+            },
+            BYE => Message::Bye,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Event, Match, Rules};
+    use std::thread;
+
+    const DT: f64 = 1.0 / 120.0;
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Hello,
+            Message::Welcome { delay: 6 },
+            Message::Inputs {
+                ack: 0,
+                first: 0,
+                inputs: Vec::new(),
+            },
+            Message::Inputs {
+                ack: 70_000,
+                first: u32::MAX - 1,
+                inputs: vec![UP; MAX_INPUTS as usize],
+            },
+            Message::Hash {
+                frame: 60,
+                hash: 0x0123_4567_89ab_cdef,
+            },
+            Message::Bye,
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn other_packets_are_ignored() {
+        assert_eq!(Message::decode(b""), None);
+        assert_eq!(Message::decode(b"VNPG"), None);
+        assert_eq!(Message::decode(b"PNG\x01\x00\x00"), None);
+        assert_eq!(
+            Message::decode(&[b'V', b'N', b'P', b'G', VERSION + 1, HELLO]),
+            None
+        );
+        assert_eq!(
+            Message::decode(&[b'V', b'N', b'P', b'G', VERSION, 99]),
+            None
+        );
+
+        let inputs = Message::Inputs {
+            ack: 1,
+            first: 2,
+            inputs: vec![UP, DOWN, 0],
+        }
+        .encode();
+        let hash = Message::Hash { frame: 60, hash: 1 }.encode();
+        for data in [&inputs, &hash, &Message::Welcome { delay: 6 }.encode()] {
+            for size in 6..data.len() {
+                assert_eq!(Message::decode(&data[..size]), None, "{:?}", &data[..size]);
+            }
+        }
+    }
+
+    /// Host and joining player on localhost
+    fn connect(delay: u32) -> (Session, Session) {
+        let socket = UdpSocket::bind(("127.0.0.1", 0)).unwrap();
+        let address = socket.local_addr().unwrap();
+        let host = thread::spawn(move || Session::accept(socket, delay).unwrap());
+        let joined = Session::join(address).unwrap();
+        (host.join().unwrap(), joined)
+    }
+
+    /// Plays a match with a random paddle, returns the winner and the state hash of every frame
+    fn play(mut session: Session, mut seed: u64) -> (Side, Vec<u64>) {
+        let rules = Rules {
+            points_to_win: 3,
+            serve_delay: 0.1,
+            serve_velocity: [300.0, 40.0],
+            ..Rules::default()
+        };
+        let mut game = Match::new(600, 400, rules);
+        let mut hashes = Vec::new();
+        let winner = 'game: loop {
+            assert!(hashes.len() < 100_000, "the match does not end");
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let inputs = match session.advance((seed % 3) as u8).unwrap() {
+                Some(inputs) => inputs,
+                None => {
+                    thread::sleep(Duration::from_micros(100));
+                    continue;
+                }
+            };
+            let velocities = inputs.map(|input| match input {
+                UP => -150.0,
+                DOWN => 150.0,
+                _ => 0.0,
+            });
+            let events = game.step(velocities, DT);
+            session.check_state(game.state_hash()).unwrap();
+            hashes.push(game.state_hash());
+            for event in events {
+                if let Event::Won(winner) = event {
+                    break 'game winner;
+                }
+            }
+        };
+        while !session.finish().unwrap() {
+            thread::sleep(Duration::from_micros(100));
+        }
+        // leaving right away says bye, the other side must still get to the end
+        (winner, hashes)
+    }
+
+    #[test]
+    fn both_sides_play_the_same_match() {
+        let (host, joined) = connect(2);
+        assert_eq!((host.side(), joined.side()), (Side::Left, Side::Right));
+        let host = thread::spawn(move || play(host, 0x9e37_79b9_7f4a_7c15));
+        let (winner, hashes) = play(joined, 0x2545_f491_4f6c_dd1d);
+        let (host_winner, host_hashes) = host.join().unwrap();
+        assert_eq!(winner, host_winner);
+        assert_eq!(hashes.len(), host_hashes.len());
+        assert!(hashes == host_hashes);
+    }
+
+    #[test]
+    fn bad_packets_do_not_break_the_session() {
+        let (mut host, mut joined) = connect(2);
+        host.check_state(0).unwrap();
+        for message in [
+            Message::Inputs {
+                ack: u32::MAX,
+                first: 0,
+                inputs: vec![UP],
+            },
+            Message::Inputs {
+                ack: 0,
+                first: u32::MAX - 1,
+                inputs: vec![DOWN; 10],
+            },
+        ] {
+            joined.send(&message).unwrap();
+        }
+        let mut frames = 0;
+        while frames < 10 {
+            joined.advance(UP).unwrap();
+            if let Some(inputs) = host.advance(0).unwrap() {
+                assert_eq!(inputs, [0, if frames < 2 { 0 } else { UP }]);
+                frames += 1;
+            }
+        }
+        assert!(host.peer_ack <= host.next_local);
+    }
+
+    #[test]
+    fn leaving_ends_the_game_on_the_other_side() {
+        let (mut host, mut joined) = connect(2);
+        let mut frames = 0;
+        while frames < 10 {
+            host.advance(0).unwrap();
+            if joined.advance(0).unwrap().is_some() {
+                frames += 1;
+            }
+        }
+        joined.close();
+        let start = Instant::now();
+        let error = loop {
+            assert!(start.elapsed() < TIMEOUT, "the host did not notice");
+            match host.advance(0) {
+                Ok(_) => thread::sleep(Duration::from_micros(100)),
+                Err(error) => break error,
+            }
+        };
+        assert!(matches!(error, NetError::Disconnected), "{:?}", error);
+    }
+}
@@ -8,9 +8,14 @@ use std::f64::consts::PI;
 use std::mem::swap;
 use std::ops::Index;
 use std::process::Output;
+use vn_engine::camera::{Camera2D, CameraRenderer, WorldPosition};
 use vn_engine::color::{BLACK, BLUE, GREEN, RED, RGBA, WHITE, YELLOW};
 use vn_engine::engine::{VNERunner, VNEngineState};
 use vn_engine::render::{PixelPosition, VNERenderer};
+use vn_engine::ui::{Ui, UiState};
+
+/// size of a hexagon in pixels
+const HEX_SCALE: f64 = 4.0;
 
 enum BufferUse {
     FIRST,
@@ -25,7 +30,11 @@ struct Runner {
     iterations: u32,
     max_iterations: u32,
     stop: bool,
+    paused: bool,
+    cluster: bool,
     settings: Vec<SimulationSetting>,
+    camera: Camera2D,
+    ui: UiState,
 }
 
 impl Runner {
@@ -40,10 +49,55 @@ impl Runner {
             iterations: 0,
             max_iterations: settings.iter().fold(0, |a, b| a + b.iterations),
             stop: false,
+            paused: false,
+            cluster,
             settings,
+            camera: Camera2D::new(0, 0),
+            ui: UiState::default(),
         }
     }
 
+    /// starts over with the current settings
+    fn restart(&mut self) {
+        let resolution = self.snowflake().resolution;
+        self.snow_flake_buffer1 = SnowFlake::new(resolution, self.settings[0].beta, self.cluster);
+        self.snow_flake_buffer2 = SnowFlake::new(resolution, self.settings[0].beta, self.cluster);
+        self.buffer_in_use = FIRST;
+        self.iterations = 0;
+        self.max_iterations = self.settings.iter().fold(0, |a, b| a + b.iterations);
+        self.stop = false;
+    }
+
+    /// the settings of the simulation, returns true if it has to restart
+    fn draw_ui(
+        &mut self,
+        engine: &VNEngineState,
+        renderer: &mut (impl VNERenderer + ?Sized),
+    ) -> bool {
+        let position = PixelPosition { x: 8, y: 8 };
+        let mut ui = Ui::new(&mut self.ui, engine.input(), renderer, position, 180);
+        ui.label(&format!("iteration {} of {}", self.iterations, self.max_iterations));
+        ui.checkbox("paused", &mut self.paused);
+        let mut restart = ui.button("restart");
+        let settings = &mut self.settings;
+        ui.panel("settings", |ui| {
+            for (idx, setting) in settings.iter_mut().enumerate() {
+                ui.panel(&format!("setting {}", idx), |ui| {
+                    ui.slider("alpha", &mut setting.alpha, 0.0..=4.0);
+                    ui.slider("beta", &mut setting.beta, 0.0..=1.0);
+                    ui.number("gamma", &mut setting.gamma, 0.0001);
+                    let mut iterations = setting.iterations as f64;
+                    if ui.number("iterations", &mut iterations, 5.0) {
+                        setting.iterations = iterations.round().max(0.0) as u32;
+                        // the total changed
+                        restart = true;
+                    }
+                });
+            }
+        });
+        restart
+    }
+
     /// (current, buffer)
     pub fn snowflake_data(&mut self) -> (&SnowFlake, &mut SnowFlake) {
         match &self.buffer_in_use {
@@ -364,99 +418,99 @@ fn rotate(p1: (f64, f64), angle: f64) -> (f64, f64) {
 }
 
 impl Runner {
-    fn compute_hex_vertices(&self, pos: (u32, u32), scale: f64) -> [PixelPosition; 6] {
+    /// the corners of a hexagon with a radius of one world unit
+    fn compute_hex_vertices(&self, pos: (u32, u32)) -> [WorldPosition; 6] {
         const ONE_SIXTH: f64 = PI / 3.0;
 
-        let t_offset: (f64, f64) = (0.0, scale);
+        let t_offset: (f64, f64) = (0.0, 1.0);
         let tl_offset: (f64, f64) = rotate(t_offset, ONE_SIXTH);
         let bl_offset: (f64, f64) = rotate(tl_offset, ONE_SIXTH);
         let b_offset: (f64, f64) = rotate(bl_offset, ONE_SIXTH);
         let br_offset: (f64, f64) = rotate(b_offset, ONE_SIXTH);
         let tr_offset: (f64, f64) = rotate(br_offset, ONE_SIXTH);
 
-        let (x, y) = pos;
-
-        let x_offset = if y % 2 == 0 {
-            0.0
-        } else {
-            scale * (3.0_f64).sqrt() * 0.5
-        };
-
-        let x = (x as f64 * scale * (3.0_f64).sqrt() + x_offset);
-        let y = (y as f64 * scale * 0.75 * 2.0);
-
-        let t = PixelPosition {
-            x: (x + t_offset.0).round() as u32,
-            y: (y + t_offset.1).round() as u32,
-        };
-
-        let tr = PixelPosition {
-            x: (x + tr_offset.0).round() as u32,
-            y: (y + tr_offset.1).round() as u32,
-        };
+        let (x, y) = hex_center(pos);
 
-        let br = PixelPosition {
-            x: (x + br_offset.0).round() as u32,
-            y: (y + br_offset.1).round() as u32,
-        };
-
-        let b = PixelPosition {
-            x: (x + b_offset.0).round() as u32,
-            y: (y + b_offset.1).round() as u32,
-        };
-
-        let bl = PixelPosition {
-            x: (x + bl_offset.0).round() as u32,
-            y: (y + bl_offset.1).round() as u32,
-        };
+        let corner = |(dx, dy): (f64, f64)| WorldPosition { x: x + dx, y: y + dy };
 
-        let tl = PixelPosition {
-            x: (x + tl_offset.0).round() as u32,
-            y: (y + tl_offset.1).round() as u32,
-        };
-
-        [t, tr, br, b, bl, tl]
+        [corner(t_offset), corner(tr_offset), corner(br_offset), corner(b_offset), corner(bl_offset), corner(tl_offset)]
     }
 
     fn fill_hex(
         &self,
         pos: (u32, u32),
-        scale: f64,
         color: RGBA,
-        renderer: &mut (impl VNERenderer + ?Sized),
+        renderer: &mut CameraRenderer<impl VNERenderer + ?Sized>,
     ) {
-        let [t, tr, br, b, bl, tl] = self.compute_hex_vertices(pos, scale);
-
-        renderer.fill_triangle(tl, t, tr, color);
-        renderer.fill_triangle(tl, tr, br, color);
-        renderer.fill_triangle(tl, bl, br, color);
-        renderer.fill_triangle(bl, b, br, color);
+        renderer.fill_polygon(&self.compute_hex_vertices(pos), color);
     }
 
     fn draw_hex(
         &self,
         pos: (u32, u32),
-        scale: f64,
         color: RGBA,
-        renderer: &mut (impl VNERenderer + ?Sized),
+        renderer: &mut CameraRenderer<impl VNERenderer + ?Sized>,
     ) {
-        let [t, tr, br, b, bl, tl] = self.compute_hex_vertices(pos, scale);
-
-        renderer.draw_line(t, tl, color);
-        renderer.draw_line(tl, bl, color);
-        renderer.draw_line(bl, b, color);
-        renderer.draw_line(b, br, color);
-        renderer.draw_line(br, tr, color);
-        renderer.draw_line(tr, t, color);
+        renderer.draw_polygon(&self.compute_hex_vertices(pos), color);
     }
 }
 
+/// every other row is shifted by half a hexagon
+fn hex_center((x, y): (u32, u32)) -> (f64, f64) {
+    let x_offset = if y % 2 == 0 {
+        0.0
+    } else {
+        (3.0_f64).sqrt() * 0.5
+    };
+    (x as f64 * (3.0_f64).sqrt() + x_offset, y as f64 * 1.5)
+}
+
+/// the cell whose center is closest to `position`
+fn hex_at(position: WorldPosition) -> Option<(u32, u32)> {
+    let row = (position.y / 1.5).round();
+    let mut closest = None;
+    let mut closest_distance = f64::MAX;
+    for y in [row - 1.0, row, row + 1.0] {
+        if y < 0.0 {
+            continue;
+        }
+        let x_offset = if y as u32 % 2 == 0 { 0.0 } else { (3.0_f64).sqrt() * 0.5 };
+        let x = ((position.x - x_offset) / (3.0_f64).sqrt()).round();
+        if x < 0.0 {
+            continue;
+        }
+        let (cx, cy) = hex_center((x as u32, y as u32));
+        let distance = (cx - position.x).powi(2) + (cy - position.y).powi(2);
+        if distance < closest_distance {
+            closest_distance = distance;
+            closest = Some((x as u32, y as u32));
+        }
+    }
+    closest
+}
+
 impl VNERunner for Runner {
-    fn setup(&mut self, _engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
+    fn setup(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
         renderer.set_title("Flaky SnowFlake Generator!");
+        // the grid starts in the top left corner of the screen
+        self.camera = Camera2D::new(engine.width, engine.height);
+        self.camera.zoom = HEX_SCALE;
+        self.camera.look_at(WorldPosition {
+            x: engine.width as f64 / 2.0 / HEX_SCALE,
+            y: engine.height as f64 / 2.0 / HEX_SCALE,
+        });
     }
 
-    fn tick(&mut self, _engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
+    fn tick(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
+        // zoom towards the cursor with the mouse wheel
+        let cursor = engine.input().cursor_position();
+        let (_, scrolled) = engine.input().scroll_lines();
+        if let Some(cursor) = cursor.filter(|cursor| !self.ui.contains(*cursor)) {
+            if scrolled != 0.0 {
+                self.camera.zoom_at(cursor, 1.25_f64.powf(scrolled as f64));
+            }
+        }
+
         let mut iterations = self.iterations;
         let mut setting_nr = 0;
         for setting in self.settings.iter() {
@@ -473,7 +527,7 @@ impl VNERunner for Runner {
         }
 
         let mut stop = self.stop.clone();
-        if self.iterations < self.max_iterations && !self.stop {
+        if self.iterations < self.max_iterations && !self.stop && !self.paused {
             let mut settings = &self.settings[setting_nr];
             let alpha = settings.alpha;
             let beta = settings.beta;
@@ -538,6 +592,8 @@ impl VNERunner for Runner {
 
         renderer.clear_screen(BLACK);
 
+        let camera = self.camera;
+        let mut world = camera.renderer(renderer);
         for y in 0..self.snowflake().resolution * 2 + 1 {
             for x in 0..self.snowflake().resolution * 2 + 1 {
                 if let Some(state) = self.snowflake().grid.get(x, y) {
@@ -545,17 +601,29 @@ impl VNERunner for Runner {
                         BOUNDARY { moisture } | EDGE { moisture } | NOT_RECEPTIVE { moisture } => {
                             if !stop {
                                 let intensity = (*moisture as f32 * 0.25).min(0.25);
-                                self.fill_hex((x, y), 4.0, RGBA { r: 0.51 * intensity, g: intensity * 0.8, b: intensity, a: 1.0 }, renderer);
+                                self.fill_hex((x, y), RGBA { r: 0.51 * intensity, g: intensity * 0.8, b: intensity, a: 1.0 }, &mut world);
                             }
                         }
                         FROZEN { moisture } => {
                             let intensity = 1.0 / (*moisture as f32);
-                            self.fill_hex((x, y), 4.0, RGBA { r: intensity, g: intensity, b: intensity, a: 1.0 }, renderer);
+                            self.fill_hex((x, y), RGBA { r: intensity, g: intensity, b: intensity, a: 1.0 }, &mut world);
                         }
                     }
                 }
             }
         }
+
+        // outline the cell under the cursor
+        let hovered = cursor.filter(|cursor| !self.ui.contains(*cursor));
+        if let Some(cell) = hovered.map(|cursor| camera.to_world(cursor)).and_then(hex_at) {
+            if self.snowflake().grid.get(cell.0, cell.1).is_some() {
+                self.draw_hex(cell, RED, &mut world);
+            }
+        }
+
+        if self.draw_ui(engine, renderer) {
+            self.restart();
+        }
     }
 }
 
@@ -600,7 +668,7 @@ fn main() {
             iterations: 50,
         },
     ];
-    let mut engine = vn_engine::engine::VNEngine::new_sprite_based(1165, 1000, 2);
+    let mut engine = vn_engine::engine::VNEngine::new_sprite_based(1165, 1000, 2, true);
     let mut runner = Runner::new(28 * 3, settings, false);
     engine.run(&mut runner);
 }
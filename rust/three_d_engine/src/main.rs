@@ -347,24 +347,16 @@ impl VNERunner for Rasterizer {
         for tris in to_draw.iter() {
             renderer.fill_triangle(
                 PixelPosition {
-                    x: tris.v1.x.round() as u32,
-                    y: tris.v1.y.round() as u32,
+                    x: tris.v1.x.round() as i32,
+                    y: tris.v1.y.round() as i32,
                 },
                 PixelPosition {
-                    x: tris.v2.x.round() as u32,
-                    y: tris.v2.y.round() as u32,
+                    x: tris.v2.x.round() as i32,
+                    y: tris.v2.y.round() as i32,
                 },
                 PixelPosition {
-                    x: tris.v3.x.round() as u32,
-                    y: tris.v3.y.round() as u32,
-                },
-                PixelPosition {
-                    x: 0,
-                    y: 0,
-                },
-                PixelPosition {
-                    x: self.width,
-                    y: self.height,
+                    x: tris.v3.x.round() as i32,
+                    y: tris.v3.y.round() as i32,
                 },
                 tris.color
             );
@@ -374,7 +366,7 @@ impl VNERunner for Rasterizer {
 
 fn main() {
     let (width, height) = (256, 240);
-    let mut engine = VNEngine::new_sprite_based(width, height, 8);
+    let mut engine = VNEngine::new_sprite_based(width, height, 8, true);
     let mut runner = Rasterizer::new(width, height);
     engine.run(&mut runner)
 }
@@ -0,0 +1,165 @@
+//! Compares the span based fills of the renderers with the per pixel default methods of
+//! [VNERenderer]. The windowed renderers need a display, so this runs on the
+//! [HeadlessRenderer]. All renderers fill spans with the same function, a unit test in `shapes`
+//! checks it on the buffers of the [OpenGLRenderer] and the sprite based renderer as well.
+//!
+//! Run with `cargo bench --bench fill`.
+//!
+//! [OpenGLRenderer]: vn_engine::opengl::OpenGLRenderer
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use vn_engine::color::{BlendMode, RGBA};
+use vn_engine::headless::HeadlessRenderer;
+use vn_engine::image::Image;
+use vn_engine::render::{ClipStack, PixelPosition, VNERenderer};
+
+const WIDTH: u32 = 1165;
+const HEIGHT: u32 = 1000;
+
+/// Forwards only the required methods, so all fills use the default trait methods
+struct DefaultFills(HeadlessRenderer);
+
+impl VNERenderer for DefaultFills {
+    fn set_title(&mut self, title: &str) {
+        self.0.set_title(title);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.0.blend_mode()
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.0.set_blend_mode(mode);
+    }
+
+    fn clear_screen(&mut self, color: RGBA) {
+        self.0.clear_screen(color);
+    }
+
+    fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+        self.0.draw_pixel(position, color);
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        self.0.clip_stack()
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        self.0.clip_stack_mut()
+    }
+
+    fn capture_frame(&self) -> Image {
+        self.0.capture_frame()
+    }
+}
+
+/// The hexagons of `snow_flakes`, four triangles each
+fn hex_triangles() -> Vec<[PixelPosition; 3]> {
+    let mut triangles = Vec::new();
+    let scale = 4.0_f64;
+    for row in 0..160 {
+        for column in 0..160 {
+            let x = column as f64 * scale * 3.0_f64.sqrt() + (row % 2) as f64 * scale * 0.75;
+            let y = row as f64 * scale * 1.5;
+            let corner = |angle: f64| PixelPosition {
+                x: (x + angle.sin() * scale).round() as i32,
+                y: (y + angle.cos() * scale).round() as i32,
+            };
+            let third = std::f64::consts::PI / 3.0;
+            let [t, tl, bl, b, br, tr] = [0, 1, 2, 3, 4, 5].map(|i| corner(i as f64 * third));
+            triangles.push([tl, tr, t]);
+            triangles.push([tl, br, tr]);
+            triangles.push([tl, br, bl]);
+            triangles.push([bl, br, b]);
+        }
+    }
+    triangles
+}
+
+fn fill_triangles(renderer: &mut dyn VNERenderer, triangles: &[[PixelPosition; 3]]) {
+    let color = RGBA {
+        r: 0.4,
+        g: 0.7,
+        b: 0.9,
+        a: 0.6,
+    };
+    for [v1, v2, v3] in triangles {
+        renderer.fill_triangle(*v1, *v2, *v3, color);
+    }
+}
+
+fn fill_rectangles(renderer: &mut dyn VNERenderer) {
+    let color = RGBA {
+        r: 0.9,
+        g: 0.2,
+        b: 0.1,
+        a: 0.5,
+    };
+    renderer.fill_rectangle(
+        PixelPosition { x: 0, y: 0 },
+        PixelPosition {
+            x: WIDTH as i32 - 1,
+            y: HEIGHT as i32 - 1,
+        },
+        color,
+    );
+    for i in 0..2_000 {
+        let x = (i * 37) % WIDTH as i32 - 20;
+        let y = (i * 91) % HEIGHT as i32 - 20;
+        renderer.fill_rectangle(
+            PixelPosition { x, y },
+            PixelPosition {
+                x: x + 40,
+                y: y + 24,
+            },
+            color,
+        );
+    }
+}
+
+/// average time of one call to `run`
+fn measure(mut run: impl FnMut()) -> Duration {
+    run();
+    let mut iterations = 0;
+    let start = Instant::now();
+    while iterations < 3 || start.elapsed() < Duration::from_secs(1) {
+        run();
+        iterations += 1;
+    }
+    start.elapsed() / iterations
+}
+
+fn bench(name: &str, mode: BlendMode, draw: impl Fn(&mut dyn VNERenderer)) {
+    let mut fast = HeadlessRenderer::new(WIDTH, HEIGHT);
+    let mut slow = DefaultFills(HeadlessRenderer::new(WIDTH, HEIGHT));
+    fast.set_blend_mode(mode);
+    slow.set_blend_mode(mode);
+
+    // both paths have to produce the same image, otherwise the comparison is meaningless
+    draw(&mut fast);
+    draw(&mut slow);
+    assert!(
+        fast.pixels() == slow.0.pixels(),
+        "{}: the fast path differs from the default methods",
+        name
+    );
+
+    let slow_time = measure(|| draw(black_box(&mut slow)));
+    let fast_time = measure(|| draw(black_box(&mut fast)));
+    println!(
+        "{:<24} default {:>10.3?}   spans {:>10.3?}   {:>5.1}x",
+        format!("{} ({:?})", name, mode),
+        slow_time,
+        fast_time,
+        slow_time.as_secs_f64() / fast_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let triangles = hex_triangles();
+    for mode in [BlendMode::Replace, BlendMode::Alpha] {
+        bench("hex triangles", mode, |r| fill_triangles(r, &triangles));
+        bench("rectangles", mode, fill_rectangles);
+    }
+}
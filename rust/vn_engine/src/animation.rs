@@ -0,0 +1,460 @@
+use crate::engine::VNEngineState;
+use crate::image::ImageError;
+use crate::render::{PixelPosition, VNERenderer};
+use crate::sprite::{Sprite, SpriteOptions, SpriteRegion};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A sprite cut into frames, frames are addressed by index and optionally by name
+pub struct SpriteSheet {
+    sprite: Sprite,
+    frames: Vec<SpriteRegion>,
+    names: HashMap<String, usize>,
+    animations: HashMap<String, Animation>,
+}
+
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    Io(io::Error),
+    Image(ImageError),
+    Json(serde_json::Error),
+    Manifest(String),
+}
+
+impl From<io::Error> for SpriteSheetError {
+    fn from(value: io::Error) -> Self {
+        SpriteSheetError::Io(value)
+    }
+}
+
+impl From<ImageError> for SpriteSheetError {
+    fn from(value: ImageError) -> Self {
+        SpriteSheetError::Image(value)
+    }
+}
+
+impl From<serde_json::Error> for SpriteSheetError {
+    fn from(value: serde_json::Error) -> Self {
+        SpriteSheetError::Json(value)
+    }
+}
+
+impl SpriteSheet {
+    /// A sheet without any frames, add them with [SpriteSheet::add_frame]
+    pub fn new(sprite: Sprite) -> SpriteSheet {
+        SpriteSheet {
+            sprite,
+            frames: Vec::new(),
+            names: HashMap::new(),
+            animations: HashMap::new(),
+        }
+    }
+
+    /// Cuts the sprite into cells of `frame_width` x `frame_height`, numbered row by row.
+    /// Cells that do not fit completely are left out.
+    pub fn from_grid(sprite: Sprite, frame_width: u32, frame_height: u32) -> SpriteSheet {
+        let mut sheet = SpriteSheet::new(sprite);
+        sheet.add_grid(frame_width, frame_height);
+        sheet
+    }
+
+    fn add_grid(&mut self, frame_width: u32, frame_height: u32) {
+        if frame_width == 0 || frame_height == 0 {
+            return;
+        }
+        let columns = self.sprite.width() as u32 / frame_width;
+        let rows = self.sprite.height() as u32 / frame_height;
+        for row in 0..rows {
+            for column in 0..columns {
+                self.frames.push(SpriteRegion {
+                    x: column * frame_width,
+                    y: row * frame_height,
+                    width: frame_width,
+                    height: frame_height,
+                });
+            }
+        }
+    }
+
+    /// Adds a frame and returns its index, a frame with the same name is shadowed
+    pub fn add_frame(&mut self, name: Option<&str>, region: SpriteRegion) -> usize {
+        let index = self.frames.len();
+        self.frames.push(region);
+        if let Some(name) = name {
+            self.names.insert(name.to_string(), index);
+        }
+        index
+    }
+
+    pub fn add_animation(&mut self, name: &str, animation: Animation) {
+        self.animations.insert(name.to_string(), animation);
+    }
+
+    pub fn sprite(&self) -> &Sprite {
+        &self.sprite
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn frame(&self, index: usize) -> Option<SpriteRegion> {
+        self.frames.get(index).copied()
+    }
+
+    pub fn frame_index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// A fresh copy of the named animation, starting at its first frame
+    pub fn animation(&self, name: &str) -> Option<Animation> {
+        self.animations.get(name).cloned()
+    }
+
+    /// Draws a single frame, the source region of `options` is replaced by the frame.
+    /// Unknown frames are not drawn.
+    pub fn draw_frame<T: VNERenderer + ?Sized>(
+        &self,
+        renderer: &mut T,
+        frame: usize,
+        position: PixelPosition,
+        options: &SpriteOptions,
+    ) {
+        if let Some(region) = self.frame(frame) {
+            let options = SpriteOptions {
+                source: Some(region),
+                ..*options
+            };
+            self.sprite.draw(renderer, position, &options);
+        }
+    }
+}
+
+//////////////
+// MANIFEST //
+//////////////
+
+/// The JSON manifest describing a sheet, e.g.
+/// ```json
+/// {
+///     "image": "hero.png",
+///     "grid": { "frame_width": 16, "frame_height": 16 },
+///     "frames": [{ "name": "jump", "x": 0, "y": 32, "width": 16, "height": 24 }],
+///     "animations": {
+///         "walk": { "frames": [0, 1, 2, 3], "duration": 0.1, "mode": "loop" },
+///         "land": { "frames": [{ "frame": "jump", "duration": 0.25 }, 0], "duration": 0.1 }
+///     }
+/// }
+/// ```
+/// Grid cells come first, named frames are numbered after them. Animation frames refer to
+/// frames by index or name, frames without a duration use the duration of the animation.
+#[derive(Deserialize)]
+struct Manifest {
+    /// relative to the manifest
+    image: Option<PathBuf>,
+    grid: Option<GridManifest>,
+    #[serde(default)]
+    frames: Vec<FrameManifest>,
+    #[serde(default)]
+    animations: HashMap<String, AnimationManifest>,
+}
+
+#[derive(Deserialize)]
+struct GridManifest {
+    frame_width: u32,
+    frame_height: u32,
+}
+
+#[derive(Deserialize)]
+struct FrameManifest {
+    name: Option<String>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct AnimationManifest {
+    frames: Vec<AnimationFrameManifest>,
+    duration: Option<f64>,
+    #[serde(default)]
+    mode: AnimationMode,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnimationFrameManifest {
+    Frame(FrameReference),
+    Timed {
+        frame: FrameReference,
+        duration: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameReference {
+    Index(usize),
+    Name(String),
+}
+
+impl SpriteSheet {
+    /// Loads a sheet from a JSON manifest, see [SpriteSheet::from_manifest]. The image named in
+    /// the manifest is loaded relative to the manifest.
+    pub fn load(path: impl AsRef<Path>) -> Result<SpriteSheet, SpriteSheetError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let manifest: Manifest = serde_json::from_str(&source)?;
+        let image = manifest
+            .image
+            .as_ref()
+            .ok_or_else(|| manifest_error("the manifest names no image"))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let sprite = Sprite::load(directory.join(image))?;
+        SpriteSheet::from_parsed_manifest(sprite, manifest)
+    }
+
+    /// Cuts `sprite` according to a JSON manifest, the image named in the manifest is ignored
+    pub fn from_manifest(sprite: Sprite, source: &str) -> Result<SpriteSheet, SpriteSheetError> {
+        SpriteSheet::from_parsed_manifest(sprite, serde_json::from_str(source)?)
+    }
+
+    fn from_parsed_manifest(
+        sprite: Sprite,
+        manifest: Manifest,
+    ) -> Result<SpriteSheet, SpriteSheetError> {
+        let mut sheet = SpriteSheet::new(sprite);
+        if let Some(grid) = manifest.grid {
+            if grid.frame_width == 0 || grid.frame_height == 0 {
+                return Err(manifest_error("the grid cell size must not be 0"));
+            }
+            sheet.add_grid(grid.frame_width, grid.frame_height);
+        }
+
+        for frame in manifest.frames {
+            let region = SpriteRegion {
+                x: frame.x,
+                y: frame.y,
+                width: frame.width,
+                height: frame.height,
+            };
+            if region.x + region.width > sheet.sprite.width() as u32
+                || region.y + region.height > sheet.sprite.height() as u32
+            {
+                return Err(manifest_error(&format!(
+                    "frame {} lies outside of the image",
+                    frame.name.as_deref().unwrap_or("without name")
+                )));
+            }
+            sheet.add_frame(frame.name.as_deref(), region);
+        }
+
+        for (name, animation) in manifest.animations {
+            let mut frames = Vec::with_capacity(animation.frames.len());
+            for frame in animation.frames {
+                let (reference, duration) = match frame {
+                    AnimationFrameManifest::Frame(reference) => (reference, animation.duration),
+                    AnimationFrameManifest::Timed { frame, duration } => (frame, Some(duration)),
+                };
+                let index = match reference {
+                    FrameReference::Index(index) if index < sheet.frame_count() => index,
+                    FrameReference::Index(index) => {
+                        return Err(manifest_error(&format!(
+                            "animation {} uses the unknown frame {}",
+                            name, index
+                        )))
+                    }
+                    FrameReference::Name(frame_name) => {
+                        sheet.frame_index(&frame_name).ok_or_else(|| {
+                            manifest_error(&format!(
+                                "animation {} uses the unknown frame {}",
+                                name, frame_name
+                            ))
+                        })?
+                    }
+                };
+                let duration = duration.ok_or_else(|| {
+                    manifest_error(&format!("animation {} has a frame without duration", name))
+                })?;
+                frames.push(AnimationFrame {
+                    frame: index,
+                    duration,
+                });
+            }
+            sheet.add_animation(&name, Animation::new(frames, animation.mode));
+        }
+        Ok(sheet)
+    }
+}
+
+fn manifest_error(message: &str) -> SpriteSheetError {
+    SpriteSheetError::Manifest(message.to_string())
+}
+
+///////////////
+// ANIMATION //
+///////////////
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationMode {
+    /// stops on the last frame
+    Once,
+    /// starts over after the last frame
+    #[default]
+    Loop,
+    /// runs forwards and backwards again, the first and last frame are shown once per turn
+    PingPong,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnimationFrame {
+    /// index into the sprite sheet
+    pub frame: usize,
+    /// in seconds
+    pub duration: f64,
+}
+
+/// Plays a sequence of sprite sheet frames
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    frames: Vec<AnimationFrame>,
+    mode: AnimationMode,
+    current: usize,
+    elapsed: f64,
+    backwards: bool,
+    finished: bool,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<AnimationFrame>, mode: AnimationMode) -> Animation {
+        Animation {
+            frames,
+            mode,
+            current: 0,
+            elapsed: 0.0,
+            backwards: false,
+            finished: false,
+        }
+    }
+
+    /// Every frame is shown for the same `duration`
+    pub fn uniform(frames: &[usize], duration: f64, mode: AnimationMode) -> Animation {
+        let frames = frames
+            .iter()
+            .map(|&frame| AnimationFrame { frame, duration })
+            .collect();
+        Animation::new(frames, mode)
+    }
+
+    pub fn mode(&self) -> AnimationMode {
+        self.mode
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    /// the sprite sheet frame to show, `None` for an animation without frames
+    pub fn current_frame(&self) -> Option<usize> {
+        self.frames.get(self.current).map(|f| f.frame)
+    }
+
+    /// true once an animation in [AnimationMode::Once] reached the end of its last frame
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn reset(&mut self) {
+        self.current = 0;
+        self.elapsed = 0.0;
+        self.backwards = false;
+        self.finished = false;
+    }
+
+    /// Advances by the delta of the current frame
+    pub fn tick(&mut self, engine: &VNEngineState) {
+        self.update(engine.delta);
+    }
+
+    /// Advances by `delta` seconds, skipping as many frames as necessary
+    pub fn update(&mut self, delta: f64) {
+        if self.finished || self.frames.is_empty() {
+            return;
+        }
+        self.elapsed += delta;
+
+        // whole cycles do not change the state, dropping them keeps long deltas cheap
+        if self.mode != AnimationMode::Once {
+            let cycle = self.cycle_duration();
+            if cycle <= 0.0 {
+                return;
+            }
+            self.elapsed %= cycle;
+        }
+
+        while self.elapsed >= self.frames[self.current].duration {
+            self.elapsed -= self.frames[self.current].duration;
+            if !self.advance() {
+                self.finished = true;
+                self.elapsed = 0.0;
+                return;
+            }
+        }
+    }
+
+    /// Moves to the next frame, false if there is none
+    fn advance(&mut self) -> bool {
+        let last = self.frames.len() - 1;
+        match self.mode {
+            AnimationMode::Once if self.current == last => return false,
+            AnimationMode::Once => self.current += 1,
+            AnimationMode::Loop => self.current = (self.current + 1) % self.frames.len(),
+            AnimationMode::PingPong if last == 0 => {}
+            AnimationMode::PingPong => {
+                if self.current == last {
+                    self.backwards = true;
+                } else if self.current == 0 {
+                    self.backwards = false;
+                }
+                match self.backwards {
+                    false => self.current += 1,
+                    true => self.current -= 1,
+                }
+            }
+        }
+        true
+    }
+
+    /// the time until the animation is back in its current state
+    fn cycle_duration(&self) -> f64 {
+        let total: f64 = self.frames.iter().map(|f| f.duration).sum();
+        match self.mode {
+            AnimationMode::PingPong if self.frames.len() > 2 => {
+                let inner: f64 = self.frames[1..self.frames.len() - 1]
+                    .iter()
+                    .map(|f| f.duration)
+                    .sum();
+                total + inner
+            }
+            _ => total,
+        }
+    }
+
+    /// Draws the current frame from `sheet`, see [SpriteSheet::draw_frame]
+    pub fn draw<T: VNERenderer + ?Sized>(
+        &self,
+        renderer: &mut T,
+        sheet: &SpriteSheet,
+        position: PixelPosition,
+        options: &SpriteOptions,
+    ) {
+        if let Some(frame) = self.current_frame() {
+            sheet.draw_frame(renderer, frame, position, options);
+        }
+    }
+}
@@ -0,0 +1,1092 @@
+use std::f64::consts::TAU;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// sample rate of the system's audio device, e.g. for `Audio::device`
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Decoded audio with one or two interleaved channels, samples are in `[-1, 1]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sound {
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<f32>,
+}
+
+impl Sound {
+    /// `samples` are interleaved if there are two channels, panics for other channel counts
+    pub fn new(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Sound {
+        if channels != 1 && channels != 2 {
+            panic!("Unsupported channel count {}", channels)
+        }
+        Sound {
+            sample_rate,
+            channels,
+            samples,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// number of samples per channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// length in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 / self.sample_rate as f64
+    }
+
+    pub fn load_wav(path: impl AsRef<Path>) -> Result<Sound, AudioError> {
+        Sound::decode_wav(&fs::read(path)?)
+    }
+
+    pub fn save_wav(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.encode_wav())
+    }
+
+    /// the sample of `channel` in frame `frame`, mono sounds have the same on both channels
+    fn sample(&self, frame: usize, channel: usize) -> f32 {
+        match self.channels {
+            1 => self.samples[frame],
+            _ => self.samples[frame * 2 + channel],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    Io(io::Error),
+    /// the data is not a supported WAV file
+    Format(String),
+}
+
+impl From<io::Error> for AudioError {
+    fn from(value: io::Error) -> Self {
+        AudioError::Io(value)
+    }
+}
+
+/////////
+// WAV //
+/////////
+
+// A RIFF file of chunks, each with a four letter id, its size as u32 and its data padded to an
+// even length. "fmt " describes the samples in "data", all numbers are little endian.
+
+const PCM: u16 = 1;
+const FLOAT: u16 = 3;
+const EXTENSIBLE: u16 = 0xfffe;
+
+impl Sound {
+    /// A WAV file with 16 bit samples
+    pub fn encode_wav(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(44 + self.samples.len() * 2);
+        write_wav_header(
+            &mut data,
+            self.sample_rate,
+            self.channels,
+            self.samples.len() * 2,
+        );
+        for sample in &self.samples {
+            data.extend_from_slice(&to_i16(*sample).to_le_bytes());
+        }
+        data
+    }
+
+    /// Reads a WAV file with 8, 16, 24 or 32 bit integer or 32 or 64 bit float samples and one or
+    /// two channels
+    ///
+    /// ```
+    /// use vn_engine::audio::Sound;
+    ///
+    /// let sound = Sound::new(8000, 2, vec![0.0, 0.5, -0.5, 1.0]);
+    /// assert_eq!(Sound::decode_wav(&sound.encode_wav()).unwrap().frames(), 2);
+    /// ```
+    pub fn decode_wav(data: &[u8]) -> Result<Sound, AudioError> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(format_error("not a WAV file"));
+        }
+        let mut format = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let id = &data[offset..offset + 4];
+            let size = u32_at(data, offset + 4) as usize;
+            // streaming writers leave the size of the last chunk too large
+            let chunk = &data[offset + 8..(offset + 8).saturating_add(size).min(data.len())];
+            match id {
+                b"fmt " => format = Some(WavFormat::decode(chunk)?),
+                b"data" => {
+                    let format = format.ok_or_else(|| format_error("data before format"))?;
+                    return format.decode_samples(chunk);
+                }
+                _ => {}
+            }
+            offset += 8 + size + size % 2;
+        }
+        Err(format_error("no data"))
+    }
+}
+
+struct WavFormat {
+    format: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits: u16,
+}
+
+impl WavFormat {
+    fn decode(chunk: &[u8]) -> Result<WavFormat, AudioError> {
+        if chunk.len() < 16 {
+            return Err(format_error("format too short"));
+        }
+        let mut format = WavFormat {
+            format: u16_at(chunk, 0),
+            channels: u16_at(chunk, 2),
+            sample_rate: u32_at(chunk, 4),
+            bits: u16_at(chunk, 14),
+        };
+        // the actual format is the start of the sub format GUID
+        if format.format == EXTENSIBLE {
+            if chunk.len() < 26 {
+                return Err(format_error("format too short"));
+            }
+            format.format = u16_at(chunk, 24);
+        }
+        if format.channels != 1 && format.channels != 2 {
+            let message = format!("unsupported channel count {}", format.channels);
+            return Err(format_error(&message));
+        }
+        match (format.format, format.bits) {
+            (PCM, 8 | 16 | 24 | 32) | (FLOAT, 32 | 64) => Ok(format),
+            (format, bits) => Err(format_error(&format!(
+                "unsupported sample format {} with {} bits",
+                format, bits
+            ))),
+        }
+    }
+
+    fn decode_samples(&self, chunk: &[u8]) -> Result<Sound, AudioError> {
+        let width = self.bits as usize / 8;
+        let frame_width = width * self.channels as usize;
+        // a truncated last frame is dropped
+        let chunk = &chunk[..chunk.len() - chunk.len() % frame_width];
+        let samples = chunk
+            .chunks_exact(width)
+            .map(|bytes| match (self.format, width) {
+                (PCM, 1) => (bytes[0] as f32 - 128.0) / 128.0,
+                (PCM, 2) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+                (PCM, 3) => {
+                    i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2147483648.0
+                }
+                (PCM, _) => u32_at(bytes, 0) as i32 as f32 / 2147483648.0,
+                (_, 4) => f32::from_bits(u32_at(bytes, 0)),
+                _ => {
+                    let mut array = [0; 8];
+                    array.copy_from_slice(bytes);
+                    f64::from_le_bytes(array) as f32
+                }
+            })
+            .collect();
+        Ok(Sound::new(self.sample_rate, self.channels, samples))
+    }
+}
+
+/// Writes the header of a 16 bit WAV file with `data_size` bytes of samples
+fn write_wav_header(data: &mut Vec<u8>, sample_rate: u32, channels: u16, data_size: usize) {
+    let block_align = channels * 2;
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + data_size as u32).to_le_bytes());
+    data.extend_from_slice(b"WAVEfmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&PCM.to_le_bytes());
+    data.extend_from_slice(&channels.to_le_bytes());
+    data.extend_from_slice(&sample_rate.to_le_bytes());
+    data.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    data.extend_from_slice(&block_align.to_le_bytes());
+    data.extend_from_slice(&16u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&(data_size as u32).to_le_bytes());
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    let mut array = [0; 4];
+    array.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(array)
+}
+
+fn format_error(message: &str) -> AudioError {
+    AudioError::Format(format!("wav: {}", message))
+}
+
+///////////
+// SYNTH //
+///////////
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Wave {
+    Sine,
+    /// high for `duty` of each period, low for the rest
+    Square {
+        duty: f64,
+    },
+    Triangle,
+    Sawtooth,
+    /// a new random level `frequency` times per second, the same for every render
+    Noise,
+}
+
+/// A synthesized sound, e.g. a beep for a ball hitting a wall
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tone {
+    pub wave: Wave,
+    /// in Hz, slides linearly to `end_frequency` over the duration
+    pub frequency: f64,
+    pub end_frequency: f64,
+    /// in seconds
+    pub duration: f64,
+    pub volume: f32,
+    /// seconds to fade in and out, so the tone does not start or end with a click
+    pub attack: f64,
+    pub release: f64,
+}
+
+impl Tone {
+    pub fn new(wave: Wave, frequency: f64, duration: f64) -> Tone {
+        Tone {
+            wave,
+            frequency,
+            end_frequency: frequency,
+            duration,
+            volume: 0.5,
+            attack: 0.005,
+            release: 0.02,
+        }
+    }
+
+    /// a square wave with equal high and low
+    pub fn beep(frequency: f64, duration: f64) -> Tone {
+        Tone::new(Wave::Square { duty: 0.5 }, frequency, duration)
+    }
+
+    /// Samples the tone into a mono sound
+    pub fn render(&self, sample_rate: u32) -> Sound {
+        let frames = (self.duration * sample_rate as f64).round() as usize;
+        let mut phase = 0.0;
+        let mut noise = Noise::default();
+        let samples = (0..frames)
+            .map(|frame| {
+                let time = frame as f64 / sample_rate as f64;
+                let progress = time / self.duration;
+                let value = match self.wave {
+                    Wave::Sine => (phase * TAU).sin(),
+                    Wave::Square { duty } if phase < duty => 1.0,
+                    Wave::Square { .. } => -1.0,
+                    Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                    Wave::Sawtooth => 2.0 * phase - 1.0,
+                    Wave::Noise => noise.level(),
+                };
+                let envelope = (time / self.attack)
+                    .min((self.duration - time) / self.release)
+                    .clamp(0.0, 1.0);
+
+                let frequency = self.frequency + (self.end_frequency - self.frequency) * progress;
+                phase += frequency / sample_rate as f64;
+                if phase >= 1.0 {
+                    phase = phase.fract();
+                    noise.step();
+                }
+                value as f32 * envelope as f32 * self.volume
+            })
+            .collect();
+        Sound::new(sample_rate, 1, samples)
+    }
+}
+
+/// a 15 bit linear feedback shift register, like the noise channel of old consoles
+struct Noise {
+    state: u16,
+}
+
+impl Default for Noise {
+    fn default() -> Noise {
+        Noise { state: 1 }
+    }
+}
+
+impl Noise {
+    fn level(&self) -> f64 {
+        if self.state & 1 == 1 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    fn step(&mut self) {
+        let feedback = (self.state ^ (self.state >> 1)) & 1;
+        self.state = (self.state >> 1) | (feedback << 14);
+    }
+}
+
+///////////
+// MIXER //
+///////////
+
+/// Identifies a sound started with [Mixer::play]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+/// slower voices would hardly advance, and without advancing they never end
+const MIN_PITCH: f64 = 1.0 / 256.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlayOptions {
+    pub volume: f32,
+    /// -1 is only left, 1 only right
+    pub pan: f32,
+    /// playback speed, 2 is an octave higher, at least 1/256
+    pub pitch: f64,
+    /// starts over at the end instead of stopping
+    pub looping: bool,
+}
+
+impl Default for PlayOptions {
+    fn default() -> PlayOptions {
+        PlayOptions {
+            volume: 1.0,
+            pan: 0.0,
+            pitch: 1.0,
+            looping: false,
+        }
+    }
+}
+
+impl PlayOptions {
+    /// the options with a pitch voices can play at
+    fn clamped(self) -> PlayOptions {
+        PlayOptions {
+            // max also replaces NaN
+            pitch: self.pitch.max(MIN_PITCH),
+            ..self
+        }
+    }
+}
+
+struct Voice {
+    id: VoiceId,
+    sound: Arc<Sound>,
+    /// in frames of the sound
+    position: f64,
+    options: PlayOptions,
+}
+
+/// Mixes any number of sounds into interleaved stereo samples at one sample rate. Sounds of other
+/// rates are resampled.
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    next_id: u64,
+    volume: f32,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            sample_rate,
+            voices: Vec::new(),
+            next_id: 0,
+            volume: 1.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// the volume all voices are multiplied with
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    /// Starts playing `sound` with the next mixed sample
+    pub fn play(&mut self, sound: &Arc<Sound>, options: PlayOptions) -> VoiceId {
+        let id = VoiceId(self.next_id);
+        self.next_id += 1;
+        self.voices.push(Voice {
+            id,
+            sound: sound.clone(),
+            position: 0.0,
+            options: options.clamped(),
+        });
+        id
+    }
+
+    /// false once the voice stopped or played to the end
+    pub fn is_playing(&self, voice: VoiceId) -> bool {
+        self.voices.iter().any(|playing| playing.id == voice)
+    }
+
+    /// number of sounds playing
+    pub fn voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn stop(&mut self, voice: VoiceId) {
+        self.voices.retain(|playing| playing.id != voice);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    /// Changes volume, pan, pitch or looping of a playing voice, does nothing if it stopped
+    pub fn set_options(&mut self, voice: VoiceId, options: PlayOptions) {
+        if let Some(playing) = self.voices.iter_mut().find(|playing| playing.id == voice) {
+            playing.options = options.clamped();
+        }
+    }
+
+    /// Overwrites `out` with the next `out.len() / 2` frames of interleaved stereo samples,
+    /// clamped to `[-1, 1]`
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let sample_rate = self.sample_rate;
+        self.voices.retain_mut(|voice| voice.mix(out, sample_rate));
+        for sample in out.iter_mut() {
+            *sample = (*sample * self.volume).clamp(-1.0, 1.0);
+        }
+    }
+
+    /// the next `frames` frames, e.g. to compare them with expected samples
+    pub fn render(&mut self, frames: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; frames * 2];
+        self.mix(&mut samples);
+        samples
+    }
+}
+
+impl Voice {
+    /// Adds the voice to `out`, false once it has ended
+    fn mix(&mut self, out: &mut [f32], sample_rate: u32) -> bool {
+        let frames = self.sound.frames();
+        if frames == 0 {
+            return false;
+        }
+        let step = self.sound.sample_rate() as f64 / sample_rate as f64 * self.options.pitch;
+        let PlayOptions { volume, pan, .. } = self.options;
+        let gains = [volume * (1.0 - pan).min(1.0), volume * (1.0 + pan).min(1.0)];
+
+        for frame in out.chunks_exact_mut(2) {
+            if self.position >= frames as f64 {
+                if !self.options.looping {
+                    return false;
+                }
+                self.position %= frames as f64;
+            }
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let next = match index + 1 {
+                next if next < frames => Some(next),
+                _ if self.options.looping => Some(0),
+                _ => None,
+            };
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let current = self.sound.sample(index, channel);
+                let next = next.map_or(0.0, |next| self.sound.sample(next, channel));
+                *sample += (current + (next - current) * fraction) * gains[channel];
+            }
+            self.position += step;
+        }
+        self.options.looping || self.position < frames as f64
+    }
+}
+
+///////////
+// SINKS //
+///////////
+
+/// Where mixed audio goes
+pub trait AudioSink {
+    /// Plays interleaved stereo samples in `[-1, 1]`, at the sample rate of the mixer
+    fn write(&mut self, samples: &[f32]) -> io::Result<()>;
+    /// Writes out anything buffered
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Discards the samples, only counting them
+#[derive(Default)]
+pub struct NullSink {
+    frames: u64,
+}
+
+impl NullSink {
+    /// number of frames written so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+impl AudioSink for NullSink {
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.frames += samples.len() as u64 / 2;
+        Ok(())
+    }
+}
+
+/// Writes a 16 bit stereo WAV file, complete after every [AudioSink::flush] and when dropped
+pub struct WavSink {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    data_size: usize,
+}
+
+impl WavSink {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<WavSink> {
+        let mut sink = WavSink {
+            file: BufWriter::new(File::create(path)?),
+            sample_rate,
+            data_size: 0,
+        };
+        sink.flush()?;
+        Ok(sink)
+    }
+}
+
+impl AudioSink for WavSink {
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&to_i16(*sample).to_le_bytes())?;
+        }
+        self.data_size += samples.len() * 2;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(44);
+        write_wav_header(&mut header, self.sample_rate, 2, self.data_size);
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        if let Err(error) = self.flush() {
+            eprintln!("Failed to finish the WAV file: {}", error);
+        }
+    }
+}
+
+/// Plays on the system's audio device by piping 16 bit samples into the first player found:
+/// `pacat` (PulseAudio and PipeWire), `aplay` (ALSA) or `play` (SoX). Only on Linux, and only
+/// with one of those installed, so the engine never opens it by itself, a game has to opt in
+/// through [Audio::device].
+#[cfg(target_os = "linux")]
+pub struct DeviceSink {
+    player: Child,
+    input: ChildStdin,
+    bytes: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+impl DeviceSink {
+    pub fn open(sample_rate: u32) -> io::Result<DeviceSink> {
+        let rate = sample_rate.to_string();
+        let players: [(&str, &[&str]); 3] = [
+            (
+                "pacat",
+                &[
+                    "--raw",
+                    "--format=s16le",
+                    "--channels=2",
+                    "--latency-msec=50",
+                    "--rate",
+                    &rate,
+                ],
+            ),
+            (
+                "aplay",
+                &[
+                    "-q",
+                    "-t",
+                    "raw",
+                    "-f",
+                    "S16_LE",
+                    "-c",
+                    "2",
+                    "--buffer-time=50000",
+                    "-r",
+                    &rate,
+                ],
+            ),
+            (
+                "play",
+                &[
+                    "-q", "-t", "raw", "-e", "signed", "-b", "16", "-c", "2", "-r", &rate, "-",
+                ],
+            ),
+        ];
+        for (program, args) in players {
+            let player = Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn();
+            match player {
+                Ok(mut player) => {
+                    let input = player
+                        .stdin
+                        .take()
+                        .expect("Failed to open the player input!");
+                    return Ok(DeviceSink {
+                        player,
+                        input,
+                        bytes: Vec::new(),
+                    });
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no audio player found, install pacat, aplay or sox",
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AudioSink for DeviceSink {
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.bytes.clear();
+        for sample in samples {
+            self.bytes.extend_from_slice(&to_i16(*sample).to_le_bytes());
+        }
+        self.input.write_all(&self.bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.input.flush()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for DeviceSink {
+    fn drop(&mut self) {
+        let _ = self.player.kill();
+        let _ = self.player.wait();
+    }
+}
+
+///////////
+// AUDIO //
+///////////
+
+/// how far ahead of the clock [Audio::start] writes, so the sink does not run dry
+const LEAD: f64 = 0.05;
+
+/// A [Mixer] feeding a sink, shared by all clones. Either the engine advances it by the delta of
+/// every frame, so offline runs always produce the same samples, or a thread feeds the sink as
+/// time passes.
+#[derive(Clone)]
+pub struct Audio {
+    /// separate from the output, so playing sounds never waits for a slow sink
+    mixer: Arc<Mutex<Mixer>>,
+    output: Arc<Mutex<Output>>,
+    realtime: bool,
+}
+
+struct Output {
+    sink: Box<dyn AudioSink + Send>,
+    buffer: Vec<f32>,
+    /// frames not written yet because they did not add up to a whole one
+    remainder: f64,
+    failed: bool,
+}
+
+impl Audio {
+    /// Audio that only advances through [Audio::advance], e.g. driven by a
+    /// [HeadlessEngine](crate::headless::HeadlessEngine)
+    pub fn new(sink: impl AudioSink + Send + 'static, sample_rate: u32) -> Audio {
+        Audio {
+            mixer: Arc::new(Mutex::new(Mixer::new(sample_rate))),
+            output: Arc::new(Mutex::new(Output {
+                sink: Box::new(sink),
+                buffer: Vec::new(),
+                remainder: 0.0,
+                failed: false,
+            })),
+            realtime: false,
+        }
+    }
+
+    /// Audio fed to `sink` by a thread at the pace of the clock, until all clones are dropped
+    pub fn start(sink: impl AudioSink + Send + 'static, sample_rate: u32) -> Audio {
+        let audio = Audio {
+            realtime: true,
+            ..Audio::new(sink, sample_rate)
+        };
+        let mixer = Arc::downgrade(&audio.mixer);
+        let output = audio.output.clone();
+        thread::spawn(move || feed(mixer, output, sample_rate));
+        audio
+    }
+
+    /// Real time audio on the system's audio device through a [DeviceSink], Linux only
+    #[cfg(target_os = "linux")]
+    pub fn device() -> Result<Audio, AudioError> {
+        Ok(Audio::start(DeviceSink::open(SAMPLE_RATE)?, SAMPLE_RATE))
+    }
+
+    /// Calls `change` with the mixer, e.g. to change the options of a voice
+    pub fn with_mixer<T>(&self, change: impl FnOnce(&mut Mixer) -> T) -> T {
+        change(&mut self.mixer.lock().expect("Failed to lock the audio!"))
+    }
+
+    pub fn play(&self, sound: &Arc<Sound>, options: PlayOptions) -> VoiceId {
+        self.with_mixer(|mixer| mixer.play(sound, options))
+    }
+
+    pub fn stop(&self, voice: VoiceId) {
+        self.with_mixer(|mixer| mixer.stop(voice));
+    }
+
+    /// Mixes the next `seconds` and writes them to the sink, does nothing for [Audio::start]
+    pub fn advance(&self, seconds: f64) {
+        if self.realtime {
+            return;
+        }
+        let mut output = self.output();
+        let sample_rate = self.with_mixer(|mixer| mixer.sample_rate());
+        let frames = seconds * sample_rate as f64 + output.remainder;
+        output.remainder = frames.fract();
+
+        let mut buffer = std::mem::take(&mut output.buffer);
+        buffer.resize(frames as usize * 2, 0.0);
+        self.with_mixer(|mixer| mixer.mix(&mut buffer));
+        output.write(&buffer);
+        output.buffer = buffer;
+    }
+
+    pub fn flush(&self) {
+        let mut output = self.output();
+        if let Err(error) = output.sink.flush() {
+            eprintln!("Failed to flush the audio: {}", error);
+        }
+    }
+
+    fn output(&self) -> MutexGuard<'_, Output> {
+        self.output.lock().expect("Failed to lock the audio!")
+    }
+}
+
+impl Output {
+    /// Writes mixed samples to the sink, after an error the sink gets nothing
+    fn write(&mut self, samples: &[f32]) {
+        if self.failed || samples.is_empty() {
+            return;
+        }
+        if let Err(error) = self.sink.write(samples) {
+            eprintln!("Failed to play the audio: {}", error);
+            self.failed = true;
+        }
+    }
+}
+
+/// The thread of [Audio::start], writes as many frames as have been played, plus [LEAD]. The
+/// mixer is only locked while mixing, writing to the sink may block until it has room.
+fn feed(mixer: Weak<Mutex<Mixer>>, output: Arc<Mutex<Output>>, sample_rate: u32) {
+    let start = Instant::now();
+    let mut written = 0;
+    let mut buffer = Vec::new();
+    loop {
+        let due = ((start.elapsed().as_secs_f64() + LEAD) * sample_rate as f64) as u64;
+        buffer.resize((due - written) as usize * 2, 0.0);
+        match mixer.upgrade() {
+            Some(mixer) => mixer
+                .lock()
+                .expect("Failed to lock the audio!")
+                .mix(&mut buffer),
+            None => return,
+        }
+
+        let mut output = output.lock().expect("Failed to lock the audio!");
+        output.write(&buffer);
+        if output.failed {
+            return;
+        }
+        drop(output);
+        written = due;
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mono(sample_rate: u32, samples: &[f32]) -> Arc<Sound> {
+        Arc::new(Sound::new(sample_rate, 1, samples.to_vec()))
+    }
+
+    fn assert_samples(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (actual_sample, expected_sample) in actual.iter().zip(expected) {
+            assert!(
+                (actual_sample - expected_sample).abs() < 1e-6,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    /// left and right of every frame the same
+    fn both(samples: &[f32]) -> Vec<f32> {
+        samples
+            .iter()
+            .flat_map(|sample| [*sample, *sample])
+            .collect()
+    }
+
+    /// keeps everything written to it, shared with the test
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<f32>>>);
+
+    impl AudioSink for Collect {
+        fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+            self.0.lock().unwrap().extend_from_slice(samples);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn volume_and_pan() {
+        let sound = mono(8000, &[0.5; 4]);
+        let mut mixer = Mixer::new(8000);
+        let options = |volume, pan| PlayOptions {
+            volume,
+            pan,
+            ..PlayOptions::default()
+        };
+
+        mixer.play(&sound, options(0.5, -0.5));
+        assert_samples(&mixer.render(1), &[0.25, 0.125]);
+        mixer.stop_all();
+
+        mixer.play(&sound, options(1.0, 1.0));
+        assert_samples(&mixer.render(1), &[0.0, 0.5]);
+        mixer.stop_all();
+
+        // voices add up, the master volume applies to the sum, which is clamped
+        mixer.set_volume(0.5);
+        mixer.play(&sound, options(1.0, 0.0));
+        mixer.play(&sound, options(0.5, 0.0));
+        assert_samples(&mixer.render(1), &[0.375, 0.375]);
+        mixer.set_volume(4.0);
+        assert_samples(&mixer.render(1), &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn resampling() {
+        let ramp = [0.0, 0.2, 0.4, 0.6, 0.8];
+        let half_speed = both(&[0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.4, 0.0, 0.0]);
+
+        let mut mixer = Mixer::new(8000);
+        let slow = PlayOptions {
+            pitch: 0.5,
+            ..PlayOptions::default()
+        };
+        mixer.play(&mono(8000, &ramp), slow);
+        assert_samples(&mixer.render(12), &half_speed);
+
+        // a sound at half the sample rate of the mixer is stretched the same way
+        mixer.play(&mono(4000, &ramp), PlayOptions::default());
+        assert_samples(&mixer.render(12), &half_speed);
+
+        let fast = PlayOptions {
+            pitch: 2.0,
+            ..PlayOptions::default()
+        };
+        mixer.play(&mono(8000, &ramp), fast);
+        assert_samples(&mixer.render(4), &both(&[0.0, 0.4, 0.8, 0.0]));
+    }
+
+    #[test]
+    fn looping_wraps_around() {
+        let mut mixer = Mixer::new(8000);
+        let options = PlayOptions {
+            pitch: 1.5,
+            looping: true,
+            ..PlayOptions::default()
+        };
+        let voice = mixer.play(&mono(8000, &[0.0, 0.2, 0.4, 0.6]), options);
+        // at 3.5 the last frame is interpolated with the first one
+        let expected = both(&[0.0, 0.3, 0.6, 0.1, 0.4, 0.3, 0.2, 0.5, 0.0]);
+        assert_samples(&mixer.render(9), &expected);
+        assert!(mixer.is_playing(voice));
+    }
+
+    #[test]
+    fn voices_end() {
+        let mut mixer = Mixer::new(8000);
+        let sound = mono(8000, &[0.5, 0.5, 0.5]);
+
+        let voice = mixer.play(&sound, PlayOptions::default());
+        assert_samples(&mixer.render(5), &both(&[0.5, 0.5, 0.5, 0.0, 0.0]));
+        assert!(!mixer.is_playing(voice));
+        assert_eq!(mixer.voices(), 0);
+
+        // ending exactly at the end of a buffer
+        let voice = mixer.play(&sound, PlayOptions::default());
+        assert_samples(&mixer.render(3), &both(&[0.5; 3]));
+        assert!(!mixer.is_playing(voice));
+        assert_samples(&mixer.render(1), &[0.0, 0.0]);
+
+        let voice = mixer.play(&sound, PlayOptions::default());
+        mixer.stop(voice);
+        assert_samples(&mixer.render(1), &[0.0, 0.0]);
+
+        // voices without a usable pitch play at the slowest one
+        for pitch in [0.0, -1.0, f64::NAN] {
+            let options = PlayOptions {
+                pitch,
+                ..PlayOptions::default()
+            };
+            let voice = mixer.play(&sound, options);
+            mixer.render(767);
+            assert!(mixer.is_playing(voice));
+            mixer.render(1);
+            assert!(!mixer.is_playing(voice));
+
+            let voice = mixer.play(&sound, PlayOptions::default());
+            mixer.set_options(voice, options);
+            mixer.render(768);
+            assert!(!mixer.is_playing(voice));
+        }
+    }
+
+    #[test]
+    fn square_tone() {
+        let tone = Tone {
+            volume: 0.5,
+            attack: 0.0,
+            release: 0.0,
+            ..Tone::new(Wave::Square { duty: 0.25 }, 1000.0, 0.002)
+        };
+        let period = [0.5, 0.5, -0.5, -0.5, -0.5, -0.5, -0.5, -0.5];
+        let sound = tone.render(8000);
+        assert_eq!((sound.sample_rate(), sound.channels()), (8000, 1));
+        assert_samples(sound.samples(), &[period, period].concat());
+
+        // the envelope fades in over 40 and out over 160 samples
+        let faded = Tone::beep(1000.0, 0.1).render(8000);
+        let samples = faded.samples();
+        assert_eq!(samples.len(), 800);
+        assert_samples(
+            &[samples[0], samples[20], samples[400], samples[720]],
+            &[0.0, -0.25, 0.5, 0.25],
+        );
+    }
+
+    #[test]
+    fn noise_tone() {
+        // one new level per sample
+        let tone = Tone {
+            volume: 1.0,
+            attack: 0.0,
+            release: 0.0,
+            ..Tone::new(Wave::Noise, 32767.0, 2.0)
+        };
+        let sound = tone.render(32767);
+        let samples = sound.samples();
+        let mut start = vec![-1.0; 16];
+        (start[0], start[15]) = (1.0, 1.0);
+        assert_samples(&samples[..16], &start);
+        assert!(samples.iter().all(|sample| sample.abs() == 1.0));
+        // the shift register goes through all of its 2^15 - 1 states before repeating
+        assert_eq!(samples[..32767], samples[32767..]);
+        assert!((1..32767).all(|shift| samples[..100] != samples[shift..shift + 100]));
+        assert_eq!(tone.render(32767), sound);
+    }
+
+    #[test]
+    fn stepped_audio_writes_whole_frames() {
+        let collect = Collect::default();
+        let audio = Audio::new(collect.clone(), 44_100);
+        let voice = audio.play(&mono(44_100, &[0.25; 1000]), PlayOptions::default());
+        for _ in 0..60 {
+            audio.advance(1.0 / 60.0);
+        }
+        let samples = collect.0.lock().unwrap();
+        assert_eq!(samples.len(), 44_100 * 2);
+        assert_samples(&samples[..2000], &[0.25; 2000]);
+        assert!(samples[2000..].iter().all(|sample| *sample == 0.0));
+        assert!(!audio.with_mixer(|mixer| mixer.is_playing(voice)));
+
+        let mut null = NullSink::default();
+        null.write(&samples).unwrap();
+        assert_eq!(null.frames(), 44_100);
+    }
+
+    #[test]
+    fn wav_sink_matches_the_mixer() {
+        let sound = Arc::new(Tone::beep(440.0, 0.1).render(8000));
+        let options = PlayOptions {
+            pan: 0.5,
+            pitch: 0.75,
+            ..PlayOptions::default()
+        };
+        let mut mixer = Mixer::new(8000);
+        mixer.play(&sound, options);
+        let expected = mixer.render(1600);
+
+        let path = std::env::temp_dir().join(format!("vn_engine_{}.wav", std::process::id()));
+        let audio = Audio::new(WavSink::create(&path, 8000).unwrap(), 8000);
+        audio.play(&sound, options);
+        audio.advance(0.2);
+        audio.flush();
+        let written = Sound::load_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((written.sample_rate(), written.channels()), (8000, 2));
+        assert_eq!(written.samples().len(), expected.len());
+        for (written, expected) in written.samples().iter().zip(&expected) {
+            assert!((written - expected).abs() <= 1.0 / i16::MAX as f32);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use crate::color::RGBA;
+use crate::render::{PixelPosition, VNERenderer};
+use crate::sprite::{Sprite, SpriteOptions};
+use std::f64::consts::TAU;
+
+/// A position in the world a [Camera2D] looks at, in arbitrary units
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct WorldPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Maps world positions to the screen: the world is moved so `position` is in the center of the
+/// screen, scaled by `zoom` and turned by `rotation` around the center
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera2D {
+    /// the world position shown in the center of the screen
+    pub position: WorldPosition,
+    /// screen pixels per world unit
+    pub zoom: f64,
+    /// in radians, positive values turn the world clockwise on the screen
+    pub rotation: f64,
+    /// size of the screen in pixels, usually the size of the framebuffer
+    pub screen_width: u32,
+    pub screen_height: u32,
+}
+
+impl Camera2D {
+    /// A camera where world positions are the same as screen positions
+    pub fn new(screen_width: u32, screen_height: u32) -> Camera2D {
+        Camera2D {
+            position: WorldPosition {
+                x: screen_width as f64 / 2.0,
+                y: screen_height as f64 / 2.0,
+            },
+            zoom: 1.0,
+            rotation: 0.0,
+            screen_width,
+            screen_height,
+        }
+    }
+
+    /// Moves the camera by `x` and `y` world units
+    pub fn translate(&mut self, x: f64, y: f64) {
+        self.position.x += x;
+        self.position.y += y;
+    }
+
+    pub fn look_at(&mut self, position: WorldPosition) {
+        self.position = position;
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the center of the screen in place
+    pub fn zoom_by(&mut self, factor: f64) {
+        self.zoom *= factor;
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the world position under the screen position
+    /// `screen` in place, e.g. to zoom towards the cursor
+    pub fn zoom_at(&mut self, screen: (f64, f64), factor: f64) {
+        let before = self.to_world(screen);
+        self.zoom *= factor;
+        let after = self.to_world(screen);
+        self.translate(before.x - after.x, before.y - after.y);
+    }
+
+    pub fn rotate_by(&mut self, angle: f64) {
+        self.rotation = (self.rotation + angle) % TAU;
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (
+            self.screen_width as f64 / 2.0,
+            self.screen_height as f64 / 2.0,
+        )
+    }
+
+    /// Where `position` ends up on the screen, in pixels from the top left
+    pub fn to_screen(&self, position: WorldPosition) -> (f64, f64) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = (position.x - self.position.x) * self.zoom;
+        let y = (position.y - self.position.y) * self.zoom;
+        let (center_x, center_y) = self.center();
+        (center_x + x * cos - y * sin, center_y + x * sin + y * cos)
+    }
+
+    /// [Camera2D::to_screen] rounded to the nearest pixel
+    pub fn to_pixel(&self, position: WorldPosition) -> PixelPosition {
+        let (x, y) = self.to_screen(position);
+        PixelPosition {
+            x: x.round() as i32,
+            y: y.round() as i32,
+        }
+    }
+
+    /// The world position shown at `screen`, e.g. the cursor from
+    /// [crate::input::Input::cursor_position] for picking objects with the mouse
+    ///
+    /// ```
+    /// use vn_engine::camera::{Camera2D, WorldPosition};
+    ///
+    /// let mut camera = Camera2D::new(320, 200);
+    /// camera.look_at(WorldPosition { x: 10.0, y: -5.0 });
+    /// camera.zoom_by(4.0);
+    /// camera.rotate_by(std::f64::consts::FRAC_PI_2);
+    ///
+    /// let world = camera.to_world((200.0, 120.0));
+    /// assert!((world.x - 15.0).abs() < 1e-9 && (world.y + 15.0).abs() < 1e-9);
+    /// let (x, y) = camera.to_screen(world);
+    /// assert!((x - 200.0).abs() < 1e-9 && (y - 120.0).abs() < 1e-9);
+    /// ```
+    pub fn to_world(&self, screen: (f64, f64)) -> WorldPosition {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (center_x, center_y) = self.center();
+        let x = (screen.0 - center_x) / self.zoom;
+        let y = (screen.1 - center_y) / self.zoom;
+        WorldPosition {
+            x: self.position.x + x * cos + y * sin,
+            y: self.position.y - x * sin + y * cos,
+        }
+    }
+
+    /// Draws through this camera to `renderer`
+    pub fn renderer<'a, R: VNERenderer + ?Sized>(
+        &'a self,
+        renderer: &'a mut R,
+    ) -> CameraRenderer<'a, R> {
+        CameraRenderer {
+            camera: self,
+            renderer,
+        }
+    }
+}
+
+/// Takes world positions, transforms them with a [Camera2D] and draws them with the wrapped
+/// renderer. Clipping happens in the wrapped renderer, so its clip rectangle is in screen pixels.
+/// Lengths like radii and thicknesses are scaled by the zoom, text and sprites keep their size.
+pub struct CameraRenderer<'a, R: VNERenderer + ?Sized> {
+    camera: &'a Camera2D,
+    renderer: &'a mut R,
+}
+
+impl<'a, R: VNERenderer + ?Sized> CameraRenderer<'a, R> {
+    pub fn camera(&self) -> &Camera2D {
+        self.camera
+    }
+
+    /// the wrapped renderer, for drawing in screen space, e.g. a HUD
+    pub fn renderer(&mut self) -> &mut R {
+        self.renderer
+    }
+
+    fn pixel(&self, position: WorldPosition) -> PixelPosition {
+        self.camera.to_pixel(position)
+    }
+
+    fn pixels(&self, positions: &[WorldPosition]) -> Vec<PixelPosition> {
+        positions.iter().map(|p| self.pixel(*p)).collect()
+    }
+
+    /// a world length in whole pixels
+    fn length(&self, length: f64) -> u32 {
+        (length * self.camera.zoom).abs().round() as u32
+    }
+
+    /// the four corners of an axis aligned world rectangle, clockwise from the top left
+    fn corners(&self, top_left: WorldPosition, bottom_right: WorldPosition) -> [PixelPosition; 4] {
+        [
+            top_left,
+            WorldPosition {
+                x: bottom_right.x,
+                y: top_left.y,
+            },
+            bottom_right,
+            WorldPosition {
+                x: top_left.x,
+                y: bottom_right.y,
+            },
+        ]
+        .map(|corner| self.pixel(corner))
+    }
+
+    /// vertices along the outline of an ellipse, for when the camera is turned
+    fn ellipse_outline(
+        &self,
+        center: WorldPosition,
+        radius_x: f64,
+        radius_y: f64,
+    ) -> Vec<PixelPosition> {
+        let circumference = (radius_x + radius_y).abs() * self.camera.zoom * std::f64::consts::PI;
+        let segments = ((circumference / 4.0).ceil() as usize).clamp(12, 360);
+        (0..segments)
+            .map(|idx| {
+                let (sin, cos) = (idx as f64 / segments as f64 * TAU).sin_cos();
+                self.pixel(WorldPosition {
+                    x: center.x + cos * radius_x,
+                    y: center.y + sin * radius_y,
+                })
+            })
+            .collect()
+    }
+
+    pub fn draw_pixel(&mut self, position: WorldPosition, color: RGBA) {
+        let position = self.pixel(position);
+        self.renderer.draw_pixel(position, color);
+    }
+
+    pub fn draw_line(&mut self, from: WorldPosition, to: WorldPosition, color: RGBA) {
+        let (from, to) = (self.pixel(from), self.pixel(to));
+        self.renderer.draw_line(from, to, color);
+    }
+
+    /// a line `thickness` world units wide, at least one pixel
+    pub fn draw_thick_line(
+        &mut self,
+        from: WorldPosition,
+        to: WorldPosition,
+        thickness: f64,
+        color: RGBA,
+    ) {
+        let (from, to) = (self.pixel(from), self.pixel(to));
+        let thickness = self.length(thickness).max(1);
+        self.renderer.draw_thick_line(from, to, thickness, color);
+    }
+
+    pub fn draw_line_aa(&mut self, from: WorldPosition, to: WorldPosition, color: RGBA) {
+        let (from, to) = (self.pixel(from), self.pixel(to));
+        self.renderer.draw_line_aa(from, to, color);
+    }
+
+    pub fn draw_circle(&mut self, center: WorldPosition, radius: f64, color: RGBA) {
+        let (center, radius) = (self.pixel(center), self.length(radius));
+        self.renderer.draw_circle(center, radius, color);
+    }
+
+    pub fn fill_circle(&mut self, center: WorldPosition, radius: f64, color: RGBA) {
+        let (center, radius) = (self.pixel(center), self.length(radius));
+        self.renderer.fill_circle(center, radius, color);
+    }
+
+    /// turned with the camera, the outline is approximated by a polygon then
+    pub fn draw_ellipse(
+        &mut self,
+        center: WorldPosition,
+        radius_x: f64,
+        radius_y: f64,
+        color: RGBA,
+    ) {
+        if self.camera.rotation == 0.0 {
+            let (rx, ry) = (self.length(radius_x), self.length(radius_y));
+            let center = self.pixel(center);
+            self.renderer.draw_ellipse(center, rx, ry, color);
+        } else {
+            let outline = self.ellipse_outline(center, radius_x, radius_y);
+            self.renderer.draw_polygon(&outline, color);
+        }
+    }
+
+    /// turned with the camera, the outline is approximated by a polygon then
+    pub fn fill_ellipse(
+        &mut self,
+        center: WorldPosition,
+        radius_x: f64,
+        radius_y: f64,
+        color: RGBA,
+    ) {
+        if self.camera.rotation == 0.0 {
+            let (rx, ry) = (self.length(radius_x), self.length(radius_y));
+            let center = self.pixel(center);
+            self.renderer.fill_ellipse(center, rx, ry, color);
+        } else {
+            let outline = self.ellipse_outline(center, radius_x, radius_y);
+            self.renderer.fill_polygon(&outline, color);
+        }
+    }
+
+    pub fn draw_polygon(&mut self, vertices: &[WorldPosition], color: RGBA) {
+        let vertices = self.pixels(vertices);
+        self.renderer.draw_polygon(&vertices, color);
+    }
+
+    /// see [VNERenderer::fill_polygon]
+    pub fn fill_polygon(&mut self, vertices: &[WorldPosition], color: RGBA) {
+        let vertices = self.pixels(vertices);
+        self.renderer.fill_polygon(&vertices, color);
+    }
+
+    pub fn draw_triangle(
+        &mut self,
+        v1: WorldPosition,
+        v2: WorldPosition,
+        v3: WorldPosition,
+        color: RGBA,
+    ) {
+        let [v1, v2, v3] = [v1, v2, v3].map(|v| self.pixel(v));
+        self.renderer.draw_triangle(v1, v2, v3, color);
+    }
+
+    /// assumes clockwise vertex order, the camera keeps the order
+    pub fn fill_triangle(
+        &mut self,
+        v1: WorldPosition,
+        v2: WorldPosition,
+        v3: WorldPosition,
+        color: RGBA,
+    ) {
+        let [v1, v2, v3] = [v1, v2, v3].map(|v| self.pixel(v));
+        self.renderer.fill_triangle(v1, v2, v3, color);
+    }
+
+    /// the outline of a rectangle that is axis aligned in the world
+    pub fn draw_rectangle(
+        &mut self,
+        top_left: WorldPosition,
+        bottom_right: WorldPosition,
+        color: RGBA,
+    ) {
+        let corners = self.corners(top_left, bottom_right);
+        self.renderer.draw_polygon(&corners, color);
+    }
+
+    /// fills a rectangle that is axis aligned in the world. Like [VNERenderer::fill_polygon] the
+    /// right and bottom edges are left out, so rectangles sharing an edge do not overlap.
+    pub fn fill_rectangle(
+        &mut self,
+        top_left: WorldPosition,
+        bottom_right: WorldPosition,
+        color: RGBA,
+    ) {
+        let corners = self.corners(top_left, bottom_right);
+        if self.camera.rotation != 0.0 {
+            self.renderer.fill_polygon(&corners, color);
+            return;
+        }
+        let [a, _, b, _] = corners;
+        let (left, right) = (a.x.min(b.x), a.x.max(b.x));
+        let (top, bottom) = (a.y.min(b.y), a.y.max(b.y));
+        if left < right && top < bottom {
+            self.renderer.fill_rectangle(
+                PixelPosition { x: left, y: top },
+                PixelPosition {
+                    x: right - 1,
+                    y: bottom - 1,
+                },
+                color,
+            );
+        }
+    }
+
+    /// draws `text` in the built-in font with its top left corner at `position`, it is neither
+    /// scaled nor turned
+    pub fn draw_text(&mut self, position: WorldPosition, text: &str, color: RGBA, scale: u32) {
+        let position = self.pixel(position);
+        self.renderer.draw_text(position, text, color, scale);
+    }
+
+    /// draws the whole sprite with its top left corner at `position`, it is neither scaled nor
+    /// turned
+    pub fn draw_sprite(&mut self, sprite: &Sprite, position: WorldPosition) {
+        self.draw_sprite_with(sprite, position, &SpriteOptions::default());
+    }
+
+    /// see [CameraRenderer::draw_sprite] and [Sprite::draw]
+    pub fn draw_sprite_with(
+        &mut self,
+        sprite: &Sprite,
+        position: WorldPosition,
+        options: &SpriteOptions,
+    ) {
+        let position = self.pixel(position);
+        self.renderer.draw_sprite_with(sprite, position, options);
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct RGBA {
     pub r: f32,
     pub g: f32,
@@ -6,6 +6,13 @@ pub struct RGBA {
     pub a: f32,
 }
 
+/// nothing, e.g. the initial content of a [crate::target::RenderTarget]
+pub const TRANSPARENT: RGBA = RGBA {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+    a: 0.0,
+};
 pub const WHITE: RGBA = RGBA {
     r: 1.0,
     g: 1.0,
@@ -54,3 +61,86 @@ pub const VIOLET: RGBA = RGBA {
     b: 1.0,
     a: 1.0,
 };
+
+/// How a drawn color is combined with the color already in the render target.
+/// The alpha of the drawn color is used as its coverage in all modes but [BlendMode::Replace].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// overwrite the target, alpha is copied
+    #[default]
+    Replace,
+    /// classic "source over" compositing
+    Alpha,
+    /// add the weighted color to the target, saturating at white
+    Additive,
+    /// multiply the target with the color, the target alpha is kept
+    Multiply,
+}
+
+impl RGBA {
+    /// Combines this color as source with `target` according to `mode`
+    pub fn blend(self, target: RGBA, mode: BlendMode) -> RGBA {
+        let a = self.a.min(1.0).max(0.0);
+        match mode {
+            BlendMode::Replace => self,
+            BlendMode::Alpha if target.a >= 1.0 => RGBA {
+                r: self.r * a + target.r * (1.0 - a),
+                g: self.g * a + target.g * (1.0 - a),
+                b: self.b * a + target.b * (1.0 - a),
+                a: a + target.a * (1.0 - a),
+            },
+            // a translucent target only adds as much color as it covers, so drawing onto a
+            // transparent render target keeps the drawn color
+            BlendMode::Alpha => {
+                let coverage = target.a.max(0.0) * (1.0 - a);
+                let alpha = a + coverage;
+                if alpha <= 0.0 {
+                    return TRANSPARENT;
+                }
+                RGBA {
+                    r: (self.r * a + target.r * coverage) / alpha,
+                    g: (self.g * a + target.g * coverage) / alpha,
+                    b: (self.b * a + target.b * coverage) / alpha,
+                    a: alpha,
+                }
+            }
+            BlendMode::Additive => RGBA {
+                r: (target.r + self.r * a).min(1.0),
+                g: (target.g + self.g * a).min(1.0),
+                b: (target.b + self.b * a).min(1.0),
+                a: target.a,
+            },
+            BlendMode::Multiply => RGBA {
+                r: target.r * (self.r * a + (1.0 - a)),
+                g: target.g * (self.g * a + (1.0 - a)),
+                b: target.b * (self.b * a + (1.0 - a)),
+                a: target.a,
+            },
+        }
+    }
+
+    /// Rounds every channel down to the 8 bit precision a render target stores.
+    /// Backends quantize after blending so that they all produce identical results.
+    pub fn quantized(self) -> RGBA {
+        let [r, g, b, a] = self.to_bytes();
+        RGBA::from_bytes(r, g, b, a)
+    }
+
+    pub fn to_bytes(self) -> [u8; 4] {
+        [
+            (255.0 * self.r).min(255.0).max(0.0) as u8,
+            (255.0 * self.g).min(255.0).max(0.0) as u8,
+            (255.0 * self.b).min(255.0).max(0.0) as u8,
+            (255.0 * self.a).min(255.0).max(0.0) as u8,
+        ]
+    }
+
+    pub fn from_bytes(r: u8, g: u8, b: u8, a: u8) -> RGBA {
+        RGBA {
+            r: r as f32 / 255.0,
+            g: g as f32 / 255.0,
+            b: b as f32 / 255.0,
+            a: a as f32 / 255.0,
+        }
+    }
+}
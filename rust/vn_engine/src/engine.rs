@@ -1,43 +1,147 @@
 use super::render::VNERenderer;
+use crate::audio::Audio;
+use crate::input::{Input, InputEvent, Keycode};
 use crate::opengl::OpenGLRenderer;
+use crate::palette::{Dither, IndexedRenderer, Palette};
+use crate::recorder::{Recorder, RecorderOptions};
+use crate::replay::{InputFrame, InputLog};
 use crate::render::{VNEFullRenderer, VNERendererCommit, VNERendererWindow};
+use crate::scaling::{ScalingPolicy, Viewport};
 use crate::sprite::SpriteBased;
-use device_query::{DeviceQuery, DeviceState, Keycode, MouseState};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use winit::event::{DeviceEvent, Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::platform::run_return::EventLoopExtRunReturn;
-use winit::window::Window;
+use winit::dpi::PhysicalSize;
+use winit::window::{Fullscreen, Window};
 
 pub struct VNEngine<T: VNEFullRenderer> {
     event_loop: EventLoop<()>,
     engine_state: VNEngineState,
     pause_on_focus_loss: bool,
+    fixed_delta: Option<f64>,
+    frame_rate_cap: Option<f64>,
+    stepper: FrameStepper,
+    input_mode: InputMode,
     renderer: T,
 }
 
+/// Where the input of a run comes from
+enum InputMode {
+    Live,
+    /// live input, logged frame by frame
+    Record {
+        log: InputLog,
+        events: Vec<InputEvent>,
+    },
+    /// input and deltas come from the log, the run ends with its last frame
+    Replay { log: InputLog, next_frame: usize },
+}
+
+/// Advances the simulation in steps of `dt` seconds through [VNERunner::fixed_update],
+/// independent of the frame rate
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FixedTimestep {
+    pub dt: f64,
+    /// at most this many steps are taken per frame, time beyond that is dropped so a slow frame
+    /// does not make the next one even slower
+    pub max_steps_per_frame: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f64) -> FixedTimestep {
+        FixedTimestep {
+            dt,
+            max_steps_per_frame: 8,
+        }
+    }
+}
+
 pub struct VNEngineState {
+    /// size of the framebuffer
     pub width: u32,
     pub height: u32,
+    /// size of the window in physical pixels
+    pub window_width: u32,
+    pub window_height: u32,
     pub delta: f64,
     pub focused: bool,
-    device_state: DeviceState,
-    inner_mouse_pos: (i32, i32),
+    pub(crate) scaling: ScalingPolicy,
+    input: Input,
+    audio: Option<Audio>,
 }
 
 impl VNEngineState {
-    pub fn mouse(&self) -> MouseState {
-        let state = self.device_state.get_mouse();
-        let (x, y) = state.coords;
-        let (ix, iy) = self.inner_mouse_pos;
-        MouseState {
-            coords: (x - ix, y - iy),
-            button_pressed: state.button_pressed,
+    fn new(width: u32, height: u32) -> VNEngineState {
+        VNEngineState {
+            width,
+            height,
+            window_width: width,
+            window_height: height,
+            delta: 0.0,
+            focused: false,
+            scaling: ScalingPolicy::default(),
+            input: Input::default(),
+            audio: None,
+        }
+    }
+
+    /// An engine state for driving runners without a window, input only changes through
+    /// [VNEngineState::input_mut]
+    pub fn scripted(width: u32, height: u32, delta: f64) -> VNEngineState {
+        VNEngineState {
+            delta,
+            focused: true,
+            ..VNEngineState::new(width, height)
         }
     }
 
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn scaling(&self) -> ScalingPolicy {
+        self.scaling
+    }
+
+    /// the audio sounds are played with, `None` plays nothing
+    pub fn audio(&self) -> Option<&Audio> {
+        self.audio.as_ref()
+    }
+
+    /// Audio that is not real time is advanced by the delta of every frame
+    pub fn set_audio(&mut self, audio: Option<Audio>) {
+        self.audio = audio;
+    }
+
+    /// where the framebuffer is drawn in the window
+    pub fn viewport(&self) -> Viewport {
+        Viewport::fit(
+            self.scaling,
+            (self.width, self.height),
+            (self.window_width, self.window_height),
+        )
+    }
+
+    /// The change a window event makes to the input, with the cursor mapped from the window into
+    /// the framebuffer
+    pub fn input_event(&self, event: &WindowEvent) -> Option<InputEvent> {
+        match InputEvent::from_window_event(event) {
+            Some(InputEvent::MoveCursor(Some(position))) => Some(InputEvent::MoveCursor(
+                self.viewport()
+                    .to_framebuffer((self.width, self.height), position),
+            )),
+            input_event => input_event,
+        }
+    }
+
+    /// all keys that are currently down
     pub fn keyboad(&self) -> Vec<Keycode> {
-        self.device_state.get_keys()
+        self.input.keys_down().collect()
     }
 }
 
@@ -48,14 +152,11 @@ impl VNEngine<OpenGLRenderer> {
         Box::new(VNEngine {
             renderer,
             pause_on_focus_loss: true,
-            engine_state: VNEngineState {
-                width,
-                height,
-                delta: 0.0,
-                device_state: DeviceState::new(),
-                focused: false,
-                inner_mouse_pos: (0, 0),
-            },
+            fixed_delta: None,
+            frame_rate_cap: None,
+            stepper: FrameStepper::default(),
+            input_mode: InputMode::Live,
+            engine_state: VNEngineState::new(width, height),
             event_loop,
         })
     }
@@ -68,14 +169,11 @@ impl VNEngine<SpriteBased> {
         Box::new(VNEngine {
             renderer,
             pause_on_focus_loss,
-            engine_state: VNEngineState {
-                width,
-                height,
-                delta: 0.0,
-                device_state: DeviceState::new(),
-                focused: false,
-                inner_mouse_pos: (0, 0),
-            },
+            fixed_delta: None,
+            frame_rate_cap: None,
+            stepper: FrameStepper::default(),
+            input_mode: InputMode::Live,
+            engine_state: VNEngineState::new(width, height),
             event_loop,
         })
     }
@@ -88,14 +186,11 @@ impl<T: VNEFullRenderer> VNEngine<T> {
             event_loop,
             renderer,
             pause_on_focus_loss: true,
-            engine_state: VNEngineState {
-                focused: false,
-                delta: 0.0,
-                width,
-                height,
-                device_state: DeviceState::new(),
-                inner_mouse_pos: (0, 0),
-            },
+            fixed_delta: None,
+            frame_rate_cap: None,
+            stepper: FrameStepper::default(),
+            input_mode: InputMode::Live,
+            engine_state: VNEngineState::new(width, height),
         })
     }
 
@@ -103,42 +198,202 @@ impl<T: VNEFullRenderer> VNEngine<T> {
         &self.engine_state
     }
 
+    /// See [VNEngineState::set_audio]
+    pub fn set_audio(&mut self, audio: Option<Audio>) {
+        self.engine_state.set_audio(audio);
+    }
+
+    /// Every tick sees `delta` instead of the measured frame time, `None` measures again
+    pub fn set_fixed_delta(&mut self, delta: Option<f64>) {
+        self.fixed_delta = delta;
+    }
+
+    /// Switches between calling [VNERunner::tick] once per frame (`None`) and stepping
+    /// [VNERunner::fixed_update] followed by [VNERunner::render]
+    pub fn set_fixed_timestep(&mut self, timestep: Option<FixedTimestep>) {
+        self.stepper = FrameStepper::new(timestep);
+    }
+
+    /// Limits how many frames are rendered per second, `None` renders as fast as possible
+    pub fn set_frame_rate_cap(&mut self, frames_per_second: Option<f64>) {
+        self.frame_rate_cap = frames_per_second;
+    }
+
+    /// Whether nothing runs while the window is not focused, on by default. Games over the network
+    /// have to keep going.
+    pub fn set_pause_on_focus_loss(&mut self, pause: bool) {
+        self.pause_on_focus_loss = pause;
+    }
+
+    /// Lets the user resize the window, the framebuffer keeps its size and is scaled according to
+    /// [VNEngine::set_scaling]
+    pub fn set_resizable(&mut self, resizable: bool) {
+        self.renderer.window().set_resizable(resizable);
+    }
+
+    /// Switches between borderless fullscreen on the current monitor and a window
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        let mode = fullscreen.then_some(Fullscreen::Borderless(None));
+        self.renderer.window().set_fullscreen(mode);
+    }
+
+    pub fn set_scaling(&mut self, scaling: ScalingPolicy) {
+        self.engine_state.scaling = scaling;
+        let size = self.renderer.window().inner_size();
+        resize(&mut self.engine_state, &mut self.renderer, size);
+    }
+
+    /// Wraps the renderer in a [Recorder]. The engine switches to the fixed delta of the options,
+    /// so the recording does not depend on how fast frames are actually rendered.
+    pub fn record(self: Box<Self>, options: RecorderOptions) -> Box<VNEngine<Recorder<T>>> {
+        let delta = options.delta;
+        let mut engine = self.wrap_renderer(|renderer| Recorder::new(renderer, options));
+        engine.fixed_delta = Some(delta);
+        engine
+    }
+
+    /// Wraps the renderer in an [IndexedRenderer], so everything is drawn with the colors of
+    /// `palette`
+    pub fn indexed(
+        self: Box<Self>,
+        palette: Palette,
+        dither: Dither,
+    ) -> Box<VNEngine<IndexedRenderer<T>>> {
+        self.wrap_renderer(|renderer| IndexedRenderer::new(renderer, palette, dither))
+    }
+
+    fn wrap_renderer<U: VNEFullRenderer>(
+        self: Box<Self>,
+        wrap: impl FnOnce(T) -> U,
+    ) -> Box<VNEngine<U>> {
+        let engine = *self;
+        Box::new(VNEngine {
+            event_loop: engine.event_loop,
+            engine_state: engine.engine_state,
+            pause_on_focus_loss: engine.pause_on_focus_loss,
+            fixed_delta: engine.fixed_delta,
+            frame_rate_cap: engine.frame_rate_cap,
+            stepper: engine.stepper,
+            input_mode: engine.input_mode,
+            renderer: wrap(engine.renderer),
+        })
+    }
+
+    /// Logs the input and delta of every frame from now on, get the log with
+    /// [VNEngine::input_log] after the run
+    pub fn record_input(&mut self) {
+        let log = InputLog::new(self.engine_state.width, self.engine_state.height);
+        self.input_mode = InputMode::Record {
+            log,
+            events: Vec::new(),
+        };
+    }
+
+    /// Feeds a recorded log to the runner instead of the real input and frame times. Window
+    /// events are still passed to [VNERunner::event], except the ones concerning input.
+    pub fn replay_input(&mut self, log: InputLog) {
+        self.input_mode = InputMode::Replay { log, next_frame: 0 };
+    }
+
+    /// the log being recorded or replayed
+    pub fn input_log(&self) -> Option<&InputLog> {
+        match &self.input_mode {
+            InputMode::Live => None,
+            InputMode::Record { log, .. } | InputMode::Replay { log, .. } => Some(log),
+        }
+    }
+
     pub fn run(&mut self, runner: &mut impl VNERunner) {
         let mut previous_frame_time = Instant::now();
 
         let mut event_loop = &mut self.event_loop;
         let renderer = &mut self.renderer;
 
+        let size = renderer.window().inner_size();
+        resize(&mut self.engine_state, renderer, size);
         runner.setup(&self.engine_state, renderer);
 
         event_loop.run_return(|event, _, control_flow| {
-            *control_flow = ControlFlow::Poll;
-
             match event {
                 Event::MainEventsCleared => {
+                    // winit only looks at the control flow after the redraw events, so it is only
+                    // set here and the other events leave it alone
+                    if let Some(cap) = self.frame_rate_cap {
+                        let next_frame_time =
+                            previous_frame_time + Duration::from_secs_f64(1.0 / cap);
+                        if Instant::now() < next_frame_time {
+                            *control_flow = ControlFlow::WaitUntil(next_frame_time);
+                            return;
+                        }
+                    }
+                    *control_flow = ControlFlow::Poll;
+
                     let delta = previous_frame_time.elapsed().as_nanos();
-                    self.engine_state.delta = delta as f64 / 1_000_000_000.0;
+                    self.engine_state.delta = match self.fixed_delta {
+                        Some(fixed_delta) => fixed_delta,
+                        None => delta as f64 / 1_000_000_000.0,
+                    };
 
                     previous_frame_time = Instant::now();
                     if delta > 0 && (self.engine_state.focused || !self.pause_on_focus_loss) {
-                        let inner_pos = renderer.window().inner_position().unwrap();
-                        self.engine_state.inner_mouse_pos = (inner_pos.x, inner_pos.y);
-                        runner.tick(&self.engine_state, renderer);
-                        renderer.commit();
+                        match &mut self.input_mode {
+                            InputMode::Live => {}
+                            InputMode::Record { log, events } => log.push_frame(InputFrame {
+                                delta: self.engine_state.delta,
+                                events: std::mem::take(events),
+                            }),
+                            InputMode::Replay { log, next_frame } => {
+                                match log.frames().get(*next_frame) {
+                                    Some(frame) => frame.apply(&mut self.engine_state),
+                                    None => {
+                                        runner.tear_down(&self.engine_state, renderer);
+                                        *control_flow = ControlFlow::Exit;
+                                        return;
+                                    }
+                                }
+                                *next_frame += 1;
+                            }
+                        }
+                        self.stepper.run_frame(&mut self.engine_state, runner, renderer);
+                        if renderer.finished() {
+                            runner.tear_down(&self.engine_state, renderer);
+                            *control_flow = ControlFlow::Exit
+                        }
                     }
                 }
-                Event::WindowEvent {
-                    event: WindowEvent::Focused(in_focus),
-                    window_id,
-                } => {
-                    self.engine_state.focused = in_focus;
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    window_id,
-                } => {
-                    runner.tear_down(&self.engine_state, renderer);
-                    *control_flow = ControlFlow::Exit
+                Event::WindowEvent { event, .. } => {
+                    let input_event = self.engine_state.input_event(&event);
+                    match (&mut self.input_mode, input_event) {
+                        (_, None) => runner.event(&self.engine_state, &event),
+                        // the log is the only input while replaying
+                        (InputMode::Replay { .. }, Some(_)) => {}
+                        (InputMode::Live, Some(input_event)) => {
+                            self.engine_state.input.apply(&input_event);
+                            runner.event(&self.engine_state, &event);
+                        }
+                        (InputMode::Record { events, .. }, Some(input_event)) => {
+                            self.engine_state.input.apply(&input_event);
+                            events.push(input_event);
+                            runner.event(&self.engine_state, &event);
+                        }
+                    }
+                    match event {
+                        WindowEvent::Resized(size) => {
+                            resize(&mut self.engine_state, renderer, size);
+                            runner.resized(&self.engine_state, size.width, size.height);
+                        }
+                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                            let size = *new_inner_size;
+                            resize(&mut self.engine_state, renderer, size);
+                            runner.resized(&self.engine_state, size.width, size.height);
+                        }
+                        WindowEvent::Focused(in_focus) => self.engine_state.focused = in_focus,
+                        WindowEvent::CloseRequested => {
+                            runner.tear_down(&self.engine_state, renderer);
+                            *control_flow = ControlFlow::Exit
+                        }
+                        _ => (),
+                    }
                 }
                 _ => (),
             }
@@ -146,11 +401,96 @@ impl<T: VNEFullRenderer> VNEngine<T> {
     }
 }
 
+/// Fits the framebuffer into a window of the new size
+fn resize(
+    engine_state: &mut VNEngineState,
+    renderer: &mut impl VNERendererWindow,
+    size: PhysicalSize<u32>,
+) {
+    engine_state.window_width = size.width;
+    engine_state.window_height = size.height;
+    renderer.resize(size.width, size.height, engine_state.viewport());
+}
+
+/// Decides how a frame advances the runner, shared by the windowed and the headless engine
+#[derive(Default)]
+pub(crate) struct FrameStepper {
+    timestep: Option<FixedTimestep>,
+    accumulator: f64,
+}
+
+impl FrameStepper {
+    pub(crate) fn new(timestep: Option<FixedTimestep>) -> FrameStepper {
+        FrameStepper {
+            timestep,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Advances the runner by one frame and presents the result
+    pub(crate) fn run_frame<R: VNERenderer + VNERendererCommit + ?Sized>(
+        &mut self,
+        engine_state: &mut VNEngineState,
+        runner: &mut impl VNERunner,
+        renderer: &mut R,
+    ) {
+        // the sounds started during the previous frame play during this one
+        if let Some(audio) = &engine_state.audio {
+            audio.advance(engine_state.delta);
+        }
+        let timestep = match self.timestep {
+            None => {
+                runner.tick(engine_state, renderer);
+                renderer.commit();
+                engine_state.input.end_frame();
+                return;
+            }
+            Some(timestep) => timestep,
+        };
+
+        self.accumulator += engine_state.delta;
+        let mut steps = 0;
+        while self.accumulator >= timestep.dt && steps < timestep.max_steps_per_frame {
+            runner.fixed_update(engine_state, timestep.dt);
+            // presses and releases are seen by exactly one step, if no step runs this frame they
+            // are kept for the next one
+            if steps == 0 {
+                engine_state.input.end_frame();
+            }
+            self.accumulator -= timestep.dt;
+            steps += 1;
+        }
+        if steps == timestep.max_steps_per_frame {
+            self.accumulator %= timestep.dt;
+        }
+
+        runner.render(engine_state, renderer, self.accumulator / timestep.dt);
+        renderer.commit();
+    }
+}
+
 pub trait VNERunner {
     /// Do your setup code here
     fn setup(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {}
-    /// Gets called every frame
-    fn tick(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized));
+    /// Gets called every frame, unless the engine runs with a [FixedTimestep]
+    fn tick(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {}
+    /// Gets called zero or more times per frame with a [FixedTimestep], advance the simulation by
+    /// `dt` seconds here
+    fn fixed_update(&mut self, engine: &VNEngineState, dt: f64) {}
+    /// Gets called once per frame after the fixed updates with a [FixedTimestep]. `alpha` in
+    /// `[0, 1)` is how far the time has progressed towards the next update, use it to interpolate
+    /// between the previous and the current simulation state.
+    fn render(
+        &mut self,
+        engine: &VNEngineState,
+        renderer: &mut (impl VNERenderer + ?Sized),
+        alpha: f64,
+    ) {
+    }
+    /// Gets called when the window changed its size, `width` and `height` are in physical pixels
+    fn resized(&mut self, engine: &VNEngineState, width: u32, height: u32) {}
+    /// Gets called for every window event, after [VNEngineState::input] has been updated with it
+    fn event(&mut self, engine: &VNEngineState, event: &WindowEvent) {}
     /// do your teadown code here
     fn tear_down(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {}
 }
@@ -0,0 +1,476 @@
+use crate::color::RGBA;
+use crate::image::{Image, ImageError};
+use crate::render::{PixelPosition, VNERenderer};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// A monospace bitmap font, every glyph occupies a cell of the same size
+#[derive(Clone, Debug)]
+pub struct Font {
+    width: u32,
+    height: u32,
+    /// one entry per pixel of the cell, row by row
+    glyphs: HashMap<char, Vec<bool>>,
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    Image(ImageError),
+    /// line number and message
+    Bdf(usize, String),
+    Grid(String),
+}
+
+impl From<io::Error> for FontError {
+    fn from(value: io::Error) -> Self {
+        FontError::Io(value)
+    }
+}
+
+impl From<ImageError> for FontError {
+    fn from(value: ImageError) -> Self {
+        FontError::Image(value)
+    }
+}
+
+impl Font {
+    /// The built-in 5x8 font covering printable ASCII
+    pub fn builtin() -> &'static Font {
+        static BUILTIN: OnceLock<Font> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let glyphs = BUILTIN_GLYPHS
+                .iter()
+                .enumerate()
+                .map(|(idx, rows)| {
+                    let pixels = rows
+                        .iter()
+                        .flat_map(|row| (0..BUILTIN_WIDTH).map(move |x| row & (0x80 >> x) != 0))
+                        .collect();
+                    (char::from(b' ' + idx as u8), pixels)
+                })
+                .collect();
+            Font {
+                width: BUILTIN_WIDTH,
+                height: BUILTIN_HEIGHT,
+                glyphs,
+            }
+        })
+    }
+
+    /// width of a glyph cell
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// height of a glyph cell, which is also the line height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn has_glyph(&self, character: char) -> bool {
+        self.glyphs.contains_key(&character)
+    }
+
+    /// The size `text` covers when drawn at `scale`, lines are separated by `\n`
+    pub fn text_size(&self, text: &str, scale: u32) -> (u32, u32) {
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+        let lines = text.lines().count().max(1) as u32;
+        (columns * self.width * scale, lines * self.height * scale)
+    }
+
+    /// Draws `text` with its top left corner at `position`, every font pixel becomes a
+    /// `scale` x `scale` block. Characters without a glyph are drawn as `?` if the font has one.
+    pub fn draw<T: VNERenderer + ?Sized>(
+        &self,
+        renderer: &mut T,
+        position: PixelPosition,
+        text: &str,
+        color: RGBA,
+        scale: u32,
+    ) {
+        let scale = scale.max(1) as i32;
+        let (width, height) = (self.width as i32, self.height as i32);
+        for (line_idx, line) in text.lines().enumerate() {
+            let top = position.y + line_idx as i32 * height * scale;
+            for (column, character) in line.chars().enumerate() {
+                let glyph = match self.glyphs.get(&character).or(self.glyphs.get(&'?')) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let left = position.x + column as i32 * width * scale;
+                for y in 0..height {
+                    for x in 0..width {
+                        if !glyph[(y * width + x) as usize] {
+                            continue;
+                        }
+                        // the rectangle is clipped, so text may start off screen
+                        renderer.fill_rectangle(
+                            PixelPosition {
+                                x: left + x * scale,
+                                y: top + y * scale,
+                            },
+                            PixelPosition {
+                                x: left + x * scale + scale - 1,
+                                y: top + y * scale + scale - 1,
+                            },
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/////////
+// BDF //
+/////////
+
+/// bounding boxes larger than this or offset further are rejected, no bitmap font comes close
+const MAX_BDF_SIZE: i64 = 1024;
+
+impl Font {
+    /// Parses a font in the Glyph Bitmap Distribution Format. The cell is the font bounding box,
+    /// glyphs are placed in it according to their own bounding box. Proportional fonts are drawn
+    /// monospaced.
+    pub fn from_bdf(source: &str) -> Result<Font, FontError> {
+        let mut cell = None;
+        let mut glyphs = HashMap::new();
+
+        let mut lines = source.lines().enumerate();
+        while let Some((idx, line)) = lines.next() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("FONTBOUNDINGBOX") => cell = Some(bdf_box(idx, words, 1)?),
+                Some("STARTCHAR") => {
+                    let cell =
+                        cell.ok_or_else(|| bdf_error(idx, "STARTCHAR before FONTBOUNDINGBOX"))?;
+                    let (encoding, pixels) = read_bdf_char(&mut lines, cell)?;
+                    // glyphs without a unicode encoding are -1
+                    if let Some(character) = u32::try_from(encoding).ok().and_then(char::from_u32) {
+                        glyphs.insert(character, pixels);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match cell {
+            Some([width, height, ..]) => Ok(Font {
+                width: width as u32,
+                height: height as u32,
+                glyphs,
+            }),
+            None => Err(bdf_error(0, "missing FONTBOUNDINGBOX")),
+        }
+    }
+
+    pub fn load_bdf(path: impl AsRef<Path>) -> Result<Font, FontError> {
+        Font::from_bdf(&fs::read_to_string(path)?)
+    }
+}
+
+/// Reads everything up to ENDCHAR, returns the encoding and the pixels of the cell
+fn read_bdf_char<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    [width, height, x_offset, y_offset]: [i64; 4],
+) -> Result<(i64, Vec<bool>), FontError> {
+    let mut encoding = -1;
+    let mut bounds = None;
+    let mut pixels = vec![false; (width * height) as usize];
+
+    while let Some((idx, line)) = lines.next() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("ENCODING") => encoding = bdf_numbers::<1>(idx, words)?[0],
+            Some("BBX") => bounds = Some(bdf_box(idx, words, 0)?),
+            Some("BITMAP") => {
+                let [w, h, x_off, y_off] =
+                    bounds.ok_or_else(|| bdf_error(idx, "BITMAP before BBX"))?;
+                // position of the glyph box inside the cell, y grows downwards
+                let left = x_off - x_offset;
+                let top = (height + y_offset) - (h + y_off);
+                for row in 0..h {
+                    let (idx, line) = lines
+                        .next()
+                        .ok_or_else(|| bdf_error(idx, "bitmap ends early"))?;
+                    let line = line.trim();
+                    if line.is_empty() {
+                        return Err(bdf_error(idx, "empty bitmap row"));
+                    }
+                    // digit by digit, rows of glyphs wider than 64 pixels do not fit in a number
+                    for (digit, character) in (0..).zip(line.chars()) {
+                        let nibble = character.to_digit(16).ok_or_else(|| {
+                            bdf_error(idx, &format!("invalid bitmap row '{}'", line))
+                        })?;
+                        for bit in 0..4 {
+                            let column = digit * 4 + bit;
+                            let (x, y) = (left + column, top + row);
+                            if nibble & (8 >> bit) != 0
+                                && column < w
+                                && (0..width).contains(&x)
+                                && (0..height).contains(&y)
+                            {
+                                pixels[(y * width + x) as usize] = true;
+                            }
+                        }
+                    }
+                }
+            }
+            Some("ENDCHAR") => break,
+            _ => {}
+        }
+    }
+    Ok((encoding, pixels))
+}
+
+fn bdf_numbers<'a, const N: usize>(
+    idx: usize,
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<[i64; N], FontError> {
+    let mut numbers = [0; N];
+    for number in numbers.iter_mut() {
+        let word = words
+            .next()
+            .ok_or_else(|| bdf_error(idx, &format!("expected {} numbers", N)))?;
+        *number = word
+            .parse()
+            .map_err(|_| bdf_error(idx, &format!("'{}' is not a number", word)))?;
+    }
+    Ok(numbers)
+}
+
+/// A bounding box of width, height and the offsets, the size has to be at least `min`
+fn bdf_box<'a>(
+    idx: usize,
+    words: impl Iterator<Item = &'a str>,
+    min: i64,
+) -> Result<[i64; 4], FontError> {
+    let numbers = bdf_numbers::<4>(idx, words)?;
+    let [width, height, x_offset, y_offset] = numbers;
+    if ![width, height]
+        .iter()
+        .all(|size| (min..=MAX_BDF_SIZE).contains(size))
+    {
+        return Err(bdf_error(
+            idx,
+            &format!("invalid bounding box size {}x{}", width, height),
+        ));
+    }
+    if ![x_offset, y_offset]
+        .iter()
+        .all(|offset| offset.abs() <= MAX_BDF_SIZE)
+    {
+        return Err(bdf_error(
+            idx,
+            &format!("invalid bounding box offset {} {}", x_offset, y_offset),
+        ));
+    }
+    Ok(numbers)
+}
+
+fn bdf_error(idx: usize, message: &str) -> FontError {
+    FontError::Bdf(idx + 1, message.to_string())
+}
+
+//////////
+// GRID //
+//////////
+
+impl Font {
+    /// Slices an image into cells of `width` x `height`, left to right and top to bottom, and
+    /// assigns them to the characters of `characters` in order. Opaque light pixels are set,
+    /// dark or transparent ones are not.
+    pub fn from_grid(
+        image: &Image,
+        width: u32,
+        height: u32,
+        characters: &str,
+    ) -> Result<Font, FontError> {
+        if width == 0 || height == 0 {
+            return Err(FontError::Grid("the cell size must not be 0".to_string()));
+        }
+        let columns = image.width() / width;
+        let cells = columns * (image.height() / height);
+        if characters.chars().count() > cells as usize {
+            return Err(FontError::Grid(format!(
+                "{} characters, but the image only has {} cells",
+                characters.chars().count(),
+                cells
+            )));
+        }
+
+        let mut glyphs = HashMap::new();
+        for (idx, character) in characters.chars().enumerate() {
+            let left = (idx as u32 % columns) * width;
+            let top = (idx as u32 / columns) * height;
+            let mut pixels = Vec::with_capacity(width as usize * height as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    let color = image.pixel(left + x, top + y);
+                    pixels.push(color.a >= 0.5 && (color.r + color.g + color.b) / 3.0 >= 0.5);
+                }
+            }
+            glyphs.insert(character, pixels);
+        }
+
+        Ok(Font {
+            width,
+            height,
+            glyphs,
+        })
+    }
+
+    /// A PNG grid font, see [Font::from_grid]
+    pub fn load_grid(
+        path: impl AsRef<Path>,
+        width: u32,
+        height: u32,
+        characters: &str,
+    ) -> Result<Font, FontError> {
+        Font::from_grid(&Image::load_png(path)?, width, height, characters)
+    }
+}
+
+/////////////
+// BUILTIN //
+/////////////
+
+// derived from the public domain X11 misc-fixed 5x8 font, one byte per row, leftmost pixel in
+// the highest bit
+const BUILTIN_WIDTH: u32 = 5;
+const BUILTIN_HEIGHT: u32 = 8;
+#[rustfmt::skip]
+const BUILTIN_GLYPHS: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x20, 0x20, 0x20, 0x20, 0x00, 0x20, 0x00], // '!'
+    [0x00, 0x50, 0x50, 0x50, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x50, 0x50, 0xf8, 0x50, 0xf8, 0x50, 0x50, 0x00], // '#'
+    [0x20, 0x70, 0xa0, 0x70, 0x28, 0x70, 0x20, 0x00], // '$'
+    [0x00, 0x40, 0x50, 0x20, 0x50, 0x10, 0x00, 0x00], // '%'
+    [0x40, 0xa0, 0xa0, 0x40, 0xa0, 0xa0, 0x50, 0x00], // '&'
+    [0x00, 0x20, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x00, 0x20, 0x40, 0x40, 0x40, 0x40, 0x20, 0x00], // '('
+    [0x00, 0x40, 0x20, 0x20, 0x20, 0x20, 0x40, 0x00], // ')'
+    [0x00, 0x00, 0x90, 0x60, 0xf0, 0x60, 0x90, 0x00], // '*'
+    [0x00, 0x00, 0x20, 0x20, 0xf8, 0x20, 0x20, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x20, 0x40], // ','
+    [0x00, 0x00, 0x00, 0x00, 0xf0, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x70, 0x20], // '.'
+    [0x00, 0x10, 0x10, 0x20, 0x40, 0x80, 0x80, 0x00], // '/'
+    [0x00, 0x20, 0x50, 0x50, 0x50, 0x50, 0x20, 0x00], // '0'
+    [0x00, 0x20, 0x60, 0x20, 0x20, 0x20, 0x70, 0x00], // '1'
+    [0x00, 0x60, 0x90, 0x10, 0x60, 0x80, 0xf0, 0x00], // '2'
+    [0x00, 0xf0, 0x20, 0x60, 0x10, 0x90, 0x60, 0x00], // '3'
+    [0x00, 0x20, 0x60, 0xa0, 0xf0, 0x20, 0x20, 0x00], // '4'
+    [0x00, 0xf0, 0x80, 0xe0, 0x10, 0x90, 0x60, 0x00], // '5'
+    [0x00, 0x60, 0x80, 0xe0, 0x90, 0x90, 0x60, 0x00], // '6'
+    [0x00, 0xf0, 0x10, 0x20, 0x20, 0x40, 0x40, 0x00], // '7'
+    [0x00, 0x60, 0x90, 0x60, 0x90, 0x90, 0x60, 0x00], // '8'
+    [0x00, 0x60, 0x90, 0x90, 0x70, 0x10, 0x60, 0x00], // '9'
+    [0x00, 0x00, 0x60, 0x60, 0x00, 0x60, 0x60, 0x00], // ':'
+    [0x00, 0x00, 0x30, 0x30, 0x00, 0x30, 0x20, 0x40], // ';'
+    [0x00, 0x10, 0x20, 0x40, 0x40, 0x20, 0x10, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0xf0, 0x00, 0xf0, 0x00, 0x00], // '='
+    [0x00, 0x40, 0x20, 0x10, 0x10, 0x20, 0x40, 0x00], // '>'
+    [0x00, 0x20, 0x50, 0x10, 0x20, 0x00, 0x20, 0x00], // '?'
+    [0x30, 0x48, 0x98, 0xa8, 0xa8, 0x90, 0x40, 0x30], // '@'
+    [0x00, 0x60, 0x90, 0x90, 0xf0, 0x90, 0x90, 0x00], // 'A'
+    [0x00, 0xe0, 0x90, 0xe0, 0x90, 0x90, 0xe0, 0x00], // 'B'
+    [0x00, 0x60, 0x90, 0x80, 0x80, 0x90, 0x60, 0x00], // 'C'
+    [0x00, 0xe0, 0x90, 0x90, 0x90, 0x90, 0xe0, 0x00], // 'D'
+    [0x00, 0xf0, 0x80, 0xe0, 0x80, 0x80, 0xf0, 0x00], // 'E'
+    [0x00, 0xf0, 0x80, 0xe0, 0x80, 0x80, 0x80, 0x00], // 'F'
+    [0x00, 0x60, 0x90, 0x80, 0xb0, 0x90, 0x60, 0x00], // 'G'
+    [0x00, 0x90, 0x90, 0xf0, 0x90, 0x90, 0x90, 0x00], // 'H'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00], // 'I'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0xa0, 0x40, 0x00], // 'J'
+    [0x00, 0x90, 0xa0, 0xc0, 0xa0, 0xa0, 0x90, 0x00], // 'K'
+    [0x00, 0x80, 0x80, 0x80, 0x80, 0x80, 0xf0, 0x00], // 'L'
+    [0x00, 0x90, 0xf0, 0xf0, 0x90, 0x90, 0x90, 0x00], // 'M'
+    [0x00, 0x90, 0xd0, 0xf0, 0xb0, 0xb0, 0x90, 0x00], // 'N'
+    [0x00, 0x60, 0x90, 0x90, 0x90, 0x90, 0x60, 0x00], // 'O'
+    [0x00, 0xe0, 0x90, 0x90, 0xe0, 0x80, 0x80, 0x00], // 'P'
+    [0x00, 0x60, 0x90, 0x90, 0xd0, 0xb0, 0x60, 0x10], // 'Q'
+    [0x00, 0xe0, 0x90, 0x90, 0xe0, 0x90, 0x90, 0x00], // 'R'
+    [0x00, 0x60, 0x90, 0x40, 0x20, 0x90, 0x60, 0x00], // 'S'
+    [0x00, 0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00], // 'T'
+    [0x00, 0x90, 0x90, 0x90, 0x90, 0x90, 0x60, 0x00], // 'U'
+    [0x00, 0x90, 0x90, 0x90, 0x90, 0x60, 0x60, 0x00], // 'V'
+    [0x00, 0x90, 0x90, 0x90, 0xf0, 0xf0, 0x90, 0x00], // 'W'
+    [0x00, 0x90, 0x90, 0x60, 0x60, 0x90, 0x90, 0x00], // 'X'
+    [0x00, 0x88, 0x88, 0x50, 0x20, 0x20, 0x20, 0x00], // 'Y'
+    [0x00, 0xf0, 0x10, 0x20, 0x40, 0x80, 0xf0, 0x00], // 'Z'
+    [0x00, 0x70, 0x40, 0x40, 0x40, 0x40, 0x70, 0x00], // '['
+    [0x00, 0x80, 0x80, 0x40, 0x20, 0x10, 0x10, 0x00], // '\\'
+    [0x00, 0x70, 0x10, 0x10, 0x10, 0x10, 0x70, 0x00], // ']'
+    [0x00, 0x20, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0], // '_'
+    [0x00, 0x40, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x70, 0x90, 0x90, 0x70, 0x00], // 'a'
+    [0x00, 0x80, 0x80, 0xe0, 0x90, 0x90, 0xe0, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x30, 0x40, 0x40, 0x30, 0x00], // 'c'
+    [0x00, 0x10, 0x10, 0x70, 0x90, 0x90, 0x70, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x60, 0xb0, 0xc0, 0x60, 0x00], // 'e'
+    [0x00, 0x20, 0x50, 0x40, 0xe0, 0x40, 0x40, 0x00], // 'f'
+    [0x00, 0x00, 0x00, 0x60, 0x90, 0x70, 0x10, 0x60], // 'g'
+    [0x00, 0x80, 0x80, 0xe0, 0x90, 0x90, 0x90, 0x00], // 'h'
+    [0x00, 0x20, 0x00, 0x60, 0x20, 0x20, 0x70, 0x00], // 'i'
+    [0x00, 0x10, 0x00, 0x10, 0x10, 0x10, 0x50, 0x20], // 'j'
+    [0x00, 0x80, 0x80, 0x90, 0xe0, 0x90, 0x90, 0x00], // 'k'
+    [0x00, 0x60, 0x20, 0x20, 0x20, 0x20, 0x70, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0xd0, 0xa8, 0xa8, 0xa8, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0xe0, 0x90, 0x90, 0x90, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x60, 0x90, 0x90, 0x60, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0xe0, 0x90, 0xe0, 0x80, 0x80], // 'p'
+    [0x00, 0x00, 0x00, 0x70, 0x90, 0x70, 0x10, 0x10], // 'q'
+    [0x00, 0x00, 0x00, 0xa0, 0xd0, 0x80, 0x80, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x30, 0x60, 0x10, 0x60, 0x00], // 's'
+    [0x00, 0x40, 0x40, 0xe0, 0x40, 0x50, 0x20, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x90, 0x90, 0x90, 0x70, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x50, 0x50, 0x50, 0x20, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x88, 0xa8, 0xa8, 0x50, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x90, 0x60, 0x60, 0x90, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x90, 0x90, 0x70, 0x90, 0x60], // 'y'
+    [0x00, 0x00, 0x00, 0xf0, 0x20, 0x40, 0xf0, 0x00], // 'z'
+    [0x30, 0x40, 0x20, 0xc0, 0x20, 0x40, 0x30, 0x00], // '{'
+    [0x00, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00], // '|'
+    [0xc0, 0x20, 0x40, 0x30, 0x40, 0x20, 0xc0, 0x00], // '}'
+    [0x00, 0x50, 0xa0, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bdf(rows: &[&str]) -> String {
+        format!(
+            "STARTFONT 2.1\nFONTBOUNDINGBOX 80 {} 0 0\nSTARTCHAR A\nENCODING 65\nBBX 80 {} 0 0\nBITMAP\n{}\nENDCHAR\nENDFONT\n",
+            rows.len(),
+            rows.len(),
+            rows.join("\n")
+        )
+    }
+
+    #[test]
+    fn glyphs_wider_than_64_pixels() {
+        let wide = format!("8{}1", "0".repeat(18));
+        // more digits than the glyph is wide, with leading zeros
+        let long = format!("{}C", "0".repeat(30));
+        let font = Font::from_bdf(&bdf(&[&wide, &long])).unwrap();
+        let pixels = &font.glyphs[&'A'];
+        let set: Vec<usize> = (0..pixels.len()).filter(|idx| pixels[*idx]).collect();
+        assert_eq!(set, [0, 79]);
+    }
+
+    #[test]
+    fn invalid_bitmap_rows() {
+        for row in ["", "0G", "-1"] {
+            assert!(Font::from_bdf(&bdf(&[row])).is_err(), "{:?}", row);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::color::{BlendMode, BLACK, RGBA};
+use crate::engine::{FixedTimestep, FrameStepper, VNERunner, VNEngineState};
+use crate::image::Image;
+use crate::render::{ClipStack, PixelPosition, VNERenderer, VNERendererCommit};
+use crate::replay::InputLog;
+use crate::scaling::ScalingPolicy;
+use crate::shapes;
+use crate::target::{self, Layers, RenderTarget};
+use winit::event::WindowEvent;
+
+/// A renderer without window or graphics context, drawing into a plain RGBA framebuffer.
+/// Colors are stored with the same 8 bit precision as the windowed renderers.
+pub struct HeadlessRenderer {
+    width: u32,
+    height: u32,
+    /// the screen layer
+    color_buffer: Vec<RGBA>,
+    layers: Layers,
+    blend_mode: BlendMode,
+    clip: ClipStack,
+    title: String,
+    commits: u64,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: u32, height: u32) -> HeadlessRenderer {
+        HeadlessRenderer {
+            width,
+            height,
+            color_buffer: vec![BLACK; width as usize * height as usize],
+            layers: Layers::new(width, height),
+            blend_mode: BlendMode::Replace,
+            clip: ClipStack::new(width, height),
+            title: String::new(),
+            commits: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// the pixel of the screen layer at (x, y), panics if it is out of bounds.
+    /// [VNERenderer::capture_frame] gives the frame with all layers.
+    pub fn pixel(&self, x: u32, y: u32) -> RGBA {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "Index {:?} out of range of {:?}",
+                (x, y),
+                (self.width, self.height)
+            )
+        }
+        self.color_buffer[self.xy_index(x, y)]
+    }
+
+    /// all pixels of the screen layer row by row, starting at the top left
+    pub fn pixels(&self) -> &[RGBA] {
+        &self.color_buffer
+    }
+
+    /// the last title set through [VNERenderer::set_title]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// how often [VNERendererCommit::commit] was called
+    pub fn commits(&self) -> u64 {
+        self.commits
+    }
+
+    fn xy_index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+impl VNERenderer for HeadlessRenderer {
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    fn capture_frame(&self) -> Image {
+        if self.layers.is_empty() {
+            return Image::from_colors(self.width, self.height, &self.color_buffer);
+        }
+        let frame = self.layers.composited(&self.color_buffer);
+        Image::from_colors(self.width, self.height, &frame)
+    }
+
+    fn layer(&self) -> i32 {
+        self.layers.active()
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        self.layers.set_active(layer);
+    }
+
+    fn draw_target(&mut self, target: &RenderTarget, position: PixelPosition, mode: BlendMode) {
+        let clip = self.clip.current();
+        let buffer = self.layers.buffer(&mut self.color_buffer);
+        target::draw_into_buffer(buffer, self.width, clip, target, position, mode);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        &self.clip
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        &mut self.clip
+    }
+
+    fn fill_triangle(
+        &mut self,
+        v1: PixelPosition,
+        v2: PixelPosition,
+        v3: PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::triangle_spans(v1, v2, v3, self.clip.current());
+        shapes::fill_color_buffer(
+            self.layers.buffer(&mut self.color_buffer),
+            self.width,
+            spans,
+            color,
+            self.blend_mode,
+        );
+    }
+
+    fn fill_rectangle(
+        &mut self,
+        top_left: PixelPosition,
+        bottom_right: PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::rectangle_spans(top_left, bottom_right, self.clip.current());
+        shapes::fill_color_buffer(
+            self.layers.buffer(&mut self.color_buffer),
+            self.width,
+            spans,
+            color,
+            self.blend_mode,
+        );
+    }
+
+    fn clear_screen(&mut self, color: RGBA) {
+        let color = color.quantized();
+        self.layers.buffer(&mut self.color_buffer).fill(color);
+    }
+
+    fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+        if !self.clip.current().contains(position) {
+            return;
+        }
+        let idx = self.xy_index(position.x as u32, position.y as u32);
+        let buffer = self.layers.buffer(&mut self.color_buffer);
+        buffer[idx] = color.blend(buffer[idx], self.blend_mode).quantized();
+    }
+}
+
+impl VNERendererCommit for HeadlessRenderer {
+    fn commit(&mut self) {
+        self.commits += 1;
+    }
+}
+
+/// Drives a [VNERunner] without a display. Every frame advances by the same delta and input comes
+/// from a script instead of window events, so runs are reproducible.
+pub struct HeadlessEngine {
+    engine_state: VNEngineState,
+    renderer: HeadlessRenderer,
+    stepper: FrameStepper,
+    frame: u64,
+}
+
+impl HeadlessEngine {
+    pub fn new(width: u32, height: u32, delta: f64) -> HeadlessEngine {
+        HeadlessEngine {
+            engine_state: VNEngineState::scripted(width, height, delta),
+            renderer: HeadlessRenderer::new(width, height),
+            stepper: FrameStepper::default(),
+            frame: 0,
+        }
+    }
+
+    pub fn engine_state(&self) -> &VNEngineState {
+        &self.engine_state
+    }
+
+    pub fn engine_state_mut(&mut self) -> &mut VNEngineState {
+        &mut self.engine_state
+    }
+
+    pub fn renderer(&self) -> &HeadlessRenderer {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut HeadlessRenderer {
+        &mut self.renderer
+    }
+
+    /// Steps the runner through [VNERunner::fixed_update] and [VNERunner::render] instead of
+    /// [VNERunner::tick], every frame adds the delta of the engine state to the accumulator
+    pub fn set_fixed_timestep(&mut self, timestep: Option<FixedTimestep>) {
+        self.stepper = FrameStepper::new(timestep);
+    }
+
+    /// Pretends the framebuffer is shown in a window of the given size, which changes where
+    /// [HeadlessEngine::window_event] maps the cursor to
+    pub fn resize_window(&mut self, width: u32, height: u32) {
+        self.engine_state.window_width = width;
+        self.engine_state.window_height = height;
+    }
+
+    pub fn set_scaling(&mut self, scaling: ScalingPolicy) {
+        self.engine_state.scaling = scaling;
+    }
+
+    /// Applies the input of a window event like the windowed engine does
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        if let Some(input_event) = self.engine_state.input_event(event) {
+            self.engine_state.input_mut().apply(&input_event);
+        }
+    }
+
+    /// number of frames ticked so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn setup(&mut self, runner: &mut impl VNERunner) {
+        runner.setup(&self.engine_state, &mut self.renderer);
+    }
+
+    /// Ticks a single frame and commits it
+    pub fn tick(&mut self, runner: &mut impl VNERunner) {
+        self.stepper
+            .run_frame(&mut self.engine_state, runner, &mut self.renderer);
+        self.frame += 1;
+    }
+
+    /// Ticks `frames` frames. Before each one `script` gets the number of the upcoming frame and
+    /// can change the engine state, e.g. to press keys or move the mouse through
+    /// [VNEngineState::input_mut].
+    pub fn run(
+        &mut self,
+        runner: &mut impl VNERunner,
+        frames: u64,
+        mut script: impl FnMut(u64, &mut VNEngineState),
+    ) {
+        for _ in 0..frames {
+            script(self.frame, &mut self.engine_state);
+            self.tick(runner);
+        }
+    }
+
+    /// Ticks one frame per frame of the log, with its input and delta. Start from an engine of
+    /// the log's size to get the frames of the recorded session.
+    pub fn replay(&mut self, runner: &mut impl VNERunner, log: &InputLog) {
+        for frame in log.frames() {
+            frame.apply(&mut self.engine_state);
+            self.tick(runner);
+        }
+    }
+
+    pub fn tear_down(&mut self, runner: &mut impl VNERunner) {
+        runner.tear_down(&self.engine_state, &mut self.renderer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalPosition;
+    use winit::event::DeviceId;
+
+    #[allow(deprecated)]
+    fn move_cursor(engine: &mut HeadlessEngine, x: f64, y: f64) -> Option<(f64, f64)> {
+        engine.window_event(&WindowEvent::CursorMoved {
+            // SAFETY: the id is only compared, never passed to the platform
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(x, y),
+            modifiers: Default::default(),
+        });
+        engine.engine_state().input().cursor_position()
+    }
+
+    #[test]
+    fn cursor_is_mapped_into_the_viewport() {
+        let mut engine = HeadlessEngine::new(320, 200, 1.0 / 60.0);
+        // without a resize the window has the size of the framebuffer
+        assert_eq!(move_cursor(&mut engine, 10.0, 20.0), Some((10.0, 20.0)));
+
+        // 960x600 at (20, 50)
+        engine.resize_window(1000, 700);
+        assert_eq!(move_cursor(&mut engine, 20.0, 50.0), Some((0.0, 0.0)));
+        assert_eq!(move_cursor(&mut engine, 500.0, 350.0), Some((160.0, 100.0)));
+        assert_eq!(move_cursor(&mut engine, 10.0, 350.0), None);
+
+        // 1000x625 at (0, 37)
+        engine.set_scaling(ScalingPolicy::Letterbox);
+        assert_eq!(move_cursor(&mut engine, 0.0, 37.0), Some((0.0, 0.0)));
+        assert_eq!(move_cursor(&mut engine, 500.0, 349.5), Some((160.0, 100.0)));
+        assert_eq!(move_cursor(&mut engine, 500.0, 20.0), None);
+
+        // the window is smaller than the framebuffer, integer scaling letterboxes to 240x150
+        engine.set_scaling(ScalingPolicy::Integer);
+        engine.resize_window(300, 150);
+        assert_eq!(move_cursor(&mut engine, 30.0, 0.0), Some((0.0, 0.0)));
+        assert_eq!(move_cursor(&mut engine, 150.0, 75.0), Some((160.0, 100.0)));
+        assert_eq!(move_cursor(&mut engine, 20.0, 75.0), None);
+
+        engine.resize_window(0, 0);
+        assert_eq!(move_cursor(&mut engine, 0.0, 0.0), None);
+    }
+}
@@ -0,0 +1,484 @@
+use crate::color::RGBA;
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// An 8 bit RGBA image, stored row by row starting at the top left.
+/// Used for captured frames and loaded images.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// `data` has to contain 4 bytes per pixel
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Image {
+        if data.len() != width as usize * height as usize * 4 {
+            panic!(
+                "Expected {} bytes for an image of {:?}, got {}",
+                width as usize * height as usize * 4,
+                (width, height),
+                data.len()
+            )
+        }
+        Image {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn from_colors<'a>(
+        width: u32,
+        height: u32,
+        colors: impl IntoIterator<Item = &'a RGBA>,
+    ) -> Image {
+        let data = colors.into_iter().flat_map(|c| c.to_bytes()).collect();
+        Image::new(width, height, data)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// the raw RGBA bytes
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// the pixel at (x, y), panics if it is out of bounds
+    pub fn pixel(&self, x: u32, y: u32) -> RGBA {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "Index {:?} out of range of {:?}",
+                (x, y),
+                (self.width, self.height)
+            )
+        }
+        let idx = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.data[idx..idx + 4];
+        RGBA::from_bytes(p[0], p[1], p[2], p[3])
+    }
+
+    /// Encodes the image as an RGBA PNG
+    pub fn encode_png(&self) -> Result<Vec<u8>, ImageError> {
+        let mut png = vec![];
+        let mut encoder = Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_encoding_error)?;
+        writer
+            .write_image_data(&self.data)
+            .map_err(png_encoding_error)?;
+        writer.finish().map_err(png_encoding_error)?;
+        Ok(png)
+    }
+
+    /// Encodes the image as a binary PPM (P6), the alpha channel is dropped
+    pub fn encode_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        ppm.reserve(self.width as usize * self.height as usize * 3);
+        for pixel in self.data.chunks(4) {
+            ppm.extend_from_slice(&pixel[..3]);
+        }
+        ppm
+    }
+
+    /// Decodes a PNG of any color type and bit depth, converting it to 8 bit RGBA
+    pub fn decode_png(data: &[u8]) -> Result<Image, ImageError> {
+        let mut decoder = Decoder::new(data);
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(png_error)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(png_error)?;
+        buffer.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            ColorType::Rgba => buffer,
+            ColorType::Rgb => buffer
+                .chunks(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            ColorType::GrayscaleAlpha => buffer
+                .chunks(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            ColorType::Grayscale => buffer.iter().flat_map(|v| [*v, *v, *v, 255]).collect(),
+            ColorType::Indexed => {
+                return Err(ImageError::Format(
+                    "indexed png was not expanded".to_string(),
+                ))
+            }
+        };
+        Ok(Image::new(info.width, info.height, data))
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Image, ImageError> {
+        Image::decode_png(&fs::read(path)?)
+    }
+
+    /// Decodes a PNG, PPM/PGM or BMP image, the format is detected from the data
+    pub fn decode(data: &[u8]) -> Result<Image, ImageError> {
+        if data.starts_with(&PNG_SIGNATURE) {
+            Image::decode_png(data)
+        } else if data.starts_with(b"BM") {
+            Image::decode_bmp(data)
+        } else if data.starts_with(b"P") {
+            Image::decode_ppm(data)
+        } else {
+            Err(ImageError::Format("unknown image format".to_string()))
+        }
+    }
+
+    /// Loads a PNG, PPM/PGM or BMP image, see [Image::decode]
+    pub fn load(path: impl AsRef<Path>) -> Result<Image, ImageError> {
+        Image::decode(&fs::read(path)?)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        fs::write(path, self.encode_png()?)?;
+        Ok(())
+    }
+
+    pub fn save_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.encode_ppm())
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// the data is not a valid or supported image
+    Format(String),
+}
+
+impl From<io::Error> for ImageError {
+    fn from(value: io::Error) -> Self {
+        ImageError::Io(value)
+    }
+}
+
+/////////
+// PNG //
+/////////
+
+fn png_error(error: png::DecodingError) -> ImageError {
+    match error {
+        png::DecodingError::IoError(e) => ImageError::Io(e),
+        e => ImageError::Format(e.to_string()),
+    }
+}
+
+fn png_encoding_error(error: png::EncodingError) -> ImageError {
+    match error {
+        png::EncodingError::IoError(e) => ImageError::Io(e),
+        e => ImageError::Format(e.to_string()),
+    }
+}
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/////////
+// PPM //
+/////////
+
+impl Image {
+    /// Decodes the netpbm formats PPM and PGM, in their binary (P6, P5) and plain (P3, P2) forms
+    pub fn decode_ppm(data: &[u8]) -> Result<Image, ImageError> {
+        let mut position = 0;
+        let magic = ppm_token(data, &mut position)?;
+        let channels = match magic {
+            b"P2" | b"P5" => 1,
+            b"P3" | b"P6" => 3,
+            _ => return Err(ppm_error("unsupported netpbm type")),
+        };
+        let width = ppm_number(data, &mut position)?;
+        let height = ppm_number(data, &mut position)?;
+        let max_value = ppm_number(data, &mut position)?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(ppm_error("invalid maximum value"));
+        }
+
+        // every plain sample takes at least one byte, so the size is checked before reserving
+        let bytes_per_sample = match magic {
+            b"P2" | b"P3" => 1,
+            _ if max_value < 256 => 1,
+            _ => 2,
+        };
+        let samples = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .filter(|samples| {
+                samples
+                    .checked_mul(bytes_per_sample)
+                    .is_some_and(|size| size <= data.len() - position)
+            })
+            .ok_or_else(|| ppm_error("image data ends early"))?;
+        let mut values = Vec::with_capacity(samples);
+        if magic == b"P2" || magic == b"P3" {
+            for _ in 0..samples {
+                values.push(ppm_number(data, &mut position)?);
+            }
+        } else {
+            // a single whitespace separates the header from the binary data
+            let start = position + 1;
+            let raster = data
+                .get(start..start + samples * bytes_per_sample)
+                .ok_or_else(|| ppm_error("image data ends early"))?;
+            if bytes_per_sample == 1 {
+                values.extend(raster.iter().map(|v| *v as u32));
+            } else {
+                values.extend(
+                    raster
+                        .chunks(2)
+                        .map(|v| u16::from_be_bytes([v[0], v[1]]) as u32),
+                );
+            }
+        }
+
+        let scale = |v: u32| (v.min(max_value) * 255 / max_value) as u8;
+        let data = values
+            .chunks(channels)
+            .flat_map(|p| match p {
+                [v] => [scale(*v), scale(*v), scale(*v), 255],
+                _ => [scale(p[0]), scale(p[1]), scale(p[2]), 255],
+            })
+            .collect();
+        Ok(Image::new(width, height, data))
+    }
+}
+
+/// The next whitespace separated token, skipping `#` comments
+fn ppm_token<'a>(data: &'a [u8], position: &mut usize) -> Result<&'a [u8], ImageError> {
+    loop {
+        match data.get(*position) {
+            Some(b'#') => {
+                while !matches!(data.get(*position), Some(b'\n') | None) {
+                    *position += 1;
+                }
+            }
+            Some(c) if c.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+            None => return Err(ppm_error("unexpected end of data")),
+        }
+    }
+    let start = *position;
+    while matches!(data.get(*position), Some(c) if !c.is_ascii_whitespace()) {
+        *position += 1;
+    }
+    Ok(&data[start..*position])
+}
+
+fn ppm_number(data: &[u8], position: &mut usize) -> Result<u32, ImageError> {
+    let token = ppm_token(data, position)?;
+    std::str::from_utf8(token)
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(|| ppm_error("expected a number"))
+}
+
+fn ppm_error(message: &str) -> ImageError {
+    ImageError::Format(format!("ppm: {}", message))
+}
+
+/////////
+// BMP //
+/////////
+
+impl Image {
+    /// Decodes uncompressed Windows bitmaps with 1, 4, 8, 16, 24 or 32 bits per pixel, including
+    /// bit field masks. Bitmaps without alpha mask are opaque.
+    pub fn decode_bmp(data: &[u8]) -> Result<Image, ImageError> {
+        let u16_at = |offset: usize| {
+            data.get(offset..offset + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+                .ok_or_else(|| bmp_error("header ends early"))
+        };
+        let u32_at = |offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or_else(|| bmp_error("header ends early"))
+        };
+
+        let pixel_offset = u32_at(10)? as usize;
+        let header_size = u32_at(14)? as usize;
+        if header_size < 40 {
+            return Err(bmp_error("only BITMAPINFOHEADER and newer are supported"));
+        }
+        let width = u32_at(18)? as i32;
+        let height = u32_at(22)? as i32;
+        let bits = u16_at(28)?;
+        let compression = u32_at(30)?;
+        let palette_size = u32_at(46)? as usize;
+        if width <= 0 || height == 0 {
+            return Err(bmp_error("invalid size"));
+        }
+        // a negative height stores the rows top down
+        let top_down = height < 0;
+        let (width, height) = (width as u32, height.unsigned_abs());
+
+        // BI_RGB or BI_BITFIELDS/BI_ALPHABITFIELDS, whose masks follow the 40 byte header
+        let masks = match (compression, bits) {
+            (0, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+            (0, 24) | (0, 32) => [0xff_0000, 0x00_ff00, 0x00_00ff, 0],
+            (3, 16) | (3, 32) | (6, 16) | (6, 32) => [
+                u32_at(54)?,
+                u32_at(58)?,
+                u32_at(62)?,
+                if header_size >= 56 || compression == 6 {
+                    u32_at(66)?
+                } else {
+                    0
+                },
+            ],
+            (0, 1) | (0, 4) | (0, 8) => [0; 4],
+            _ => return Err(bmp_error("compressed bitmaps are not supported")),
+        };
+
+        let palette = if bits <= 8 {
+            let entries = if palette_size == 0 {
+                1 << bits
+            } else {
+                palette_size
+            };
+            let start = 14 + header_size;
+            let palette = data
+                .get(start..start + entries * 4)
+                .ok_or_else(|| bmp_error("palette ends early"))?;
+            palette.chunks(4).map(|c| [c[2], c[1], c[0], 255]).collect()
+        } else {
+            vec![]
+        };
+
+        // rows are padded to multiples of 4 bytes
+        let row_size = (width as usize * bits as usize).div_ceil(32) * 4;
+        let fits = row_size
+            .checked_mul(height as usize)
+            .and_then(|size| size.checked_add(pixel_offset))
+            .is_some_and(|end| end <= data.len());
+        if !fits {
+            return Err(bmp_error("image data ends early"));
+        }
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height as usize {
+            let row = if top_down { y } else { height as usize - 1 - y };
+            let start = pixel_offset + row * row_size;
+            let row = data
+                .get(start..start + row_size)
+                .ok_or_else(|| bmp_error("image data ends early"))?;
+            for x in 0..width as usize {
+                let pixel = match bits {
+                    1 | 4 | 8 => {
+                        let bit = x * bits as usize;
+                        let shift = 8 - bits as usize - bit % 8;
+                        let index = (row[bit / 8] as u32 >> shift) & ((1 << bits) - 1);
+                        *palette
+                            .get(index as usize)
+                            .ok_or_else(|| bmp_error("palette index out of range"))?
+                    }
+                    _ => {
+                        let bytes = bits as usize / 8;
+                        let mut value = 0;
+                        for (idx, byte) in row[x * bytes..(x + 1) * bytes].iter().enumerate() {
+                            value |= (*byte as u32) << (idx * 8);
+                        }
+                        let [r, g, b, a] = masks.map(|mask| bmp_channel(value, mask));
+                        [r, g, b, if masks[3] == 0 { 255 } else { a }]
+                    }
+                };
+                pixels.extend_from_slice(&pixel);
+            }
+        }
+        Ok(Image::new(width, height, pixels))
+    }
+}
+
+/// Extracts the bits of `mask` from `value` and scales them to 8 bits
+fn bmp_channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = mask >> mask.trailing_zeros();
+    (((value & mask) >> mask.trailing_zeros()) as u64 * 255 / max as u64) as u8
+}
+
+fn bmp_error(message: &str) -> ImageError {
+    ImageError::Format(format!("bmp: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 24 bit bitmap of the given size, `pixels` does not have to match it
+    fn bmp(width: i32, height: i32, pixels: &[u8]) -> Vec<u8> {
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&(54 + pixels.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&54u32.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&24u16.to_le_bytes());
+        data.extend_from_slice(&[0; 24]);
+        data.extend_from_slice(pixels);
+        data
+    }
+
+    #[test]
+    fn ppm_decodes() {
+        let image = Image::decode_ppm(b"P6 2 1 255\n\xff\x00\x00\x00\x80\xff").unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.data, [255, 0, 0, 255, 0, 128, 255, 255]);
+
+        let image = Image::decode_ppm(b"P2\n# gray\n1 2\n4\n0 2").unwrap();
+        assert_eq!(image.data, [0, 0, 0, 255, 127, 127, 127, 255]);
+    }
+
+    #[test]
+    fn ppm_sizes_beyond_the_data_are_rejected() {
+        for data in [
+            &b"P6 100000 100000 255\n\x00\x00\x00"[..],
+            b"P6 4294967295 4294967295 65535\n",
+            b"P3 4294967295 4294967295 255\n0 0 0",
+            b"P5 2 2 255\n\x00\x00\x00",
+        ] {
+            assert!(matches!(
+                Image::decode_ppm(data),
+                Err(ImageError::Format(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn bmp_decodes() {
+        // rows go bottom up and are padded to 4 bytes
+        let image = Image::decode_bmp(&bmp(1, 2, &[0, 0, 255, 0, 0, 255, 0, 0])).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq!(image.data, [0, 255, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bmp_sizes_beyond_the_data_are_rejected() {
+        for (width, height) in [
+            (i32::MAX, i32::MAX),
+            (i32::MAX, i32::MIN + 1),
+            (1, 3),
+            (100000, 100000),
+        ] {
+            let data = bmp(width, height, &[0; 8]);
+            assert!(matches!(
+                Image::decode_bmp(&data),
+                Err(ImageError::Format(_))
+            ));
+        }
+    }
+}
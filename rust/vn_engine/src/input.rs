@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+pub type Keycode = VirtualKeyCode;
+
+/// Keyboard and mouse state, fed by the window events the engine receives.
+/// "Pressed" and "released" only hold for the frame in which the change happened, "down" holds
+/// as long as the key or button is held.
+#[derive(Debug, Default, Clone)]
+pub struct Input {
+    keys_down: HashSet<Keycode>,
+    keys_pressed: HashSet<Keycode>,
+    keys_released: HashSet<Keycode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    cursor_position: Option<(f64, f64)>,
+    scroll_lines: (f32, f32),
+    scroll_pixels: (f64, f64),
+    text: String,
+    frame: u64,
+}
+
+//////////////
+// QUERYING //
+//////////////
+
+impl Input {
+    pub fn key_down(&self, key: Keycode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// true in the frame the key went down
+    pub fn key_pressed(&self, key: Keycode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    /// true in the frame the key went up
+    pub fn key_released(&self, key: Keycode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn keys_down(&self) -> impl Iterator<Item = Keycode> + '_ {
+        self.keys_down.iter().copied()
+    }
+
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    /// true in the frame the button went down
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    /// true in the frame the button went up
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// the cursor in framebuffer coordinates, `None` while it is outside of the window or of the
+    /// area the framebuffer is drawn to
+    pub fn cursor_position(&self) -> Option<(f64, f64)> {
+        self.cursor_position
+    }
+
+    /// scrolled lines in this frame, from mouse wheels
+    pub fn scroll_lines(&self) -> (f32, f32) {
+        self.scroll_lines
+    }
+
+    /// scrolled pixels in this frame, from touchpads
+    pub fn scroll_pixels(&self) -> (f64, f64) {
+        self.scroll_pixels
+    }
+
+    /// how often [Input::end_frame] was called, presses, releases, scrolling and text belong to
+    /// this frame
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// text typed in this frame, without control characters
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+//////////////
+// UPDATING //
+//////////////
+
+/// A single change of the input state. Window events are turned into these, so they can be
+/// recorded and replayed, see [crate::replay].
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    PressKey(Keycode),
+    ReleaseKey(Keycode),
+    PressButton(MouseButton),
+    ReleaseButton(MouseButton),
+    MoveCursor(Option<(f64, f64)>),
+    ScrollLines(f32, f32),
+    ScrollPixels(f64, f64),
+    TypeText(String),
+    ReleaseAll,
+}
+
+impl InputEvent {
+    /// The change a window event makes, `None` for events not concerning input
+    pub fn from_window_event(event: &WindowEvent) -> Option<InputEvent> {
+        let input_event = match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => InputEvent::PressKey(*key),
+                ElementState::Released => InputEvent::ReleaseKey(*key),
+            },
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => InputEvent::PressButton(*button),
+                ElementState::Released => InputEvent::ReleaseButton(*button),
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                InputEvent::MoveCursor(Some((position.x, position.y)))
+            }
+            WindowEvent::CursorLeft { .. } => InputEvent::MoveCursor(None),
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => InputEvent::ScrollLines(*x, *y),
+                MouseScrollDelta::PixelDelta(position) => {
+                    InputEvent::ScrollPixels(position.x, position.y)
+                }
+            },
+            WindowEvent::ReceivedCharacter(character) => {
+                InputEvent::TypeText(character.to_string())
+            }
+            // the window will not see the matching release events
+            WindowEvent::Focused(false) => InputEvent::ReleaseAll,
+            _ => return None,
+        };
+        Some(input_event)
+    }
+}
+
+impl Input {
+    /// Updates the state from a window event, events not concerning input are ignored.
+    /// Cursor positions are taken as they are, the engine maps them to the framebuffer afterwards.
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        if let Some(input_event) = InputEvent::from_window_event(event) {
+            self.apply(&input_event);
+        }
+    }
+
+    pub fn apply(&mut self, event: &InputEvent) {
+        match event {
+            InputEvent::PressKey(key) => self.press_key(*key),
+            InputEvent::ReleaseKey(key) => self.release_key(*key),
+            InputEvent::PressButton(button) => self.press_button(*button),
+            InputEvent::ReleaseButton(button) => self.release_button(*button),
+            InputEvent::MoveCursor(position) => self.move_cursor(*position),
+            InputEvent::ScrollLines(x, y) => {
+                self.scroll_lines.0 += x;
+                self.scroll_lines.1 += y;
+            }
+            InputEvent::ScrollPixels(x, y) => {
+                self.scroll_pixels.0 += x;
+                self.scroll_pixels.1 += y;
+            }
+            InputEvent::TypeText(text) => self.type_text(text),
+            InputEvent::ReleaseAll => self.release_all(),
+        }
+    }
+
+    /// Forgets everything that only holds for a single frame, called after every tick
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.scroll_lines = (0.0, 0.0);
+        self.scroll_pixels = (0.0, 0.0);
+        self.text.clear();
+        self.frame += 1;
+    }
+
+    pub fn press_key(&mut self, key: Keycode) {
+        // key repeat sends further presses while the key is held
+        if self.keys_down.insert(key) {
+            self.keys_pressed.insert(key);
+        }
+    }
+
+    pub fn release_key(&mut self, key: Keycode) {
+        if self.keys_down.remove(&key) {
+            self.keys_released.insert(key);
+        }
+    }
+
+    pub fn press_button(&mut self, button: MouseButton) {
+        if self.buttons_down.insert(button) {
+            self.buttons_pressed.insert(button);
+        }
+    }
+
+    pub fn release_button(&mut self, button: MouseButton) {
+        if self.buttons_down.remove(&button) {
+            self.buttons_released.insert(button);
+        }
+    }
+
+    pub fn move_cursor(&mut self, position: Option<(f64, f64)>) {
+        self.cursor_position = position;
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        self.text.extend(text.chars().filter(|c| !c.is_control()));
+    }
+
+    /// Releases all keys and buttons that are down
+    pub fn release_all(&mut self) {
+        for key in std::mem::take(&mut self.keys_down) {
+            self.keys_released.insert(key);
+        }
+        for button in std::mem::take(&mut self.buttons_down) {
+            self.buttons_released.insert(button);
+        }
+    }
+}
@@ -3,10 +3,26 @@ use crate::engine::{VNERunner, VNEngine};
 use crate::opengl::OpenGLRenderer;
 use crate::render::{PixelPosition, VNERenderer};
 
+pub mod animation;
+pub mod audio;
+pub mod camera;
 pub mod color;
 pub mod engine;
+pub mod font;
+pub mod headless;
+pub mod image;
+pub mod input;
 pub mod opengl;
+pub mod palette;
+pub mod recorder;
 pub mod render;
-mod sprite;
+pub mod replay;
+pub mod scaling;
+pub mod scene;
+mod shapes;
+pub mod sprite;
+pub mod target;
+pub mod ui;
 
-pub use device_query::{Keycode, MouseState};
+pub use input::Keycode;
+pub use winit::event::MouseButton;
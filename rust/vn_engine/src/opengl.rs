@@ -1,6 +1,7 @@
 use crate::color;
-use crate::color::RGBA;
-use gl::types::{GLenum, GLuint};
+use crate::color::{BlendMode, RGBA};
+use crate::image::Image;
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
 use glutin::config::ConfigTemplateBuilder;
 use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext};
 use glutin::surface::{Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
@@ -13,7 +14,12 @@ use winit::dpi::PhysicalSize;
 use winit::event_loop::EventLoop;
 use winit::window::{Window, WindowBuilder};
 
-use crate::render::{VNEFullRenderer, VNERenderer, VNERendererCommit, VNERendererWindow};
+use crate::render::{
+    ClipStack, PixelPosition, VNEFullRenderer, VNERenderer, VNERendererCommit, VNERendererWindow,
+};
+use crate::scaling::Viewport;
+use crate::shapes;
+use crate::target::{self, Layers, RenderTarget};
 use glutin::display::GetGlDisplay;
 use glutin::prelude::*;
 use glutin_winit::{self};
@@ -211,24 +217,101 @@ pub struct OpenGLRenderer {
     pub window: Window,
     surface: Surface<WindowSurface>,
     context: PossiblyCurrentContext,
+    /// the screen layer
     color_buffer: Vec<RGBA>,
+    layers: Layers,
+    /// all layers merged, only used once there are layers besides the screen
+    frame: Vec<RGBA>,
+    blend_mode: BlendMode,
+    clip: ClipStack,
 }
 
 impl VNERenderer for OpenGLRenderer {
     fn clear_screen(&mut self, color: RGBA) {
-        for idx in 0..self.width as usize * self.height as usize {
-            self.color_buffer[idx] = color;
-        }
+        let color = color.quantized();
+        self.layers.buffer(&mut self.color_buffer).fill(color);
     }
 
     fn draw_pixel(&mut self, position: crate::render::PixelPosition, color: RGBA) {
-        let x = position.x;
-        let y = position.y;
-        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+        if !self.clip.current().contains(position) {
             return;
         }
-        let idx = self.xy_index(x, y);
-        self.color_buffer[idx] = color;
+        let idx = self.xy_index(position.x as u32, position.y as u32);
+        let buffer = self.layers.buffer(&mut self.color_buffer);
+        // quantized like the sprite based renderer, so both produce the same results
+        buffer[idx] = color.blend(buffer[idx], self.blend_mode).quantized();
+    }
+
+    fn capture_frame(&self) -> Image {
+        if self.layers.is_empty() {
+            // the color buffer is uploaded as is on commit, so it already holds the frame
+            return Image::from_colors(self.width, self.height, &self.color_buffer);
+        }
+        let frame = self.layers.composited(&self.color_buffer);
+        Image::from_colors(self.width, self.height, &frame)
+    }
+
+    fn layer(&self) -> i32 {
+        self.layers.active()
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        self.layers.set_active(layer);
+    }
+
+    fn draw_target(&mut self, target: &RenderTarget, position: PixelPosition, mode: BlendMode) {
+        let clip = self.clip.current();
+        let buffer = self.layers.buffer(&mut self.color_buffer);
+        target::draw_into_buffer(buffer, self.width, clip, target, position, mode);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        &self.clip
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        &mut self.clip
+    }
+
+    fn fill_triangle(
+        &mut self,
+        v1: PixelPosition,
+        v2: PixelPosition,
+        v3: PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::triangle_spans(v1, v2, v3, self.clip.current());
+        shapes::fill_color_buffer(
+            self.layers.buffer(&mut self.color_buffer),
+            self.width,
+            spans,
+            color,
+            self.blend_mode,
+        );
+    }
+
+    fn fill_rectangle(
+        &mut self,
+        top_left: PixelPosition,
+        bottom_right: PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::rectangle_spans(top_left, bottom_right, self.clip.current());
+        shapes::fill_color_buffer(
+            self.layers.buffer(&mut self.color_buffer),
+            self.width,
+            spans,
+            color,
+            self.blend_mode,
+        );
     }
 
     fn set_title(&mut self, title: &str) {
@@ -238,14 +321,22 @@ impl VNERenderer for OpenGLRenderer {
 
 impl VNERendererCommit for OpenGLRenderer {
     fn commit(&mut self) {
+        let frame = if self.layers.is_empty() {
+            &self.color_buffer
+        } else {
+            self.layers.composite(&self.color_buffer, &mut self.frame);
+            &self.frame
+        };
         unsafe {
             gl::BufferSubData(
                 gl::ARRAY_BUFFER,
                 0,
-                size_of::<RGBA>() as isize * self.color_buffer.len() as isize,
-                self.color_buffer.as_ptr().cast(),
+                size_of::<RGBA>() as isize * frame.len() as isize,
+                frame.as_ptr().cast(),
             );
-            gl::DrawArrays(gl::POINTS, 0, self.color_buffer.len() as i32);
+            // the bars around the viewport
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::DrawArrays(gl::POINTS, 0, frame.len() as i32);
         }
         self.surface
             .swap_buffers(&self.context)
@@ -263,6 +354,39 @@ impl VNERendererWindow for OpenGLRenderer {
     fn window(&mut self) -> &Window {
         &self.window
     }
+
+    fn resize(&mut self, window_width: u32, window_height: u32, viewport: Viewport) {
+        resize_surface(&self.surface, &self.context, window_width, window_height, viewport);
+        unsafe {
+            // every framebuffer pixel is one point, it has to cover the scaled pixel
+            gl::PointSize((viewport.width as f32 / self.width as f32).ceil());
+        }
+    }
+}
+
+/// Resizes the surface and restricts drawing to `viewport`, does nothing for a minimized window
+pub(crate) fn resize_surface(
+    surface: &Surface<WindowSurface>,
+    context: &PossiblyCurrentContext,
+    window_width: u32,
+    window_height: u32,
+    viewport: Viewport,
+) {
+    let (width, height) = match (NonZeroU32::new(window_width), NonZeroU32::new(window_height)) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return,
+    };
+    surface.resize(context, width, height);
+    unsafe {
+        // opengl counts rows from the bottom
+        gl::Viewport(
+            viewport.x as GLint,
+            (window_height - viewport.y - viewport.height) as GLint,
+            viewport.width as GLsizei,
+            viewport.height as GLsizei,
+        );
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+    }
 }
 
 impl VNEFullRenderer for OpenGLRenderer {}
@@ -369,6 +493,10 @@ impl OpenGLRenderer {
             surface,
             context,
             color_buffer,
+            layers: Layers::new(width, height),
+            frame: Vec::new(),
+            blend_mode: BlendMode::Replace,
+            clip: ClipStack::new(width, height),
         };
 
         opengl
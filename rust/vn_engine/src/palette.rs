@@ -0,0 +1,332 @@
+use crate::color::{BlendMode, RGBA};
+use crate::image::Image;
+use crate::render::{
+    ClipStack, PixelPosition, VNEFullRenderer, VNERenderer, VNERendererCommit, VNERendererWindow,
+};
+use crate::scaling::Viewport;
+use std::collections::HashMap;
+use std::ops::Range;
+use winit::window::Window;
+
+/// Up to 256 colors an [IndexedRenderer] draws with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<RGBA>,
+}
+
+impl Palette {
+    /// panics if there are no colors or more than 256
+    pub fn new(colors: Vec<RGBA>) -> Palette {
+        if colors.is_empty() || colors.len() > 256 {
+            panic!("Expected 1 to 256 palette colors, got {}", colors.len())
+        }
+        Palette {
+            colors: colors.into_iter().map(RGBA::quantized).collect(),
+        }
+    }
+
+    /// `levels` evenly spaced grays from black to white
+    pub fn grayscale(levels: usize) -> Palette {
+        let steps = levels.max(2) - 1;
+        Palette::new(
+            (0..levels)
+                .map(|idx| {
+                    let value = (idx * 255 / steps) as u8;
+                    RGBA::from_bytes(value, value, value, 255)
+                })
+                .collect(),
+        )
+    }
+
+    /// the 16 colors of CGA text mode
+    pub fn cga() -> Palette {
+        Palette::new(
+            (0..16)
+                .map(|idx: u8| {
+                    let bright = if idx & 8 != 0 { 0x55 } else { 0 };
+                    let channel = |bit: u8| bright + (idx & bit != 0) as u8 * 0xaa;
+                    // dark yellow is brown on real hardware
+                    let g = if idx == 6 { 0x55 } else { channel(2) };
+                    RGBA::from_bytes(channel(4), g, channel(1), 255)
+                })
+                .collect(),
+        )
+    }
+
+    /// 256 colors with 3 bits of red, 3 bits of green and 2 bits of blue, the index is `rrrgggbb`
+    pub fn rgb332() -> Palette {
+        Palette::new(
+            (0..=255u8)
+                .map(|idx| {
+                    let r = (idx >> 5) as u32 * 255 / 7;
+                    let g = ((idx >> 2) & 7) as u32 * 255 / 7;
+                    let b = (idx & 3) as u32 * 255 / 3;
+                    RGBA::from_bytes(r as u8, g as u8, b as u8, 255)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn colors(&self) -> &[RGBA] {
+        &self.colors
+    }
+
+    /// panics if `index` is not part of the palette
+    pub fn color(&self, index: u8) -> RGBA {
+        self.colors[index as usize]
+    }
+
+    pub fn set_color(&mut self, index: u8, color: RGBA) {
+        self.colors[index as usize] = color.quantized();
+    }
+
+    /// The index of the color closest to `color`, alpha is ignored
+    ///
+    /// ```
+    /// use vn_engine::color::RGBA;
+    /// use vn_engine::palette::Palette;
+    ///
+    /// let palette = Palette::grayscale(5);
+    /// assert_eq!(palette.nearest(RGBA { r: 0.3, g: 0.2, b: 0.25, a: 1.0 }), 1);
+    /// ```
+    pub fn nearest(&self, color: RGBA) -> u8 {
+        let distance = |other: &RGBA| {
+            let (r, g, b) = (other.r - color.r, other.g - color.g, other.b - color.b);
+            r * r + g * g + b * b
+        };
+        let mut nearest = 0;
+        for (idx, other) in self.colors.iter().enumerate() {
+            if distance(other) < distance(&self.colors[nearest]) {
+                nearest = idx;
+            }
+        }
+        nearest as u8
+    }
+
+    /// Rotates the colors in `range` by `steps`, towards higher indices for positive steps.
+    /// Pixels keep their index, so everything drawn with these colors appears to move.
+    pub fn cycle(&mut self, range: Range<usize>, steps: isize) {
+        let colors = &mut self.colors[range];
+        if colors.is_empty() {
+            return;
+        }
+        let steps = steps.rem_euclid(colors.len() as isize) as usize;
+        colors.rotate_right(steps);
+    }
+}
+
+/// How colors that are not in the palette are mapped to it
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Dither {
+    /// the closest palette color
+    #[default]
+    Nearest,
+    /// a 4x4 Bayer pattern mixes neighboring palette colors to approximate the color. `spread`
+    /// is how far the channels are shifted at most, about the distance between palette colors
+    /// works well, e.g. 0.33 for 4 levels per channel.
+    Ordered { spread: f32 },
+}
+
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Wraps a renderer and draws palette indices instead of colors. Drawn colors are blended with
+/// the palette color already at the pixel and mapped back to the palette. On every commit the
+/// indices are resolved to the current palette and drawn to the wrapped renderer, so changing the
+/// palette recolors everything drawn before.
+pub struct IndexedRenderer<R> {
+    renderer: R,
+    width: u32,
+    height: u32,
+    palette: Palette,
+    dither: Dither,
+    indices: Vec<u8>,
+    blend_mode: BlendMode,
+    clip: ClipStack,
+    /// nearest palette index of 8 bit colors
+    nearest: HashMap<[u8; 3], u8>,
+}
+
+impl<R: VNERenderer> IndexedRenderer<R> {
+    /// Takes the size of the wrapped renderer, all pixels start with index 0
+    pub fn new(renderer: R, palette: Palette, dither: Dither) -> IndexedRenderer<R> {
+        let screen = renderer.clip_stack().screen();
+        let width = (screen.bottom_right.x + 1) as u32;
+        let height = (screen.bottom_right.y + 1) as u32;
+        IndexedRenderer {
+            renderer,
+            width,
+            height,
+            palette,
+            dither,
+            indices: vec![0; width as usize * height as usize],
+            blend_mode: BlendMode::default(),
+            clip: ClipStack::new(width, height),
+            nearest: HashMap::new(),
+        }
+    }
+}
+
+impl<R> IndexedRenderer<R> {
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut R {
+        &mut self.renderer
+    }
+
+    /// Swaps the palette, pixels keep their indices. Indices the new palette does not have
+    /// resolve to its last color.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.nearest.clear();
+        self.palette = palette;
+    }
+
+    pub fn dither(&self) -> Dither {
+        self.dither
+    }
+
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+    }
+
+    /// the palette index at (x, y), panics if it is out of bounds
+    pub fn index(&self, x: u32, y: u32) -> u8 {
+        self.indices[self.xy_index(x, y)]
+    }
+
+    fn xy_index(&self, x: u32, y: u32) -> usize {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "Index {:?} out of range of {:?}",
+                (x, y),
+                (self.width, self.height)
+            )
+        }
+        y as usize * self.width as usize + x as usize
+    }
+
+    fn resolve(&self, index: u8) -> RGBA {
+        let colors = self.palette.colors();
+        colors[(index as usize).min(colors.len() - 1)]
+    }
+
+    /// the palette index `color` is drawn with at `position`
+    fn quantize(&mut self, color: RGBA, position: PixelPosition) -> u8 {
+        let color = match self.dither {
+            Dither::Nearest => color,
+            Dither::Ordered { spread } => {
+                let threshold = BAYER[(position.y & 3) as usize][(position.x & 3) as usize];
+                let offset = ((threshold as f32 + 0.5) / 16.0 - 0.5) * spread;
+                RGBA {
+                    r: color.r + offset,
+                    g: color.g + offset,
+                    b: color.b + offset,
+                    a: color.a,
+                }
+            }
+        };
+        let [r, g, b, _] = color.to_bytes();
+        let palette = &self.palette;
+        *self
+            .nearest
+            .entry([r, g, b])
+            .or_insert_with(|| palette.nearest(RGBA::from_bytes(r, g, b, 255)))
+    }
+}
+
+impl<R: VNERenderer> VNERenderer for IndexedRenderer<R> {
+    fn set_title(&mut self, title: &str) {
+        self.renderer.set_title(title);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    /// fills the screen with the palette color closest to `color`, without dithering
+    fn clear_screen(&mut self, color: RGBA) {
+        let index = self.palette.nearest(color);
+        self.indices.fill(index);
+    }
+
+    fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+        if !self.clip.current().contains(position) {
+            return;
+        }
+        let idx = self.xy_index(position.x as u32, position.y as u32);
+        let color = color.blend(self.resolve(self.indices[idx]), self.blend_mode);
+        self.indices[idx] = self.quantize(color, position);
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        &self.clip
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        &mut self.clip
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        Some(&self.palette)
+    }
+
+    /// e.g. for [Palette::cycle], takes effect on the next commit
+    fn palette_mut(&mut self) -> Option<&mut Palette> {
+        self.nearest.clear();
+        Some(&mut self.palette)
+    }
+
+    /// the indices resolved to the current palette
+    fn capture_frame(&self) -> Image {
+        let colors: Vec<RGBA> = self.indices.iter().map(|idx| self.resolve(*idx)).collect();
+        Image::from_colors(self.width, self.height, &colors)
+    }
+}
+
+impl<R: VNERenderer + VNERendererCommit> VNERendererCommit for IndexedRenderer<R> {
+    fn commit(&mut self) {
+        let mode = self.renderer.blend_mode();
+        self.renderer.set_blend_mode(BlendMode::Replace);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = self.resolve(self.indices[self.xy_index(x, y)]);
+                let position = PixelPosition {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                self.renderer.draw_pixel(position, color);
+            }
+        }
+        self.renderer.set_blend_mode(mode);
+        self.renderer.commit();
+    }
+
+    fn finished(&self) -> bool {
+        self.renderer.finished()
+    }
+}
+
+impl<R: VNERendererWindow> VNERendererWindow for IndexedRenderer<R> {
+    fn window(&mut self) -> &Window {
+        self.renderer.window()
+    }
+
+    fn resize(&mut self, window_width: u32, window_height: u32, viewport: Viewport) {
+        self.renderer.resize(window_width, window_height, viewport);
+    }
+}
+
+impl<R: VNEFullRenderer> VNEFullRenderer for IndexedRenderer<R> {}
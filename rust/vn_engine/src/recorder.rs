@@ -0,0 +1,284 @@
+use crate::color::{BlendMode, RGBA};
+use crate::image::{Image, ImageError};
+use crate::palette::Palette;
+use crate::render::{
+    ClipStack, PixelPosition, VNEFullRenderer, VNERenderer, VNERendererCommit, VNERendererWindow,
+};
+use crate::scaling::Viewport;
+use crate::target::RenderTarget;
+use gif::{Encoder, Frame, Repeat};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use winit::window::Window;
+
+pub enum RecordingFormat {
+    /// one `frame_00000.png`, `frame_00001.png`, ... per recorded frame in the given directory
+    PngSequence(PathBuf),
+    /// a looping animated GIF, colors are quantized to a palette per frame
+    Gif(PathBuf),
+}
+
+pub struct RecorderOptions {
+    pub format: RecordingFormat,
+    /// record every n-th committed frame, 1 records all of them
+    pub frame_step: u32,
+    /// stop the run after this many recorded frames
+    pub max_frames: Option<u32>,
+    /// the delta every tick sees while recording, in seconds
+    pub delta: f64,
+}
+
+impl RecorderOptions {
+    /// Records every frame at 60 frames per second until the window is closed
+    pub fn new(format: RecordingFormat) -> RecorderOptions {
+        RecorderOptions {
+            format,
+            frame_step: 1,
+            max_frames: None,
+            delta: 1.0 / 60.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    /// the frame can not be stored in the format, e.g. it is too large for a GIF
+    Format(String),
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(value: io::Error) -> Self {
+        RecordingError::Io(value)
+    }
+}
+
+impl From<ImageError> for RecordingError {
+    fn from(value: ImageError) -> Self {
+        match value {
+            ImageError::Io(e) => RecordingError::Io(e),
+            ImageError::Format(message) => RecordingError::Format(message),
+        }
+    }
+}
+
+impl From<gif::EncodingError> for RecordingError {
+    fn from(value: gif::EncodingError) -> Self {
+        match value {
+            gif::EncodingError::Io(e) => RecordingError::Io(e),
+            e => RecordingError::Format(e.to_string()),
+        }
+    }
+}
+
+/// Wraps a renderer and captures the frame on every commit.
+/// Drawing is passed through unchanged, so runners do not notice the recording. After the first
+/// error nothing more is recorded, the game keeps running.
+pub struct Recorder<R> {
+    renderer: R,
+    options: RecorderOptions,
+    commits: u64,
+    recorded: u32,
+    gif: Option<Encoder<BufWriter<File>>>,
+    /// hundredths of a second the GIF delays so far are behind the recorded time
+    delay_remainder: f64,
+    error: Option<RecordingError>,
+}
+
+impl<R> Recorder<R> {
+    pub fn new(renderer: R, options: RecorderOptions) -> Recorder<R> {
+        let error = match &options.format {
+            RecordingFormat::PngSequence(directory) => {
+                fs::create_dir_all(directory).err().map(RecordingError::Io)
+            }
+            RecordingFormat::Gif(_) => None,
+        };
+        Recorder {
+            renderer,
+            options,
+            commits: 0,
+            recorded: 0,
+            gif: None,
+            delay_remainder: 0.0,
+            error,
+        }
+    }
+
+    pub fn options(&self) -> &RecorderOptions {
+        &self.options
+    }
+
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut R {
+        &mut self.renderer
+    }
+
+    /// number of frames written so far
+    pub fn recorded_frames(&self) -> u32 {
+        self.recorded
+    }
+
+    /// the error that stopped the recording, or kept it from starting
+    pub fn error(&self) -> Option<&RecordingError> {
+        self.error.as_ref()
+    }
+
+    fn record(&mut self, frame: Image) -> Result<(), RecordingError> {
+        match &self.options.format {
+            RecordingFormat::PngSequence(directory) => {
+                let path = directory.join(format!("frame_{:05}.png", self.recorded));
+                frame.save_png(path)?;
+            }
+            RecordingFormat::Gif(path) => {
+                let (width, height) =
+                    match (u16::try_from(frame.width()), u16::try_from(frame.height())) {
+                        (Ok(width), Ok(height)) => (width, height),
+                        _ => {
+                            return Err(RecordingError::Format(format!(
+                                "a {}x{} frame is too large for a GIF",
+                                frame.width(),
+                                frame.height()
+                            )))
+                        }
+                    };
+                let encoder = match &mut self.gif {
+                    Some(encoder) => encoder,
+                    None => {
+                        let file = BufWriter::new(File::create(path)?);
+                        let mut encoder = Encoder::new(file, width, height, &[])?;
+                        encoder.set_repeat(Repeat::Infinite)?;
+                        self.gif.insert(encoder)
+                    }
+                };
+
+                let mut data = frame.data().to_vec();
+                let mut gif_frame = Frame::from_rgba_speed(width, height, &mut data, 10);
+                // gif delays are in hundredths of a second, the rounding is carried to the next
+                // frame so e.g. 60 frames per second alternate between 2 and 1
+                let delay = self.options.delta * self.options.frame_step as f64 * 100.0
+                    + self.delay_remainder;
+                gif_frame.delay = delay.round() as u16;
+                self.delay_remainder = delay - gif_frame.delay as f64;
+                encoder.write_frame(&gif_frame)?;
+            }
+        }
+        self.recorded += 1;
+        Ok(())
+    }
+}
+
+impl<R: VNERenderer> VNERenderer for Recorder<R> {
+    fn set_title(&mut self, title: &str) {
+        self.renderer.set_title(title);
+    }
+
+    fn clear_screen(&mut self, color: RGBA) {
+        self.renderer.clear_screen(color);
+    }
+
+    fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+        self.renderer.draw_pixel(position, color);
+    }
+
+    fn capture_frame(&self) -> Image {
+        self.renderer.capture_frame()
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        self.renderer.palette()
+    }
+
+    fn palette_mut(&mut self) -> Option<&mut Palette> {
+        self.renderer.palette_mut()
+    }
+
+    fn layer(&self) -> i32 {
+        self.renderer.layer()
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        self.renderer.set_layer(layer);
+    }
+
+    fn draw_target(&mut self, target: &RenderTarget, position: PixelPosition, mode: BlendMode) {
+        self.renderer.draw_target(target, position, mode);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.renderer.blend_mode()
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.renderer.set_blend_mode(mode);
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        self.renderer.clip_stack()
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        self.renderer.clip_stack_mut()
+    }
+}
+
+impl<R: VNERenderer + VNERendererCommit> VNERendererCommit for Recorder<R> {
+    fn commit(&mut self) {
+        self.renderer.commit();
+        if self.error.is_none()
+            && !self.finished()
+            && self
+                .commits
+                .is_multiple_of(self.options.frame_step.max(1) as u64)
+        {
+            let frame = self.renderer.capture_frame();
+            if let Err(error) = self.record(frame) {
+                self.error = Some(error);
+            }
+        }
+        self.commits += 1;
+    }
+
+    fn finished(&self) -> bool {
+        match self.options.max_frames {
+            Some(max_frames) => self.recorded >= max_frames,
+            None => false,
+        }
+    }
+}
+
+impl<R: VNERendererWindow> VNERendererWindow for Recorder<R> {
+    fn window(&mut self) -> &Window {
+        self.renderer.window()
+    }
+
+    fn resize(&mut self, window_width: u32, window_height: u32, viewport: Viewport) {
+        self.renderer.resize(window_width, window_height, viewport);
+    }
+}
+
+impl<R: VNEFullRenderer> VNEFullRenderer for Recorder<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessRenderer;
+
+    #[test]
+    fn unusable_directories_stop_the_recording() {
+        // a directory can not be created inside a file
+        let file = std::env::temp_dir().join(format!("vn_recorder_{}", std::process::id()));
+        fs::write(&file, b"").unwrap();
+        let options = RecorderOptions::new(RecordingFormat::PngSequence(file.join("frames")));
+        let mut recorder = Recorder::new(HeadlessRenderer::new(4, 4), options);
+        recorder.commit();
+        fs::remove_file(&file).unwrap();
+
+        assert!(matches!(recorder.error(), Some(RecordingError::Io(_))));
+        assert_eq!(recorder.recorded_frames(), 0);
+        assert_eq!(recorder.renderer().commits(), 1);
+    }
+}
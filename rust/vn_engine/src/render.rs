@@ -1,4 +1,11 @@
-use crate::color::RGBA;
+use crate::color::{BlendMode, RGBA};
+use crate::font::Font;
+use crate::image::Image;
+use crate::palette::Palette;
+use crate::scaling::Viewport;
+use crate::shapes;
+use crate::sprite::{Sprite, SpriteOptions};
+use crate::target::{self, RenderTarget};
 use std::cmp::max;
 use std::ops;
 use winit::event::VirtualKeyCode::P;
@@ -47,11 +54,11 @@ fn fill_flat_bottom<T: VNERenderer + ?Sized>(
     for y in p1.y..p2.y {
         render.draw_line(
             PixelPosition {
-                x: xl.floor() as u32,
+                x: xl.floor() as i32,
                 y,
             },
             PixelPosition {
-                x: xr.ceil() as u32,
+                x: xr.ceil() as i32,
                 y,
             },
             color,
@@ -80,11 +87,11 @@ fn fill_flat_top<T: VNERenderer + ?Sized>(
     for y in (p1.y..p3.y).rev() {
         render.draw_line(
             PixelPosition {
-                x: xl.floor() as u32,
+                x: xl.floor() as i32,
                 y,
             },
             PixelPosition {
-                x: xr.ceil() as u32,
+                x: xr.ceil() as i32,
                 y,
             },
             color,
@@ -96,44 +103,231 @@ fn fill_flat_top<T: VNERenderer + ?Sized>(
 
 /// test if point point is to the right of vector v1 -> v2
 fn edge_function(v1: PixelPosition, v2: PixelPosition, point: PixelPosition) -> bool {
-    (point.x as i32 - v1.x as i32) * (v2.y as i32 - v1.y as i32) - (point.y as i32 - v1.y as i32) * (v2.x as i32 - v1.x as i32) >= 0
+    (point.x as i64 - v1.x as i64) * (v2.y as i64 - v1.y as i64) - (point.y as i64 - v1.y as i64) * (v2.x as i64 - v1.x as i64) >= 0
+}
+
+/// The range of DDA steps `i` for which `from + i * (dx, dy) / steps` rounds to a pixel inside of
+/// `clip`, the pixels at either end may still be just outside
+fn clipped_steps(
+    from: PixelPosition,
+    dx: i64,
+    dy: i64,
+    steps: i64,
+    clip: ClipRect,
+) -> Option<(i64, i64)> {
+    let mut first = 0.0;
+    let mut last = steps as f64;
+    for (start, delta, min, max) in [
+        (from.x, dx, clip.top_left.x, clip.bottom_right.x),
+        (from.y, dy, clip.top_left.y, clip.bottom_right.y),
+    ] {
+        let low = (min as f64 - 0.5 - start as f64) * steps as f64;
+        let high = (max as f64 + 0.5 - start as f64) * steps as f64;
+        if delta == 0 {
+            if start < min || start > max {
+                return None;
+            }
+            continue;
+        }
+        let (a, b) = (low / delta as f64, high / delta as f64);
+        first = f64::max(first, a.min(b));
+        last = f64::min(last, a.max(b));
+    }
+    if first > last {
+        return None;
+    }
+    Some((first.floor() as i64, last.ceil() as i64))
+}
+
+/// DDA line, the end pixel is skipped if `include_end` is false so connected segments don't draw
+/// their shared vertices twice. Only the steps inside of the clip rectangle are visited.
+fn draw_line_segment<T: VNERenderer + ?Sized>(
+    render: &mut T,
+    from: PixelPosition,
+    to: PixelPosition,
+    color: RGBA,
+    include_end: bool,
+) {
+    let clip = render.clip();
+    let dx = to.x as i64 - from.x as i64;
+    let dy = to.y as i64 - from.y as i64;
+
+    let steps = dx.abs().max(dy.abs());
+    let steps_drawn = if include_end { steps } else { steps - 1 };
+    if steps_drawn < 0 || clip.is_empty() {
+        return;
+    }
+    if steps == 0 {
+        if clip.contains(from) {
+            render.draw_pixel(from, color);
+        }
+        return;
+    }
+
+    let (first, last) = match clipped_steps(from, dx, dy, steps, clip) {
+        Some(range) => range,
+        None => return,
+    };
+    for step in first.max(0)..=last.min(steps_drawn) {
+        let t = step as f64 / steps as f64;
+        let point = PixelPosition {
+            x: (from.x as f64 + dx as f64 * t).round() as i32,
+            y: (from.y as f64 + dy as f64 * t).round() as i32,
+        };
+        if clip.contains(point) {
+            render.draw_pixel(point, color);
+        }
+    }
 }
 
 pub trait VNERenderer {
     fn set_title(&mut self, title: &str);
+    /// the blend mode used by all drawing operations except [VNERenderer::clear_screen]
+    fn blend_mode(&self) -> BlendMode;
+    fn set_blend_mode(&mut self, mode: BlendMode);
+    /// fills the whole screen with `color`, ignoring the blend mode
     fn clear_screen(&mut self, color: RGBA);
+    /// draws a single pixel, combining it with the current content according to the blend mode.
+    /// Pixels outside of the clip rectangle are ignored.
     fn draw_pixel(&mut self, position: PixelPosition, color: RGBA);
+    fn clip_stack(&self) -> &ClipStack;
+    fn clip_stack_mut(&mut self) -> &mut ClipStack;
+    /// copies everything drawn so far, independent of whether it was committed
+    fn capture_frame(&self) -> Image;
+    /// the area drawing is currently restricted to
+    fn clip(&self) -> ClipRect {
+        self.clip_stack().current()
+    }
+
+    /// the colors of an indexed render target, `None` if it stores colors directly
+    fn palette(&self) -> Option<&Palette> {
+        None
+    }
+
+    /// changes to the palette recolor everything drawn so far, see
+    /// [crate::palette::IndexedRenderer]
+    fn palette_mut(&mut self) -> Option<&mut Palette> {
+        None
+    }
+
+    /// the layer drawing currently goes to, see [VNERenderer::set_layer]
+    fn layer(&self) -> i32 {
+        0
+    }
+
+    /// Draws to `layer` from now on, all layers are merged on commit with higher ones on top.
+    /// Layer 0 is the screen, other layers start out transparent and keep their content between
+    /// frames, so they need to be cleared with [crate::color::TRANSPARENT] before redrawing them.
+    /// Renderers without layers draw everything to the screen.
+    #[allow(unused_variables)]
+    fn set_layer(&mut self, layer: i32) {}
+
+    /// draws `target` with its top left corner at `position`, blended with `mode` instead of the
+    /// current blend mode
+    fn draw_target(&mut self, target: &RenderTarget, position: PixelPosition, mode: BlendMode) {
+        target::draw_target(self, target, position, mode);
+    }
+
+    /// restricts drawing to `rect` within the current clip rectangle until the matching
+    /// [VNERenderer::pop_clip]
+    fn push_clip(&mut self, rect: ClipRect) {
+        self.clip_stack_mut().push(rect);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clip_stack_mut().pop();
+    }
+
+    /// draws `text` in the built-in font, see [Font::draw]
+    fn draw_text(&mut self, position: PixelPosition, text: &str, color: RGBA, scale: u32) {
+        Font::builtin().draw(self, position, text, color, scale);
+    }
+
+    /// draws `text` in the given font, see [Font::draw]
+    fn draw_text_with(
+        &mut self,
+        font: &Font,
+        position: PixelPosition,
+        text: &str,
+        color: RGBA,
+        scale: u32,
+    ) {
+        font.draw(self, position, text, color, scale);
+    }
+
+    /// draws the whole sprite with its top left corner at `position`
+    fn draw_sprite(&mut self, sprite: &Sprite, position: PixelPosition) {
+        sprite.draw(self, position, &SpriteOptions::default());
+    }
+
+    /// draws a sprite, see [Sprite::draw]
+    fn draw_sprite_with(
+        &mut self,
+        sprite: &Sprite,
+        position: PixelPosition,
+        options: &SpriteOptions,
+    ) {
+        sprite.draw(self, position, options);
+    }
+
     fn draw_line(&mut self, from: PixelPosition, to: PixelPosition, color: RGBA) {
-        let dx = to.x as i32 - from.x as i32;
-        let dy = to.y as i32 - from.y as i32;
+        draw_line_segment(self, from, to, color, true);
+    }
+
+    /// a line `thickness` pixels wide, both ends reach half a pixel beyond `from` and `to`
+    fn draw_thick_line(
+        &mut self,
+        from: PixelPosition,
+        to: PixelPosition,
+        thickness: u32,
+        color: RGBA,
+    ) {
+        shapes::draw_thick_line(self, from, to, thickness, color);
+    }
 
-        let steps = dx.abs().max(dy.abs());
+    /// an anti-aliased line, pixels the line only partially covers get a lower alpha.
+    /// Use [BlendMode::Alpha] to blend them with the background.
+    fn draw_line_aa(&mut self, from: PixelPosition, to: PixelPosition, color: RGBA) {
+        shapes::draw_line_aa(self, from, to, color);
+    }
 
-        let x_inc = dx as f64 / steps as f64;
-        let y_inc = dy as f64 / steps as f64;
+    fn draw_circle(&mut self, center: PixelPosition, radius: u32, color: RGBA) {
+        shapes::draw_circle(self, center, radius, color);
+    }
 
-        let mut x = from.x as f64;
-        let mut y = from.y as f64;
-        self.draw_pixel(
-            PixelPosition {
-                x: x as u32,
-                y: y as u32,
-            },
-            color,
-        );
-        for _ in 0..steps as i32 {
-            x += x_inc;
-            y += y_inc;
-            self.draw_pixel(
-                PixelPosition {
-                    x: x.round() as u32,
-                    y: y.round() as u32,
-                },
-                color,
-            );
+    fn fill_circle(&mut self, center: PixelPosition, radius: u32, color: RGBA) {
+        shapes::fill_circle(self, center, radius, color);
+    }
+
+    fn draw_ellipse(&mut self, center: PixelPosition, radius_x: u32, radius_y: u32, color: RGBA) {
+        shapes::draw_ellipse(self, center, radius_x, radius_y, color);
+    }
+
+    fn fill_ellipse(&mut self, center: PixelPosition, radius_x: u32, radius_y: u32, color: RGBA) {
+        shapes::fill_ellipse(self, center, radius_x, radius_y, color);
+    }
+
+    /// the outline through all vertices, closed back to the first one
+    fn draw_polygon(&mut self, vertices: &[PixelPosition], color: RGBA) {
+        match vertices.len() {
+            0 => {}
+            1 => self.draw_line(vertices[0], vertices[0], color),
+            // every vertex is only drawn once, so translucent outlines blend evenly
+            len => {
+                for idx in 0..len {
+                    draw_line_segment(self, vertices[idx], vertices[(idx + 1) % len], color, false);
+                }
+            }
         }
     }
 
+    /// fills any convex, concave or self intersecting polygon with the even-odd rule, in either
+    /// winding order. Pixels on the right and bottom edges are left out, so polygons sharing an
+    /// edge do not overlap.
+    fn fill_polygon(&mut self, vertices: &[PixelPosition], color: RGBA) {
+        shapes::fill_polygon(self, vertices, color);
+    }
+
     fn draw_triangle(
         &mut self,
         v1: PixelPosition,
@@ -141,9 +335,10 @@ pub trait VNERenderer {
         v3: PixelPosition,
         color: RGBA,
     ) {
-        self.draw_line(v1, v2, color);
-        self.draw_line(v2, v3, color);
-        self.draw_line(v3, v1, color);
+        // every vertex is only drawn once, so translucent outlines blend evenly
+        draw_line_segment(self, v1, v2, color, false);
+        draw_line_segment(self, v2, v3, color, false);
+        draw_line_segment(self, v3, v1, color, false);
     }
 
     /// assumes clockwise vertex order
@@ -152,17 +347,19 @@ pub trait VNERenderer {
         v1: PixelPosition,
         v2: PixelPosition,
         v3: PixelPosition,
-        clamp_top_left: PixelPosition,
-        clamp_bottom_right: PixelPosition,
         color: RGBA,
     ) {
-        let x_min = v1.x.min(v2.x).min(v3.x).max(clamp_top_left.x);
-        let x_max = v1.x.max(v2.x).max(v3.x).min(clamp_bottom_right.x);
-        let y_min = v1.y.min(v2.y).min(v3.y).max(clamp_top_left.y);
-        let y_max = v1.y.max(v2.y).max(v3.y).min(clamp_bottom_right.y);
+        let bounds = ClipRect::new(
+            PixelPosition { x: v1.x.min(v2.x).min(v3.x), y: v1.y.min(v2.y).min(v3.y) },
+            PixelPosition { x: v1.x.max(v2.x).max(v3.x), y: v1.y.max(v2.y).max(v3.y) },
+        )
+        .intersect(&self.clip());
+        if bounds.is_empty() {
+            return;
+        }
 
-        for y in y_min..=y_max {
-            for x in x_min..=x_max {
+        for y in bounds.top_left.y..=bounds.bottom_right.y {
+            for x in bounds.top_left.x..=bounds.bottom_right.x {
                 let point = PixelPosition{ x, y };
                 let mut inside = edge_function(v1, v2, point);
                 inside &= edge_function(v2, v3, point);
@@ -180,12 +377,17 @@ pub trait VNERenderer {
         bottom_right: PixelPosition,
         color: RGBA
     ) {
+        if top_left.x == bottom_right.x || top_left.y == bottom_right.y {
+            self.draw_line(top_left, bottom_right, color);
+            return;
+        }
         let top_right = PixelPosition { x: bottom_right.x, y: top_left.y };
         let bottom_left = PixelPosition { x: top_left.x, y: bottom_right.y };
-        self.draw_line(top_left, top_right, color);
-        self.draw_line(top_right, bottom_right, color);
-        self.draw_line(bottom_right, bottom_left, color);
-        self.draw_line(bottom_left, top_left, color);
+        // every corner is only drawn once, so translucent outlines blend evenly
+        draw_line_segment(self, top_left, top_right, color, false);
+        draw_line_segment(self, top_right, bottom_right, color, false);
+        draw_line_segment(self, bottom_right, bottom_left, color, false);
+        draw_line_segment(self, bottom_left, top_left, color, false);
     }
 
     fn fill_rectangle(
@@ -194,27 +396,305 @@ pub trait VNERenderer {
         bottom_right: PixelPosition,
         color: RGBA
     ) {
-        for y in top_left.y..=bottom_right.y {
-            for x in top_left.x..=bottom_right.x {
+        let area = ClipRect::new(top_left, bottom_right).intersect(&self.clip());
+        if area.is_empty() {
+            return;
+        }
+        for y in area.top_left.y..=area.bottom_right.y {
+            for x in area.top_left.x..=area.bottom_right.x {
                 self.draw_pixel(PixelPosition{ x, y }, color);
             }
         }
     }
 }
 
+/// Lets a `&mut` to any renderer, also an unsized one, be used where a sized renderer is needed,
+/// e.g. as `&mut dyn VNERenderer`. Every method is forwarded, so overrides of the renderer are
+/// kept.
+impl<T: VNERenderer + ?Sized> VNERenderer for &mut T {
+    fn set_title(&mut self, title: &str) {
+        (**self).set_title(title);
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        (**self).blend_mode()
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        (**self).set_blend_mode(mode);
+    }
+
+    fn clear_screen(&mut self, color: RGBA) {
+        (**self).clear_screen(color);
+    }
+
+    fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+        (**self).draw_pixel(position, color);
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        (**self).clip_stack()
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        (**self).clip_stack_mut()
+    }
+
+    fn capture_frame(&self) -> Image {
+        (**self).capture_frame()
+    }
+
+    fn clip(&self) -> ClipRect {
+        (**self).clip()
+    }
+
+    fn palette(&self) -> Option<&Palette> {
+        (**self).palette()
+    }
+
+    fn palette_mut(&mut self) -> Option<&mut Palette> {
+        (**self).palette_mut()
+    }
+
+    fn layer(&self) -> i32 {
+        (**self).layer()
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        (**self).set_layer(layer);
+    }
+
+    fn draw_target(&mut self, target: &RenderTarget, position: PixelPosition, mode: BlendMode) {
+        (**self).draw_target(target, position, mode);
+    }
+
+    fn push_clip(&mut self, rect: ClipRect) {
+        (**self).push_clip(rect);
+    }
+
+    fn pop_clip(&mut self) {
+        (**self).pop_clip();
+    }
+
+    fn draw_text(&mut self, position: PixelPosition, text: &str, color: RGBA, scale: u32) {
+        (**self).draw_text(position, text, color, scale);
+    }
+
+    fn draw_text_with(
+        &mut self,
+        font: &Font,
+        position: PixelPosition,
+        text: &str,
+        color: RGBA,
+        scale: u32,
+    ) {
+        (**self).draw_text_with(font, position, text, color, scale);
+    }
+
+    fn draw_sprite(&mut self, sprite: &Sprite, position: PixelPosition) {
+        (**self).draw_sprite(sprite, position);
+    }
+
+    fn draw_sprite_with(
+        &mut self,
+        sprite: &Sprite,
+        position: PixelPosition,
+        options: &SpriteOptions,
+    ) {
+        (**self).draw_sprite_with(sprite, position, options);
+    }
+
+    fn draw_line(&mut self, from: PixelPosition, to: PixelPosition, color: RGBA) {
+        (**self).draw_line(from, to, color);
+    }
+
+    fn draw_thick_line(
+        &mut self,
+        from: PixelPosition,
+        to: PixelPosition,
+        thickness: u32,
+        color: RGBA,
+    ) {
+        (**self).draw_thick_line(from, to, thickness, color);
+    }
+
+    fn draw_line_aa(&mut self, from: PixelPosition, to: PixelPosition, color: RGBA) {
+        (**self).draw_line_aa(from, to, color);
+    }
+
+    fn draw_circle(&mut self, center: PixelPosition, radius: u32, color: RGBA) {
+        (**self).draw_circle(center, radius, color);
+    }
+
+    fn fill_circle(&mut self, center: PixelPosition, radius: u32, color: RGBA) {
+        (**self).fill_circle(center, radius, color);
+    }
+
+    fn draw_ellipse(&mut self, center: PixelPosition, radius_x: u32, radius_y: u32, color: RGBA) {
+        (**self).draw_ellipse(center, radius_x, radius_y, color);
+    }
+
+    fn fill_ellipse(&mut self, center: PixelPosition, radius_x: u32, radius_y: u32, color: RGBA) {
+        (**self).fill_ellipse(center, radius_x, radius_y, color);
+    }
+
+    fn draw_polygon(&mut self, vertices: &[PixelPosition], color: RGBA) {
+        (**self).draw_polygon(vertices, color);
+    }
+
+    fn fill_polygon(&mut self, vertices: &[PixelPosition], color: RGBA) {
+        (**self).fill_polygon(vertices, color);
+    }
+
+    fn draw_triangle(
+        &mut self,
+        v1: PixelPosition,
+        v2: PixelPosition,
+        v3: PixelPosition,
+        color: RGBA,
+    ) {
+        (**self).draw_triangle(v1, v2, v3, color);
+    }
+
+    fn fill_triangle(
+        &mut self,
+        v1: PixelPosition,
+        v2: PixelPosition,
+        v3: PixelPosition,
+        color: RGBA,
+    ) {
+        (**self).fill_triangle(v1, v2, v3, color);
+    }
+
+    fn draw_rectangle(
+        &mut self,
+        top_left: PixelPosition,
+        bottom_right: PixelPosition,
+        color: RGBA,
+    ) {
+        (**self).draw_rectangle(top_left, bottom_right, color);
+    }
+
+    fn fill_rectangle(
+        &mut self,
+        top_left: PixelPosition,
+        bottom_right: PixelPosition,
+        color: RGBA,
+    ) {
+        (**self).fill_rectangle(top_left, bottom_right, color);
+    }
+}
+
 pub trait VNERendererCommit {
     /// commit all drawing operations
     fn commit(&mut self);
+    /// the engine stops once this returns true, e.g. when a recording is complete
+    fn finished(&self) -> bool {
+        false
+    }
 }
 
 pub trait VNERendererWindow {
     fn window(&mut self) -> &Window;
+    /// the window now has the given physical size, the framebuffer is drawn into `viewport`
+    fn resize(&mut self, window_width: u32, window_height: u32, viewport: Viewport);
 }
 
 pub trait VNEFullRenderer: VNERenderer + VNERendererWindow + VNERendererCommit {}
 
-#[derive(Copy, Clone, Debug)]
+/// Drawing coordinates, positions outside of the screen are valid and get clipped
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct PixelPosition {
-    pub x: u32,
-    pub y: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A rectangle of pixels, both corners are included
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClipRect {
+    pub top_left: PixelPosition,
+    pub bottom_right: PixelPosition,
+}
+
+impl ClipRect {
+    /// The rectangle spanned by two corners, in any order
+    pub fn new(a: PixelPosition, b: PixelPosition) -> ClipRect {
+        ClipRect {
+            top_left: PixelPosition { x: a.x.min(b.x), y: a.y.min(b.y) },
+            bottom_right: PixelPosition { x: a.x.max(b.x), y: a.y.max(b.y) },
+        }
+    }
+
+    /// all pixels of a `width` x `height` screen
+    pub fn screen(width: u32, height: u32) -> ClipRect {
+        ClipRect {
+            top_left: PixelPosition { x: 0, y: 0 },
+            bottom_right: PixelPosition { x: width as i32 - 1, y: height as i32 - 1 },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.top_left.x > self.bottom_right.x || self.top_left.y > self.bottom_right.y
+    }
+
+    pub fn contains(&self, position: PixelPosition) -> bool {
+        position.x >= self.top_left.x
+            && position.x <= self.bottom_right.x
+            && position.y >= self.top_left.y
+            && position.y <= self.bottom_right.y
+    }
+
+    /// the overlap of both rectangles, may be empty
+    pub fn intersect(&self, other: &ClipRect) -> ClipRect {
+        ClipRect {
+            top_left: PixelPosition {
+                x: self.top_left.x.max(other.top_left.x),
+                y: self.top_left.y.max(other.top_left.y),
+            },
+            bottom_right: PixelPosition {
+                x: self.bottom_right.x.min(other.bottom_right.x),
+                y: self.bottom_right.y.min(other.bottom_right.y),
+            },
+        }
+    }
+}
+
+/// The clip rectangles of a renderer, every pushed rectangle is intersected with the ones below
+/// it and the screen
+#[derive(Clone, Debug)]
+pub struct ClipStack {
+    screen: ClipRect,
+    stack: Vec<ClipRect>,
+}
+
+impl ClipStack {
+    pub fn new(width: u32, height: u32) -> ClipStack {
+        ClipStack {
+            screen: ClipRect::screen(width, height),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> ClipRect {
+        self.stack.last().copied().unwrap_or(self.screen)
+    }
+
+    /// all pixels of the render target, the bottom of the stack
+    pub fn screen(&self) -> ClipRect {
+        self.screen
+    }
+
+    pub fn push(&mut self, rect: ClipRect) {
+        let rect = rect.intersect(&self.current());
+        self.stack.push(rect);
+    }
+
+    /// removes the last pushed rectangle, the screen itself is never removed
+    pub fn pop(&mut self) -> Option<ClipRect> {
+        self.stack.pop()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
 }
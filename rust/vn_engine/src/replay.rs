@@ -0,0 +1,442 @@
+use crate::engine::VNEngineState;
+use crate::input::{InputEvent, Keycode};
+use std::fs;
+use std::io;
+use std::path::Path;
+use winit::event::MouseButton;
+
+/// What happened before a single frame: the input changes since the previous frame and the
+/// delta the frame was ticked with
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    pub delta: f64,
+    pub events: Vec<InputEvent>,
+}
+
+impl InputFrame {
+    /// Puts the engine state where it was when the frame was recorded
+    pub fn apply(&self, engine: &mut VNEngineState) {
+        engine.delta = self.delta;
+        for event in &self.events {
+            engine.input_mut().apply(event);
+        }
+    }
+}
+
+/// The input of a whole session, frame by frame. Replaying it through the same runner gives the
+/// same frames, as long as the runner only reads input through [VNEngineState::input] and does not
+/// depend on anything else that changes between runs, like the clock or random seeds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputLog {
+    /// size of the framebuffer during the recording
+    width: u32,
+    height: u32,
+    frames: Vec<InputFrame>,
+}
+
+impl InputLog {
+    pub fn new(width: u32, height: u32) -> InputLog {
+        InputLog {
+            width,
+            height,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn frames(&self) -> &[InputFrame] {
+        &self.frames
+    }
+
+    pub fn push_frame(&mut self, frame: InputFrame) {
+        self.frames.push(frame);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<InputLog, InputLogError> {
+        InputLog::decode(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+}
+
+#[derive(Debug)]
+pub enum InputLogError {
+    Io(io::Error),
+    /// the data is not a valid input log
+    Format(String),
+}
+
+impl From<io::Error> for InputLogError {
+    fn from(value: io::Error) -> Self {
+        InputLogError::Io(value)
+    }
+}
+
+////////////
+// FORMAT //
+////////////
+
+// All numbers are little endian, counts and lengths are LEB128 encoded:
+//
+//   "VNEINPUT" version:u8 width:u32 height:u32 frame_count
+//   per frame:  delta:f64 event_count event*
+//   per event:  tag:u8 followed by its fields, see `encode_event`
+
+const MAGIC: &[u8; 8] = b"VNEINPUT";
+const VERSION: u8 = 1;
+
+const PRESS_KEY: u8 = 0;
+const RELEASE_KEY: u8 = 1;
+const PRESS_BUTTON: u8 = 2;
+const RELEASE_BUTTON: u8 = 3;
+const MOVE_CURSOR: u8 = 4;
+const LEAVE_CURSOR: u8 = 5;
+const SCROLL_LINES: u8 = 6;
+const SCROLL_PIXELS: u8 = 7;
+const TYPE_TEXT: u8 = 8;
+const RELEASE_ALL: u8 = 9;
+
+impl InputLog {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&self.width.to_le_bytes());
+        data.extend_from_slice(&self.height.to_le_bytes());
+        write_length(&mut data, self.frames.len());
+        for frame in &self.frames {
+            data.extend_from_slice(&frame.delta.to_le_bytes());
+            write_length(&mut data, frame.events.len());
+            for event in &frame.events {
+                encode_event(&mut data, event);
+            }
+        }
+        data
+    }
+
+    /// Reads a log written by [InputLog::encode]
+    ///
+    /// ```
+    /// use vn_engine::input::InputEvent;
+    /// use vn_engine::replay::{InputFrame, InputLog};
+    /// use vn_engine::Keycode;
+    ///
+    /// let mut log = InputLog::new(320, 200);
+    /// log.push_frame(InputFrame {
+    ///     delta: 1.0 / 60.0,
+    ///     events: vec![InputEvent::PressKey(Keycode::Space), InputEvent::MoveCursor(None)],
+    /// });
+    /// log.push_frame(InputFrame { delta: 0.02, events: vec![] });
+    /// assert_eq!(InputLog::decode(&log.encode()).unwrap(), log);
+    /// ```
+    pub fn decode(data: &[u8]) -> Result<InputLog, InputLogError> {
+        let mut reader = Reader { data, offset: 0 };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(format_error("not an input log"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(format_error(&format!("unsupported version {}", version)));
+        }
+        let mut log = InputLog::new(reader.u32()?, reader.u32()?);
+        let frame_count = reader.length()?;
+        for _ in 0..frame_count {
+            let delta = reader.f64()?;
+            let event_count = reader.length()?;
+            let events = (0..event_count)
+                .map(|_| decode_event(&mut reader))
+                .collect::<Result<_, _>>()?;
+            log.push_frame(InputFrame { delta, events });
+        }
+        if reader.offset != data.len() {
+            return Err(format_error("trailing data"));
+        }
+        Ok(log)
+    }
+}
+
+fn format_error(message: &str) -> InputLogError {
+    InputLogError::Format(format!("input log: {}", message))
+}
+
+fn write_length(data: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length & 0x7f) as u8;
+        length >>= 7;
+        if length == 0 {
+            data.push(byte);
+            return;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+fn encode_button(data: &mut Vec<u8>, button: MouseButton) {
+    let code = match button {
+        MouseButton::Left => 0,
+        MouseButton::Right => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Other(other) => 3 + other as u32,
+    };
+    data.extend_from_slice(&code.to_le_bytes());
+}
+
+fn encode_event(data: &mut Vec<u8>, event: &InputEvent) {
+    match event {
+        InputEvent::PressKey(key) => {
+            data.push(PRESS_KEY);
+            data.extend_from_slice(&(*key as u16).to_le_bytes());
+        }
+        InputEvent::ReleaseKey(key) => {
+            data.push(RELEASE_KEY);
+            data.extend_from_slice(&(*key as u16).to_le_bytes());
+        }
+        InputEvent::PressButton(button) => {
+            data.push(PRESS_BUTTON);
+            encode_button(data, *button);
+        }
+        InputEvent::ReleaseButton(button) => {
+            data.push(RELEASE_BUTTON);
+            encode_button(data, *button);
+        }
+        InputEvent::MoveCursor(Some((x, y))) => {
+            data.push(MOVE_CURSOR);
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+        InputEvent::MoveCursor(None) => data.push(LEAVE_CURSOR),
+        InputEvent::ScrollLines(x, y) => {
+            data.push(SCROLL_LINES);
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+        InputEvent::ScrollPixels(x, y) => {
+            data.push(SCROLL_PIXELS);
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+        InputEvent::TypeText(text) => {
+            data.push(TYPE_TEXT);
+            write_length(data, text.len());
+            data.extend_from_slice(text.as_bytes());
+        }
+        InputEvent::ReleaseAll => data.push(RELEASE_ALL),
+    }
+}
+
+fn decode_key(code: u16) -> Result<Keycode, InputLogError> {
+    if code as u32 > Keycode::Cut as u32 {
+        return Err(format_error(&format!("unknown key {}", code)));
+    }
+    // SAFETY: Keycode is a `repr(u32)` enum without fields whose variants are numbered from 0 up
+    // to `Cut`, the last one
+    Ok(unsafe { std::mem::transmute::<u32, Keycode>(code as u32) })
+}
+
+fn decode_button(code: u32) -> Result<MouseButton, InputLogError> {
+    Ok(match code {
+        0 => MouseButton::Left,
+        1 => MouseButton::Right,
+        2 => MouseButton::Middle,
+        other => MouseButton::Other(
+            u16::try_from(other - 3).map_err(|_| format_error("unknown mouse button"))?,
+        ),
+    })
+}
+
+fn decode_event(reader: &mut Reader) -> Result<InputEvent, InputLogError> {
+    Ok(match reader.u8()? {
+        PRESS_KEY => InputEvent::PressKey(decode_key(reader.u16()?)?),
+        RELEASE_KEY => InputEvent::ReleaseKey(decode_key(reader.u16()?)?),
+        PRESS_BUTTON => InputEvent::PressButton(decode_button(reader.u32()?)?),
+        RELEASE_BUTTON => InputEvent::ReleaseButton(decode_button(reader.u32()?)?),
+        MOVE_CURSOR => InputEvent::MoveCursor(Some((reader.f64()?, reader.f64()?))),
+        LEAVE_CURSOR => InputEvent::MoveCursor(None),
+        SCROLL_LINES => InputEvent::ScrollLines(reader.f32()?, reader.f32()?),
+        SCROLL_PIXELS => InputEvent::ScrollPixels(reader.f64()?, reader.f64()?),
+        TYPE_TEXT => {
+            let length = reader.length()?;
+            let text = std::str::from_utf8(reader.bytes(length)?)
+                .map_err(|_| format_error("text is not valid UTF-8"))?;
+            InputEvent::TypeText(text.to_string())
+        }
+        RELEASE_ALL => InputEvent::ReleaseAll,
+        tag => return Err(format_error(&format!("unknown event {}", tag))),
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], InputLogError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset.saturating_add(count))
+            .ok_or_else(|| format_error("data ends early"))?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], InputLogError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, InputLogError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, InputLogError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, InputLogError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, InputLogError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, InputLogError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn length(&mut self) -> Result<usize, InputLogError> {
+        let mut length = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.u8()?;
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(length);
+            }
+        }
+        Err(format_error("length too large"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, RED, WHITE};
+    use crate::engine::VNERunner;
+    use crate::headless::HeadlessEngine;
+    use crate::image::Image;
+    use crate::render::{PixelPosition, VNERenderer};
+
+    /// Moves a box with the arrow keys at a speed, so the delta matters, and marks the cursor
+    /// while the left button is down. Keeps every frame.
+    #[derive(Default)]
+    struct Painter {
+        x: f64,
+        y: f64,
+        frames: Vec<Image>,
+    }
+
+    impl VNERunner for Painter {
+        fn tick(&mut self, engine: &VNEngineState, renderer: &mut (impl VNERenderer + ?Sized)) {
+            let input = engine.input();
+            let distance = 40.0 * engine.delta;
+            for (key, dx, dy) in [
+                (Keycode::Right, 1.0, 0.0),
+                (Keycode::Left, -1.0, 0.0),
+                (Keycode::Down, 0.0, 1.0),
+            ] {
+                if input.key_down(key) {
+                    self.x += dx * distance;
+                    self.y += dy * distance;
+                }
+            }
+            let (x, y) = (self.x as i32, self.y as i32);
+            renderer.clear_screen(BLACK);
+            renderer.fill_rectangle(
+                PixelPosition { x, y },
+                PixelPosition { x: x + 3, y: y + 3 },
+                WHITE,
+            );
+            if let (true, Some((x, y))) = (
+                input.button_down(MouseButton::Left),
+                input.cursor_position(),
+            ) {
+                let (x, y) = (x as i32, y as i32);
+                renderer.draw_pixel(PixelPosition { x, y }, RED);
+            }
+            if input.key_pressed(Keycode::Space) {
+                renderer.draw_pixel(PixelPosition { x: 0, y: 0 }, RED);
+            }
+            self.frames.push(renderer.capture_frame());
+        }
+    }
+
+    /// The input before frame `frame` of the scripted session
+    fn script(frame: u64) -> InputFrame {
+        let mut events = Vec::new();
+        match frame % 40 {
+            0 => events.push(InputEvent::PressKey(Keycode::Right)),
+            25 => events.push(InputEvent::ReleaseKey(Keycode::Right)),
+            _ => {}
+        }
+        match frame % 30 {
+            10 => events.push(InputEvent::PressKey(Keycode::Down)),
+            20 => events.push(InputEvent::ReleaseKey(Keycode::Down)),
+            _ => {}
+        }
+        if frame % 17 == 3 {
+            let position = ((frame % 32) as f64 + 0.5, (frame % 21) as f64 + 0.25);
+            events.push(InputEvent::MoveCursor(Some(position)));
+        }
+        match frame {
+            50 => events.push(InputEvent::PressButton(MouseButton::Left)),
+            90 => events.push(InputEvent::ReleaseButton(MouseButton::Left)),
+            100 => events.push(InputEvent::ReleaseAll),
+            _ if frame.is_multiple_of(23) => events.push(InputEvent::PressKey(Keycode::Space)),
+            _ if frame % 23 == 1 => events.push(InputEvent::ReleaseKey(Keycode::Space)),
+            _ => {}
+        }
+        // uneven frame times
+        let delta = [1.0 / 60.0, 1.0 / 30.0, 0.021][frame as usize % 3];
+        InputFrame { delta, events }
+    }
+
+    #[test]
+    fn replays_give_the_recorded_frames() {
+        let mut recorded = Painter::default();
+        let mut log = InputLog::new(32, 24);
+        let mut engine = HeadlessEngine::new(32, 24, 1.0 / 60.0);
+        engine.setup(&mut recorded);
+        // recorded the way a live session is, not through the replay code
+        engine.run(&mut recorded, 120, |frame, engine| {
+            let input = script(frame);
+            engine.delta = input.delta;
+            for event in &input.events {
+                engine.input_mut().apply(event);
+            }
+            log.push_frame(input);
+        });
+
+        let log = InputLog::decode(&log.encode()).unwrap();
+        let mut replayed = Painter::default();
+        let mut engine = HeadlessEngine::new(log.width(), log.height(), 1.0 / 60.0);
+        engine.setup(&mut replayed);
+        engine.replay(&mut replayed, &log);
+
+        assert_eq!(replayed.frames.len(), 120);
+        assert!(replayed.frames == recorded.frames);
+        // the session did something worth replaying
+        assert!(recorded.frames[0] != recorded.frames[119]);
+        assert!((recorded.x, recorded.y) != (0.0, 0.0));
+    }
+}
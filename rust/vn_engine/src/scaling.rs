@@ -0,0 +1,160 @@
+/// How the framebuffer is fitted into a window of a different size
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScalingPolicy {
+    /// the largest whole multiple of the framebuffer that fits, so all pixels keep the same size.
+    /// Falls back to [ScalingPolicy::Letterbox] if the window is smaller than the framebuffer.
+    #[default]
+    Integer,
+    /// the largest scale that fits while keeping the aspect ratio
+    Letterbox,
+}
+
+/// The part of the window the framebuffer is drawn to, in physical pixels from the top left.
+/// The rest of the window stays black.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Centers a `framebuffer` sized area scaled according to `policy` in a `window` sized area
+    ///
+    /// ```
+    /// use vn_engine::scaling::{ScalingPolicy, Viewport};
+    ///
+    /// let integer = Viewport::fit(ScalingPolicy::Integer, (320, 200), (1000, 700));
+    /// assert_eq!(integer, Viewport { x: 20, y: 50, width: 960, height: 600 });
+    ///
+    /// let letterbox = Viewport::fit(ScalingPolicy::Letterbox, (320, 200), (1000, 700));
+    /// assert_eq!(letterbox, Viewport { x: 0, y: 37, width: 1000, height: 625 });
+    /// ```
+    pub fn fit(policy: ScalingPolicy, framebuffer: (u32, u32), window: (u32, u32)) -> Viewport {
+        let (fw, fh) = (framebuffer.0.max(1), framebuffer.1.max(1));
+        let (ww, wh) = window;
+        let (width, height) = match policy {
+            ScalingPolicy::Integer if ww >= fw && wh >= fh => {
+                let scale = (ww / fw).min(wh / fh);
+                (fw * scale, fh * scale)
+            }
+            _ => {
+                let scale = f64::min(ww as f64 / fw as f64, wh as f64 / fh as f64);
+                let width = (fw as f64 * scale).round() as u32;
+                let height = (fh as f64 * scale).round() as u32;
+                (width.min(ww), height.min(wh))
+            }
+        };
+        Viewport {
+            x: (ww - width) / 2,
+            y: (wh - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Maps a position in the window to the framebuffer, `None` if it lies outside of the
+    /// viewport
+    ///
+    /// ```
+    /// use vn_engine::scaling::{ScalingPolicy, Viewport};
+    ///
+    /// let viewport = Viewport::fit(ScalingPolicy::Integer, (320, 200), (1000, 700));
+    /// assert_eq!(viewport.to_framebuffer((320, 200), (20.0, 50.0)), Some((0.0, 0.0)));
+    /// assert_eq!(viewport.to_framebuffer((320, 200), (500.0, 350.0)), Some((160.0, 100.0)));
+    /// assert_eq!(viewport.to_framebuffer((320, 200), (10.0, 350.0)), None);
+    /// assert_eq!(viewport.to_framebuffer((320, 200), (980.0, 350.0)), None);
+    /// ```
+    pub fn to_framebuffer(
+        &self,
+        framebuffer: (u32, u32),
+        position: (f64, f64),
+    ) -> Option<(f64, f64)> {
+        let x = position.0 - self.x as f64;
+        let y = position.1 - self.y as f64;
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
+        Some((
+            x * framebuffer.0 as f64 / self.width as f64,
+            y * framebuffer.1 as f64 / self.height as f64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_falls_back_to_letterbox_in_small_windows() {
+        for window in [(300, 150), (1000, 150), (300, 1000)] {
+            let integer = Viewport::fit(ScalingPolicy::Integer, (320, 200), window);
+            let letterbox = Viewport::fit(ScalingPolicy::Letterbox, (320, 200), window);
+            assert_eq!(integer, letterbox, "{:?}", window);
+        }
+        let viewport = Viewport::fit(ScalingPolicy::Integer, (320, 200), (300, 150));
+        assert_eq!(
+            viewport,
+            Viewport {
+                x: 30,
+                y: 0,
+                width: 240,
+                height: 150
+            }
+        );
+    }
+
+    #[test]
+    fn minimized_window() {
+        for policy in [ScalingPolicy::Integer, ScalingPolicy::Letterbox] {
+            let viewport = Viewport::fit(policy, (320, 200), (0, 0));
+            assert_eq!(
+                viewport,
+                Viewport {
+                    x: 0,
+                    y: 0,
+                    width: 0,
+                    height: 0
+                }
+            );
+            assert_eq!(viewport.to_framebuffer((320, 200), (0.0, 0.0)), None);
+        }
+    }
+
+    #[test]
+    fn odd_remainders_are_rounded_down() {
+        let integer = Viewport::fit(ScalingPolicy::Integer, (320, 200), (1001, 701));
+        assert_eq!(
+            integer,
+            Viewport {
+                x: 20,
+                y: 50,
+                width: 960,
+                height: 600
+            }
+        );
+        let letterbox = Viewport::fit(ScalingPolicy::Letterbox, (100, 100), (301, 200));
+        assert_eq!(
+            letterbox,
+            Viewport {
+                x: 50,
+                y: 0,
+                width: 200,
+                height: 200
+            }
+        );
+    }
+
+    #[test]
+    fn framebuffer_edges() {
+        let viewport = Viewport::fit(ScalingPolicy::Integer, (320, 200), (1001, 701));
+        let last = (20.0 + 959.0, 50.0 + 599.0);
+        let (x, y) = viewport.to_framebuffer((320, 200), last).unwrap();
+        assert!((319.0..320.0).contains(&x) && (199.0..200.0).contains(&y));
+        assert_eq!(viewport.to_framebuffer((320, 200), (980.0, 300.0)), None);
+        assert_eq!(viewport.to_framebuffer((320, 200), (300.0, 650.0)), None);
+        assert_eq!(viewport.to_framebuffer((320, 200), (19.9, 300.0)), None);
+    }
+}
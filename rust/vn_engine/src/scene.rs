@@ -0,0 +1,301 @@
+use crate::color::{BlendMode, RGBA};
+use crate::engine::{VNERunner, VNEngineState};
+use crate::render::{PixelPosition, VNERenderer};
+use winit::event::WindowEvent;
+
+/// One screen of a game, e.g. a title screen, the gameplay or a pause menu.
+/// Scenes live on the stack of a [SceneManager], only the top one is updated.
+#[allow(unused_variables)]
+pub trait Scene {
+    /// Gets called when the scene is put on the stack
+    fn setup(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer) {}
+    /// Advances the scene by `dt` seconds, once per frame or once per fixed step with a
+    /// [crate::engine::FixedTimestep]. The returned command changes the scene stack.
+    fn tick(&mut self, engine: &VNEngineState, dt: f64) -> SceneCommand {
+        SceneCommand::None
+    }
+    /// Draws the scene, `alpha` is the interpolation factor of [VNERunner::render] and 1 without a
+    /// fixed timestep
+    fn render(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, alpha: f64) {}
+    /// Gets called for every window event while the scene is on top
+    fn event(&mut self, engine: &VNEngineState, event: &WindowEvent) {}
+    /// Overlays are drawn on top of the scenes below them, e.g. for a pause menu
+    fn is_overlay(&self) -> bool {
+        false
+    }
+    /// Gets called when the scene is removed from the stack
+    fn tear_down(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer) {}
+}
+
+/// A change of the scene stack, returned from [Scene::tick]
+pub enum SceneCommand {
+    None,
+    /// puts a scene on top, the current one is paused until it is on top again
+    Push(Box<dyn Scene>),
+    /// removes the top scene
+    Pop,
+    /// swaps the top scene for another one
+    Replace(Box<dyn Scene>),
+    /// removes all scenes and starts over with the given one
+    ReplaceAll(Box<dyn Scene>),
+    /// fades to `color` over the first half of `duration`, then applies `command` and fades back
+    Fade {
+        command: Box<SceneCommand>,
+        color: RGBA,
+        duration: f64,
+    },
+}
+
+impl SceneCommand {
+    /// Wraps the command in a [SceneCommand::Fade]
+    pub fn fade(self, color: RGBA, duration: f64) -> SceneCommand {
+        SceneCommand::Fade {
+            command: Box::new(self),
+            color,
+            duration,
+        }
+    }
+}
+
+struct Fade {
+    /// applied at the darkest point, `None` once it was
+    command: Option<SceneCommand>,
+    color: RGBA,
+    duration: f64,
+    elapsed: f64,
+}
+
+/// A [VNERunner] driving a stack of scenes
+pub struct SceneManager {
+    scenes: Vec<Box<dyn Scene>>,
+    /// commands wait here until a renderer is available to set up and tear down scenes
+    pending: Option<SceneCommand>,
+    fade: Option<Fade>,
+}
+
+impl SceneManager {
+    pub fn new(scene: Box<dyn Scene>) -> SceneManager {
+        SceneManager {
+            scenes: Vec::new(),
+            pending: Some(SceneCommand::Push(scene)),
+            fade: None,
+        }
+    }
+
+    /// number of scenes on the stack
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    /// true while a fade is running, the scenes are not updated meanwhile
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Updates the top scene, commands are applied on the next draw
+    fn update(&mut self, engine: &VNEngineState, dt: f64) {
+        if self.pending.is_some() || self.fade.is_some() {
+            return;
+        }
+        if let Some(scene) = self.scenes.last_mut() {
+            match scene.tick(engine, dt) {
+                SceneCommand::None => {}
+                command => self.pending = Some(command),
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        command: SceneCommand,
+        engine: &VNEngineState,
+        renderer: &mut dyn VNERenderer,
+    ) {
+        match command {
+            SceneCommand::None => {}
+            SceneCommand::Push(mut scene) => {
+                scene.setup(engine, renderer);
+                self.scenes.push(scene);
+            }
+            SceneCommand::Pop => {
+                if let Some(mut scene) = self.scenes.pop() {
+                    scene.tear_down(engine, renderer);
+                }
+            }
+            SceneCommand::Replace(scene) => {
+                self.apply(SceneCommand::Pop, engine, renderer);
+                self.apply(SceneCommand::Push(scene), engine, renderer);
+            }
+            SceneCommand::ReplaceAll(scene) => {
+                self.tear_down_all(engine, renderer);
+                self.apply(SceneCommand::Push(scene), engine, renderer);
+            }
+            SceneCommand::Fade {
+                command,
+                color,
+                duration,
+            } => {
+                self.fade = Some(Fade {
+                    command: Some(*command),
+                    color,
+                    duration,
+                    elapsed: 0.0,
+                });
+            }
+        }
+    }
+
+    fn tear_down_all(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer) {
+        while let Some(mut scene) = self.scenes.pop() {
+            scene.tear_down(engine, renderer);
+        }
+    }
+
+    /// Applies pending commands, advances the fade and draws all visible scenes
+    fn draw(&mut self, engine: &VNEngineState, renderer: &mut dyn VNERenderer, alpha: f64) {
+        if let Some(command) = self.pending.take() {
+            self.apply(command, engine, renderer);
+        }
+
+        let mut fade_color = None;
+        let mut command = None;
+        if let Some(fade) = &mut self.fade {
+            fade.elapsed += engine.delta;
+            let half = fade.duration / 2.0;
+            if fade.elapsed >= half {
+                command = fade.command.take();
+            }
+            if half > 0.0 {
+                let strength = 1.0 - ((fade.elapsed - half) / half).abs();
+                fade_color = Some(RGBA {
+                    a: fade.color.a * strength.clamp(0.0, 1.0) as f32,
+                    ..fade.color
+                });
+            }
+            if fade.elapsed >= fade.duration {
+                self.fade = None;
+            }
+        }
+        if let Some(mut command) = command {
+            // the screen is already faded out, a nested fade would replace the running one
+            while let SceneCommand::Fade { command: inner, .. } = command {
+                command = *inner;
+            }
+            self.apply(command, engine, renderer);
+        }
+
+        // every overlay needs the scene below it
+        let first_visible = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        for scene in &mut self.scenes[first_visible..] {
+            scene.render(engine, renderer, alpha);
+        }
+
+        if let Some(color) = fade_color {
+            let mode = renderer.blend_mode();
+            renderer.set_blend_mode(BlendMode::Alpha);
+            renderer.fill_rectangle(
+                PixelPosition { x: 0, y: 0 },
+                PixelPosition {
+                    x: engine.width as i32 - 1,
+                    y: engine.height as i32 - 1,
+                },
+                color,
+            );
+            renderer.set_blend_mode(mode);
+        }
+    }
+}
+
+impl VNERunner for SceneManager {
+    fn setup(&mut self, engine: &VNEngineState, mut renderer: &mut (impl VNERenderer + ?Sized)) {
+        if let Some(command) = self.pending.take() {
+            self.apply(command, engine, &mut renderer);
+        }
+    }
+
+    fn tick(&mut self, engine: &VNEngineState, mut renderer: &mut (impl VNERenderer + ?Sized)) {
+        self.update(engine, engine.delta);
+        self.draw(engine, &mut renderer, 1.0);
+    }
+
+    fn fixed_update(&mut self, engine: &VNEngineState, dt: f64) {
+        self.update(engine, dt);
+    }
+
+    fn render(
+        &mut self,
+        engine: &VNEngineState,
+        mut renderer: &mut (impl VNERenderer + ?Sized),
+        alpha: f64,
+    ) {
+        self.draw(engine, &mut renderer, alpha);
+    }
+
+    fn event(&mut self, engine: &VNEngineState, event: &WindowEvent) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.event(engine, event);
+        }
+    }
+
+    fn tear_down(&mut self, engine: &VNEngineState, mut renderer: &mut (impl VNERenderer + ?Sized)) {
+        self.tear_down_all(engine, &mut renderer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, BLUE, RED};
+    use crate::headless::HeadlessEngine;
+
+    /// Fills the screen and returns `command` on its first tick
+    struct Fill {
+        color: RGBA,
+        command: Option<SceneCommand>,
+    }
+
+    impl Scene for Fill {
+        fn tick(&mut self, _engine: &VNEngineState, _dt: f64) -> SceneCommand {
+            self.command.take().unwrap_or(SceneCommand::None)
+        }
+
+        fn render(&mut self, _engine: &VNEngineState, renderer: &mut dyn VNERenderer, _alpha: f64) {
+            renderer.clear_screen(self.color);
+        }
+    }
+
+    #[test]
+    fn nested_fades_apply_their_command() {
+        let next = Fill {
+            color: RED,
+            command: None,
+        };
+        let command = SceneCommand::Replace(Box::new(next))
+            .fade(BLACK, 0.5)
+            .fade(BLACK, 0.5);
+        let mut manager = SceneManager::new(Box::new(Fill {
+            color: BLUE,
+            command: Some(command),
+        }));
+        let mut engine = HeadlessEngine::new(4, 4, 0.125);
+        engine.setup(&mut manager);
+
+        // the command comes on the first frame, the screen is black on the second
+        engine.run(&mut manager, 2, |_, _| {});
+        assert!(manager.is_fading());
+        assert_eq!(engine.renderer().pixel(0, 0), BLACK);
+
+        engine.run(&mut manager, 2, |_, _| {});
+        assert!(!manager.is_fading());
+        assert_eq!(manager.len(), 1);
+        assert_eq!(engine.renderer().pixel(0, 0), RED);
+    }
+}
@@ -0,0 +1,810 @@
+use crate::color::{BlendMode, RGBA};
+use crate::render::{ClipRect, PixelPosition, VNERenderer};
+
+/// Draws every point once, points outside of the clip rectangle are skipped
+fn draw_points<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    mut points: Vec<(i32, i32)>,
+    color: RGBA,
+) {
+    // the symmetric octants and quadrants overlap on the axes and diagonals, drawing those
+    // pixels twice would blend translucent colors twice
+    points.sort_unstable();
+    points.dedup();
+    let clip = renderer.clip();
+    for (x, y) in points {
+        let position = PixelPosition { x, y };
+        if clip.contains(position) {
+            renderer.draw_pixel(position, color);
+        }
+    }
+}
+
+/// Fills every row between the leftmost and rightmost outline point of that row
+fn fill_rows<T: VNERenderer + ?Sized>(renderer: &mut T, points: &[(i32, i32)], color: RGBA) {
+    let (top, bottom) = match (
+        points.iter().map(|p| p.1).min(),
+        points.iter().map(|p| p.1).max(),
+    ) {
+        (Some(top), Some(bottom)) => (top, bottom),
+        _ => return,
+    };
+    let mut rows = vec![(i32::MAX, i32::MIN); (bottom - top + 1) as usize];
+    for &(x, y) in points {
+        let row = &mut rows[(y - top) as usize];
+        row.0 = row.0.min(x);
+        row.1 = row.1.max(x);
+    }
+    for (idx, (left, right)) in rows.into_iter().enumerate() {
+        fill_span(renderer, top + idx as i32, left, right, color);
+    }
+}
+
+/// Fills the pixels from `left` to `right` in row `y`, both included
+fn fill_span<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    y: i32,
+    left: i32,
+    right: i32,
+    color: RGBA,
+) {
+    let clip = renderer.clip();
+    if y < clip.top_left.y || y > clip.bottom_right.y {
+        return;
+    }
+    for x in left.max(clip.top_left.x)..=right.min(clip.bottom_right.x) {
+        renderer.draw_pixel(PixelPosition { x, y }, color);
+    }
+}
+
+/////////////
+// CIRCLES //
+/////////////
+
+/// The outline of a circle with the midpoint algorithm
+fn circle_points(center: PixelPosition, radius: u32) -> Vec<(i32, i32)> {
+    let radius = radius as i32;
+    let mut points = Vec::new();
+    let mut x = 0;
+    let mut y = radius;
+    let mut decision = 1 - radius;
+    while x <= y {
+        for (dx, dy) in [(x, y), (y, x)] {
+            points.push((center.x + dx, center.y + dy));
+            points.push((center.x - dx, center.y + dy));
+            points.push((center.x + dx, center.y - dy));
+            points.push((center.x - dx, center.y - dy));
+        }
+        x += 1;
+        if decision < 0 {
+            decision += 2 * x + 1;
+        } else {
+            y -= 1;
+            decision += 2 * (x - y) + 1;
+        }
+    }
+    points
+}
+
+pub(crate) fn draw_circle<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    center: PixelPosition,
+    radius: u32,
+    color: RGBA,
+) {
+    draw_points(renderer, circle_points(center, radius), color);
+}
+
+pub(crate) fn fill_circle<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    center: PixelPosition,
+    radius: u32,
+    color: RGBA,
+) {
+    fill_rows(renderer, &circle_points(center, radius), color);
+}
+
+//////////////
+// ELLIPSES //
+//////////////
+
+/// The outline of an axis aligned ellipse with the midpoint algorithm, the decision variables are
+/// scaled by 4 to stay in integers
+fn ellipse_points(center: PixelPosition, radius_x: u32, radius_y: u32) -> Vec<(i32, i32)> {
+    let mut points = Vec::new();
+    let mut plot = |x: i64, y: i64| {
+        let (x, y) = (x as i32, y as i32);
+        points.push((center.x + x, center.y + y));
+        points.push((center.x - x, center.y + y));
+        points.push((center.x + x, center.y - y));
+        points.push((center.x - x, center.y - y));
+    };
+
+    let (rx, ry) = (radius_x as i64, radius_y as i64);
+    if ry == 0 {
+        // the algorithm below only walks down from the top, a flat ellipse has no height
+        for x in 0..=rx {
+            plot(x, 0);
+        }
+        return points;
+    }
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let mut x = 0;
+    let mut y = ry;
+    let mut dx = 0;
+    let mut dy = 2 * rx2 * y;
+
+    // region 1, the slope is flatter than -1
+    let mut decision = 4 * ry2 - 4 * rx2 * ry + rx2;
+    while dx < dy {
+        plot(x, y);
+        x += 1;
+        dx += 2 * ry2;
+        if decision < 0 {
+            decision += 4 * (dx + ry2);
+        } else {
+            y -= 1;
+            dy -= 2 * rx2;
+            decision += 4 * (dx - dy + ry2);
+        }
+    }
+
+    // region 2, the slope is steeper than -1
+    let mut decision =
+        ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2;
+    while y >= 0 {
+        plot(x, y);
+        y -= 1;
+        dy -= 2 * rx2;
+        if decision > 0 {
+            decision += 4 * (rx2 - dy);
+        } else {
+            x += 1;
+            dx += 2 * ry2;
+            decision += 4 * (dx - dy + rx2);
+        }
+    }
+    points
+}
+
+pub(crate) fn draw_ellipse<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    center: PixelPosition,
+    radius_x: u32,
+    radius_y: u32,
+    color: RGBA,
+) {
+    draw_points(renderer, ellipse_points(center, radius_x, radius_y), color);
+}
+
+pub(crate) fn fill_ellipse<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    center: PixelPosition,
+    radius_x: u32,
+    radius_y: u32,
+    color: RGBA,
+) {
+    fill_rows(renderer, &ellipse_points(center, radius_x, radius_y), color);
+}
+
+//////////////
+// POLYGONS //
+//////////////
+
+/// Scanline fill with the even-odd rule. A pixel is filled if its position lies inside of the
+/// polygon, edges on the top and left are inside, edges on the bottom and right are not, so
+/// polygons sharing an edge do not overlap.
+fn fill_polygon_points<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    vertices: &[(f64, f64)],
+    color: RGBA,
+) {
+    if vertices.len() < 3 {
+        return;
+    }
+    let clip = renderer.clip();
+    let top = vertices.iter().map(|v| v.1).fold(f64::INFINITY, f64::min);
+    let bottom = vertices
+        .iter()
+        .map(|v| v.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let first_row = (top.ceil() as i32).max(clip.top_left.y);
+    let last_row = ((bottom.ceil() as i32) - 1).min(clip.bottom_right.y);
+
+    let mut crossings = Vec::new();
+    for y in first_row..=last_row {
+        let scanline = y as f64;
+        crossings.clear();
+        for (idx, &(x1, y1)) in vertices.iter().enumerate() {
+            let (x2, y2) = vertices[(idx + 1) % vertices.len()];
+            // half open, so a vertex on the scanline is only counted once
+            if (y1 <= scanline) != (y2 <= scanline) {
+                crossings.push(x1 + (scanline - y1) * (x2 - x1) / (y2 - y1));
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for pair in crossings.chunks_exact(2) {
+            fill_span(
+                renderer,
+                y,
+                pair[0].ceil() as i32,
+                pair[1].ceil() as i32 - 1,
+                color,
+            );
+        }
+    }
+}
+
+pub(crate) fn fill_polygon<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    vertices: &[PixelPosition],
+    color: RGBA,
+) {
+    let vertices: Vec<(f64, f64)> = vertices.iter().map(|v| (v.x as f64, v.y as f64)).collect();
+    fill_polygon_points(renderer, &vertices, color);
+}
+
+///////////
+// LINES //
+///////////
+
+/// A line `thickness` pixels wide, drawn as a rectangle around the segment that reaches half a
+/// pixel beyond both ends
+pub(crate) fn draw_thick_line<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    from: PixelPosition,
+    to: PixelPosition,
+    thickness: u32,
+    color: RGBA,
+) {
+    let (dx, dy) = ((to.x - from.x) as f64, (to.y - from.y) as f64);
+    let length = (dx * dx + dy * dy).sqrt();
+    if thickness <= 1 || length == 0.0 {
+        renderer.draw_line(from, to, color);
+        return;
+    }
+
+    let (ux, uy) = (dx / length * 0.5, dy / length * 0.5);
+    let half = thickness as f64 * 0.5;
+    let (nx, ny) = (-dy / length * half, dx / length * half);
+    // pixel positions are sampled at integers, the corners are shifted by half a pixel so the
+    // rectangle is centered on the pixels of the line
+    let (fx, fy) = (from.x as f64 + 0.5 - ux, from.y as f64 + 0.5 - uy);
+    let (tx, ty) = (to.x as f64 + 0.5 + ux, to.y as f64 + 0.5 + uy);
+    fill_polygon_points(
+        renderer,
+        &[
+            (fx + nx, fy + ny),
+            (tx + nx, ty + ny),
+            (tx - nx, ty - ny),
+            (fx - nx, fy - ny),
+        ],
+        color,
+    );
+}
+
+/// Xiaolin Wu's line, every step covers two pixels across the line whose alpha is scaled by how
+/// much of the line falls on them. The result depends on the blend mode,
+/// [crate::color::BlendMode::Alpha] gives smooth lines.
+pub(crate) fn draw_line_aa<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    from: PixelPosition,
+    to: PixelPosition,
+    color: RGBA,
+) {
+    let clip = renderer.clip();
+    let steep = (to.y - from.y).abs() > (to.x - from.x).abs();
+    // walk along the longer axis, `major` and `minor` are swapped back when plotting
+    let (mut a, mut b) = match steep {
+        false => ((from.x, from.y), (to.x, to.y)),
+        true => ((from.y, from.x), (to.y, to.x)),
+    };
+    if a.0 > b.0 {
+        std::mem::swap(&mut a, &mut b);
+    }
+    let gradient = match b.0 - a.0 {
+        0 => 0.0,
+        length => (b.1 - a.1) as f64 / length as f64,
+    };
+    let (major_min, major_max) = match steep {
+        false => (clip.top_left.x, clip.bottom_right.x),
+        true => (clip.top_left.y, clip.bottom_right.y),
+    };
+
+    for major in a.0.max(major_min)..=b.0.min(major_max) {
+        let minor = a.1 as f64 + gradient * (major - a.0) as f64;
+        let base = minor.floor();
+        let fraction = minor - base;
+        for (offset, coverage) in [(0, 1.0 - fraction), (1, fraction)] {
+            if coverage <= 0.0 {
+                continue;
+            }
+            let minor = base as i32 + offset;
+            let position = match steep {
+                false => PixelPosition { x: major, y: minor },
+                true => PixelPosition { x: minor, y: major },
+            };
+            if clip.contains(position) {
+                let color = RGBA {
+                    a: color.a * coverage as f32,
+                    ..color
+                };
+                renderer.draw_pixel(position, color);
+            }
+        }
+    }
+}
+
+////////////////
+// FAST FILLS //
+////////////////
+
+/// A row of pixels as `(y, left, right)`, both ends included
+pub(crate) type Span = (i32, i32, i32);
+
+/// The rows of a filled rectangle inside of `clip`
+pub(crate) fn rectangle_spans(
+    top_left: PixelPosition,
+    bottom_right: PixelPosition,
+    clip: ClipRect,
+) -> impl Iterator<Item = Span> {
+    let area = ClipRect::new(top_left, bottom_right).intersect(&clip);
+    let rows = match area.is_empty() {
+        true => 0..0,
+        false => area.top_left.y..area.bottom_right.y + 1,
+    };
+    rows.map(move |y| (y, area.top_left.x, area.bottom_right.x))
+}
+
+/// The rows of a filled triangle inside of `clip`, covering exactly the pixels
+/// [crate::render::VNERenderer::fill_triangle] tests as inside
+pub(crate) fn triangle_spans(
+    v1: PixelPosition,
+    v2: PixelPosition,
+    v3: PixelPosition,
+    clip: ClipRect,
+) -> impl Iterator<Item = Span> {
+    let bounds = ClipRect::new(
+        PixelPosition {
+            x: v1.x.min(v2.x).min(v3.x),
+            y: v1.y.min(v2.y).min(v3.y),
+        },
+        PixelPosition {
+            x: v1.x.max(v2.x).max(v3.x),
+            y: v1.y.max(v2.y).max(v3.y),
+        },
+    )
+    .intersect(&clip);
+    let rows = match bounds.is_empty() {
+        true => 0..0,
+        false => bounds.top_left.y..bounds.bottom_right.y + 1,
+    };
+    rows.filter_map(move |y| {
+        let mut left = bounds.top_left.x as i64;
+        let mut right = bounds.bottom_right.x as i64;
+        for (a, b) in [(v1, v2), (v2, v3), (v3, v1)] {
+            // the edge test is linear in x: x * slope >= limit
+            let slope = b.y as i64 - a.y as i64;
+            let limit = a.x as i64 * slope + (y as i64 - a.y as i64) * (b.x as i64 - a.x as i64);
+            match slope {
+                0 if limit > 0 => return None,
+                0 => {}
+                slope if slope > 0 => left = left.max(div_ceil(limit, slope)),
+                slope => right = right.min(div_floor(limit, slope)),
+            }
+        }
+        match left <= right {
+            true => Some((y, left as i32, right as i32)),
+            false => None,
+        }
+    })
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    let quotient = a / b;
+    match a % b != 0 && (a < 0) != (b < 0) {
+        true => quotient - 1,
+        false => quotient,
+    }
+}
+
+fn div_ceil(a: i64, b: i64) -> i64 {
+    -div_floor(-a, b)
+}
+
+/// A pixel of a buffer spans are filled in, stored with 8 bits per channel
+pub(crate) trait BufferPixel: Copy {
+    fn from_color(color: RGBA) -> Self;
+    fn to_color(self) -> RGBA;
+}
+
+impl BufferPixel for RGBA {
+    fn from_color(color: RGBA) -> RGBA {
+        color.quantized()
+    }
+
+    fn to_color(self) -> RGBA {
+        self
+    }
+}
+
+/// Fills spans of a row major buffer the way the renderers draw pixels, the spans must lie inside
+/// of the buffer. All renderers fill through this, the color buffers of
+/// [crate::headless::HeadlessRenderer] and [crate::opengl::OpenGLRenderer] as well as the sprite
+/// of [crate::sprite::SpriteBased].
+pub(crate) fn fill_color_buffer<P: BufferPixel>(
+    buffer: &mut [P],
+    width: u32,
+    spans: impl Iterator<Item = Span>,
+    color: RGBA,
+    mode: BlendMode,
+) {
+    // replacing does not depend on the target, so the color is only converted once
+    let replacement = P::from_color(color);
+    for (y, left, right) in spans {
+        let row = y as usize * width as usize;
+        let pixels = &mut buffer[row + left as usize..=row + right as usize];
+        match mode {
+            BlendMode::Replace => pixels.fill(replacement),
+            mode => {
+                for pixel in pixels {
+                    *pixel = P::from_color(color.blend(pixel.to_color(), mode));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, WHITE};
+    use crate::headless::HeadlessRenderer;
+    use crate::image::Image;
+    use crate::render::ClipStack;
+    use crate::sprite::Pixel;
+
+    /// The renderer as rows of `#` for drawn and `.` for untouched pixels
+    fn grid(renderer: &HeadlessRenderer) -> Vec<String> {
+        (0..renderer.height())
+            .map(|y| {
+                (0..renderer.width())
+                    .map(|x| match renderer.pixel(x, y) {
+                        BLACK => '.',
+                        _ => '#',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// A grid in the form of [grid], one row per line
+    fn rows(text: &str) -> Vec<&str> {
+        text.split_whitespace().collect()
+    }
+
+    fn drawn(renderer: &HeadlessRenderer) -> Vec<(i32, i32)> {
+        let mut points = Vec::new();
+        for y in 0..renderer.height() {
+            for x in 0..renderer.width() {
+                if renderer.pixel(x, y) != BLACK {
+                    points.push((x as i32, y as i32));
+                }
+            }
+        }
+        points
+    }
+
+    fn at(x: i32, y: i32) -> PixelPosition {
+        PixelPosition { x, y }
+    }
+
+    /// Draws with `draw` on a small renderer, so the shape crosses its edges, and at an offset on
+    /// a large one that holds all of it. The small one has to show the same part of the shape.
+    fn assert_clipped(draw: impl Fn(&mut HeadlessRenderer, PixelPosition)) {
+        let mut small = HeadlessRenderer::new(8, 8);
+        draw(&mut small, at(0, 0));
+        let mut large = HeadlessRenderer::new(24, 24);
+        draw(&mut large, at(8, 8));
+        let inside: Vec<String> = grid(&large)[8..16]
+            .iter()
+            .map(|row| row[8..16].to_string())
+            .collect();
+        assert_eq!(grid(&small), inside);
+        assert!(drawn(&large).len() > drawn(&small).len());
+    }
+
+    #[test]
+    fn circles() {
+        let outline = "
+            ..###..
+            .#...#.
+            #.....#
+            #.....#
+            #.....#
+            .#...#.
+            ..###..
+        ";
+        let filled = "
+            ..###..
+            .#####.
+            #######
+            #######
+            #######
+            .#####.
+            ..###..
+        ";
+        let mut renderer = HeadlessRenderer::new(7, 7);
+        renderer.draw_circle(at(3, 3), 3, WHITE);
+        assert_eq!(grid(&renderer), rows(outline));
+        renderer.fill_circle(at(3, 3), 3, WHITE);
+        assert_eq!(grid(&renderer), rows(filled));
+
+        for radius in 0..30 {
+            let mut renderer = HeadlessRenderer::new(61, 61);
+            renderer.draw_circle(at(30, 30), radius, WHITE);
+            let points: Vec<(i32, i32)> = drawn(&renderer)
+                .into_iter()
+                .map(|(x, y)| (x - 30, y - 30))
+                .collect();
+            for &(x, y) in &points {
+                for mirrored in [(-x, y), (x, -y), (y, x)] {
+                    assert!(points.contains(&mirrored), "{:?} of {}", mirrored, radius);
+                }
+                let distance = ((x * x + y * y) as f64).sqrt();
+                assert!((distance - radius as f64).abs() < 1.0, "{:?}", (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn translucent_outlines_are_drawn_once() {
+        let color = RGBA { a: 0.5, ..WHITE };
+        let expected = color.blend(BLACK, BlendMode::Alpha).quantized();
+        let outlines: [&dyn Fn(&mut HeadlessRenderer); 3] = [
+            &|renderer| renderer.draw_circle(at(10, 10), 8, color),
+            &|renderer| renderer.draw_ellipse(at(10, 10), 10, 4, color),
+            &|renderer| renderer.draw_ellipse(at(10, 10), 3, 9, color),
+        ];
+        for draw in outlines {
+            let mut renderer = HeadlessRenderer::new(21, 21);
+            renderer.set_blend_mode(BlendMode::Alpha);
+            draw(&mut renderer);
+            for (x, y) in drawn(&renderer) {
+                assert_eq!(renderer.pixel(x as u32, y as u32), expected, "{:?}", (x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn ellipses() {
+        let mut renderer = HeadlessRenderer::new(13, 7);
+        renderer.draw_ellipse(at(6, 3), 6, 3, WHITE);
+        let points = drawn(&renderer);
+        for &(x, y) in &points {
+            assert!(points.contains(&(12 - x, y)) && points.contains(&(x, 6 - y)));
+        }
+        for extreme in [(0, 3), (12, 3), (6, 0), (6, 6)] {
+            assert!(points.contains(&extreme), "{:?}", extreme);
+        }
+
+        renderer.fill_ellipse(at(6, 3), 6, 3, WHITE);
+        for row in grid(&renderer) {
+            // every row is one span, centered
+            let filled = row.trim_matches('.');
+            assert!(!filled.contains('.'), "{}", row);
+            assert_eq!(row.find('#'), Some(12 - row.rfind('#').unwrap()));
+        }
+
+        let flat = "
+            .........
+            .#######.
+            .........
+        ";
+        let mut renderer = HeadlessRenderer::new(9, 3);
+        renderer.draw_ellipse(at(4, 1), 3, 0, WHITE);
+        assert_eq!(grid(&renderer), rows(flat));
+    }
+
+    #[test]
+    fn concave_polygons() {
+        let u = [
+            at(0, 0),
+            at(6, 0),
+            at(6, 6),
+            at(4, 6),
+            at(4, 2),
+            at(2, 2),
+            at(2, 6),
+            at(0, 6),
+        ];
+        let filled = "
+            ######..
+            ######..
+            ##..##..
+            ##..##..
+            ##..##..
+            ##..##..
+            ........
+            ........
+        ";
+        let mut renderer = HeadlessRenderer::new(8, 8);
+        renderer.fill_polygon(&u, WHITE);
+        assert_eq!(grid(&renderer), rows(filled));
+
+        // the winding order does not matter
+        let reversed: Vec<PixelPosition> = u.iter().rev().copied().collect();
+        let mut renderer = HeadlessRenderer::new(8, 8);
+        renderer.fill_polygon(&reversed, WHITE);
+        assert_eq!(grid(&renderer), rows(filled));
+
+        // where a self intersecting polygon overlaps itself, even-odd leaves it empty
+        let twice = [
+            at(0, 0),
+            at(6, 0),
+            at(6, 6),
+            at(0, 6),
+            at(0, 2),
+            at(4, 2),
+            at(4, 4),
+            at(0, 4),
+        ];
+        let filled = "
+            ######..
+            ######..
+            ....##..
+            ....##..
+            ######..
+            ######..
+            ........
+            ........
+        ";
+        let mut renderer = HeadlessRenderer::new(8, 8);
+        renderer.fill_polygon(&twice, WHITE);
+        assert_eq!(grid(&renderer), rows(filled));
+    }
+
+    #[test]
+    fn thick_lines() {
+        let lines = "
+            .##.......
+            .##.......
+            .##.......
+            ..........
+            ..######..
+            ..######..
+            ..######..
+            ..........
+        ";
+        let mut renderer = HeadlessRenderer::new(10, 8);
+        renderer.draw_thick_line(at(2, 5), at(7, 5), 3, WHITE);
+        // even widths have one more pixel on the right of the center
+        renderer.draw_thick_line(at(1, 0), at(1, 2), 2, WHITE);
+        assert_eq!(grid(&renderer), rows(lines));
+    }
+
+    #[test]
+    fn anti_aliased_lines() {
+        let mut renderer = HeadlessRenderer::new(5, 5);
+        renderer.draw_line_aa(at(0, 0), at(4, 2), WHITE);
+        let alpha = |x, y| renderer.pixel(x, y).a;
+        // on the pixels in the middle of a step, half of the line falls on each
+        assert_eq!((alpha(0, 0), alpha(2, 1), alpha(4, 2)), (1.0, 1.0, 1.0));
+        assert!((alpha(1, 0) - 0.5).abs() < 0.01 && (alpha(1, 1) - 0.5).abs() < 0.01);
+        assert_eq!(drawn(&renderer).len(), 7);
+
+        // steep lines step along y, every row is covered once in total
+        let mut renderer = HeadlessRenderer::new(5, 5);
+        renderer.draw_line_aa(at(3, 4), at(0, 0), WHITE);
+        for y in 0..5 {
+            let coverage: f32 = (0..5)
+                .map(|x| renderer.pixel(x, y))
+                .filter(|pixel| *pixel != BLACK)
+                .map(|pixel| pixel.a)
+                .sum();
+            assert!((coverage - 1.0).abs() < 0.02, "row {}", y);
+        }
+    }
+
+    #[test]
+    fn shapes_are_clipped_at_the_framebuffer_edges() {
+        assert_clipped(|renderer, o| renderer.draw_circle(at(o.x + 1, o.y + 6), 5, WHITE));
+        assert_clipped(|renderer, o| renderer.fill_circle(at(o.x - 1, o.y + 2), 6, WHITE));
+        assert_clipped(|renderer, o| renderer.draw_ellipse(at(o.x + 4, o.y + 4), 7, 2, WHITE));
+        assert_clipped(|renderer, o| renderer.fill_ellipse(at(o.x + 7, o.y), 3, 6, WHITE));
+        assert_clipped(|renderer, o| {
+            let vertices = [
+                at(o.x - 5, o.y + 3),
+                at(o.x + 12, o.y - 2),
+                at(o.x + 4, o.y + 11),
+            ];
+            renderer.fill_polygon(&vertices, WHITE)
+        });
+        assert_clipped(|renderer, o| {
+            renderer.draw_thick_line(at(o.x - 3, o.y + 9), at(o.x + 10, o.y - 1), 3, WHITE)
+        });
+        assert_clipped(|renderer, o| {
+            renderer.draw_line_aa(at(o.x - 4, o.y + 1), at(o.x + 11, o.y + 6), WHITE)
+        });
+    }
+
+    /// Forwards only the required methods, so all fills use the default per pixel methods
+    struct DefaultFills(HeadlessRenderer);
+
+    impl VNERenderer for DefaultFills {
+        fn set_title(&mut self, title: &str) {
+            self.0.set_title(title);
+        }
+
+        fn blend_mode(&self) -> BlendMode {
+            self.0.blend_mode()
+        }
+
+        fn set_blend_mode(&mut self, mode: BlendMode) {
+            self.0.set_blend_mode(mode);
+        }
+
+        fn clear_screen(&mut self, color: RGBA) {
+            self.0.clear_screen(color);
+        }
+
+        fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+            self.0.draw_pixel(position, color);
+        }
+
+        fn clip_stack(&self) -> &ClipStack {
+            self.0.clip_stack()
+        }
+
+        fn clip_stack_mut(&mut self) -> &mut ClipStack {
+            self.0.clip_stack_mut()
+        }
+
+        fn capture_frame(&self) -> Image {
+            self.0.capture_frame()
+        }
+    }
+
+    /// The color buffers of the headless and OpenGL renderers and the sprite of the sprite based
+    /// one are all filled by [fill_color_buffer], they have to match drawing pixel by pixel
+    #[test]
+    fn span_fills_match_the_default_methods() {
+        let color = RGBA {
+            r: 0.4,
+            g: 0.7,
+            b: 0.9,
+            a: 0.6,
+        };
+        let triangles = [
+            [at(1, 1), at(30, 4), at(9, 20)],
+            [at(-10, 5), at(20, -7), at(40, 30)],
+            [at(5, 5), at(5, 5), at(12, 5)],
+        ];
+        let rectangles = [[at(3, 2), at(17, 9)], [at(-5, 20), at(40, 28)]];
+        let clip = ClipStack::new(32, 24).current();
+
+        for mode in [BlendMode::Replace, BlendMode::Alpha, BlendMode::Additive] {
+            let mut slow = DefaultFills(HeadlessRenderer::new(32, 24));
+            let mut fast = HeadlessRenderer::new(32, 24);
+            let mut sprite = vec![Pixel::from_color(BLACK); 32 * 24];
+            slow.set_blend_mode(mode);
+            fast.set_blend_mode(mode);
+            for [v1, v2, v3] in triangles {
+                slow.fill_triangle(v1, v2, v3, color);
+                fast.fill_triangle(v1, v2, v3, color);
+                let spans = triangle_spans(v1, v2, v3, clip);
+                fill_color_buffer(&mut sprite, 32, spans, color, mode);
+            }
+            for [top_left, bottom_right] in rectangles {
+                slow.fill_rectangle(top_left, bottom_right, color);
+                fast.fill_rectangle(top_left, bottom_right, color);
+                let spans = rectangle_spans(top_left, bottom_right, clip);
+                fill_color_buffer(&mut sprite, 32, spans, color, mode);
+            }
+
+            assert!(fast.pixels() == slow.0.pixels(), "{:?}", mode);
+            let sprite: Vec<RGBA> = sprite.into_iter().map(Pixel::to_floats).collect();
+            assert!(sprite == slow.0.pixels(), "{:?}", mode);
+        }
+    }
+}
@@ -1,8 +1,15 @@
 // inspiration taken from olc::PixelEngine
 
-use crate::color::RGBA;
-use crate::opengl::{create_program, create_shader};
-use crate::render::{VNEFullRenderer, VNERenderer, VNERendererCommit, VNERendererWindow};
+use crate::color::{BlendMode, RGBA};
+use crate::image::{Image, ImageError};
+use crate::opengl::{create_program, create_shader, resize_surface};
+use crate::render::{
+    ClipRect, ClipStack, VNEFullRenderer, VNERenderer, VNERendererCommit, VNERendererWindow,
+};
+use crate::scaling::Viewport;
+use crate::shapes::{self, BufferPixel, Span};
+use crate::target::{self, Layers, RenderTarget};
+use std::path::Path;
 use gl::types::{GLint, GLsizei, GLsizeiptr, GLuint};
 use glutin::config::ConfigTemplateBuilder;
 use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext};
@@ -20,7 +27,7 @@ use winit::dpi::PhysicalSize;
 use winit::event_loop::EventLoop;
 use winit::window::{Window, WindowBuilder};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Pixel {
     r: u8,
     g: u8,
@@ -54,6 +61,20 @@ impl Pixel {
             a: (255.0 * a).min(255.0).max(0.0) as u8,
         }
     }
+
+    pub fn to_floats(self) -> RGBA {
+        RGBA::from_bytes(self.r, self.g, self.b, self.a)
+    }
+}
+
+impl BufferPixel for Pixel {
+    fn from_color(color: RGBA) -> Pixel {
+        Pixel::from_floats(color.r, color.g, color.b, color.a)
+    }
+
+    fn to_color(self) -> RGBA {
+        self.to_floats()
+    }
 }
 
 macro_rules! pixel {
@@ -93,6 +114,39 @@ macro_rules! pixel {
     };
 }
 
+/// A rectangular part of a sprite
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpriteRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteOptions {
+    /// the part of the sprite to draw, `None` draws all of it
+    pub source: Option<SpriteRegion>,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// every sprite pixel becomes a `scale` x `scale` block
+    pub scale: u32,
+    /// pixels with this color are not drawn, alpha is ignored for the comparison
+    pub color_key: Option<RGBA>,
+}
+
+impl Default for SpriteOptions {
+    fn default() -> Self {
+        SpriteOptions {
+            source: None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            scale: 1,
+            color_key: None,
+        }
+    }
+}
+
 pub struct Sprite {
     width: usize,
     height: usize,
@@ -162,6 +216,91 @@ impl Sprite {
         self.height
     }
 
+    pub fn from_image(image: &Image) -> Sprite {
+        let data = image
+            .data()
+            .chunks(4)
+            .map(|p| Pixel::new(p[0], p[1], p[2], p[3]))
+            .collect();
+        Sprite {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            data,
+            tex_id: None,
+        }
+    }
+
+    pub fn to_image(&self) -> Image {
+        let data = self.data.iter().flat_map(|p| [p.r, p.g, p.b, p.a]).collect();
+        Image::new(self.width as u32, self.height as u32, data)
+    }
+
+    /// Loads a PNG, PPM/PGM or BMP image, see [Image::decode]
+    pub fn load(path: impl AsRef<Path>) -> Result<Sprite, ImageError> {
+        Ok(Sprite::from_image(&Image::load(path)?))
+    }
+
+    /// Draws the sprite with its top left corner at `position`. Fully transparent pixels and
+    /// pixels matching the color key are skipped, all others go through
+    /// [VNERenderer::draw_pixel] and are blended according to the blend mode of the renderer.
+    pub fn draw<T: VNERenderer + ?Sized>(
+        &self,
+        renderer: &mut T,
+        position: crate::render::PixelPosition,
+        options: &SpriteOptions,
+    ) {
+        let region = options.source.unwrap_or(SpriteRegion {
+            x: 0,
+            y: 0,
+            width: self.width as u32,
+            height: self.height as u32,
+        });
+        let key = options.color_key.map(|c| {
+            let [r, g, b, _] = c.to_bytes();
+            (r, g, b)
+        });
+        let scale = options.scale.max(1);
+
+        let right = (region.x + region.width).min(self.width as u32);
+        let bottom = (region.y + region.height).min(self.height as u32);
+        if right <= region.x || bottom <= region.y {
+            return;
+        }
+        // walk the destination pixels inside of the clip rectangle and look up their source pixel
+        let target = ClipRect::new(
+            position,
+            crate::render::PixelPosition {
+                x: position.x + ((right - region.x) * scale) as i32 - 1,
+                y: position.y + ((bottom - region.y) * scale) as i32 - 1,
+            },
+        )
+        .intersect(&renderer.clip());
+        if target.is_empty() {
+            return;
+        }
+
+        for ty in target.top_left.y..=target.bottom_right.y {
+            let dy = (ty - position.y) as u32 / scale;
+            let y = match options.flip_vertical {
+                false => region.y + dy,
+                true => bottom - 1 - dy,
+            };
+            for tx in target.top_left.x..=target.bottom_right.x {
+                let dx = (tx - position.x) as u32 / scale;
+                let x = match options.flip_horizontal {
+                    false => region.x + dx,
+                    true => right - 1 - dx,
+                };
+                let pixel = self[(x, y)];
+                if pixel.a == 0 || key == Some((pixel.r, pixel.g, pixel.b)) {
+                    continue;
+                }
+                let target = crate::render::PixelPosition { x: tx, y: ty };
+                renderer.draw_pixel(target, pixel.to_floats());
+            }
+        }
+    }
+
     fn xy_idx(&self, x: u32, y: u32) -> usize {
         self.width * y as usize + x as usize
     }
@@ -292,8 +431,14 @@ pub struct SpriteBased {
     window: Window,
     context: PossiblyCurrentContext,
     surface: Surface<WindowSurface>,
+    /// the screen layer
     sprite: Sprite,
+    layers: Layers,
+    /// all layers merged, only used once there are layers besides the screen
+    frame: Sprite,
     scale: f32,
+    blend_mode: BlendMode,
+    clip: ClipStack,
 }
 
 impl SpriteBased {
@@ -388,16 +533,43 @@ impl SpriteBased {
 
         SpriteBased {
             sprite: Sprite::new(width, height),
+            layers: Layers::new(width as u32, height as u32),
+            frame: Sprite::new(width, height),
             window,
             surface,
             context,
             scale: scale as f32,
+            blend_mode: BlendMode::Replace,
+            clip: ClipStack::new(width as u32, height as u32),
+        }
+    }
+}
+
+impl SpriteBased {
+    /// Writes the spans straight into the sprite, they must lie inside of it
+    fn fill_spans(&mut self, spans: impl Iterator<Item = Span>, color: RGBA) {
+        let (width, mode) = (self.sprite.width as u32, self.blend_mode);
+        match self.layers.active_buffer() {
+            Some(buffer) => shapes::fill_color_buffer(buffer, width, spans, color, mode),
+            None => shapes::fill_color_buffer(&mut self.sprite.data, width, spans, color, mode),
         }
     }
 }
 
+impl SpriteBased {
+    /// the screen merged with all layers
+    fn composited(&self) -> Vec<RGBA> {
+        let screen: Vec<RGBA> = self.sprite.data.iter().map(|p| p.to_floats()).collect();
+        self.layers.composited(&screen)
+    }
+}
+
 impl VNERenderer for SpriteBased {
     fn clear_screen(&mut self, color: RGBA) {
+        if let Some(buffer) = self.layers.active_buffer() {
+            buffer.fill(color.quantized());
+            return;
+        }
         let color = Pixel::from_floats(color.r, color.g, color.b, color.a);
         for idx in 0..self.sprite.width as usize * self.sprite.height as usize {
             self.sprite.data[idx] = color;
@@ -405,15 +577,103 @@ impl VNERenderer for SpriteBased {
     }
 
     fn draw_pixel(&mut self, position: crate::render::PixelPosition, color: RGBA) {
-        let x = position.x;
-        let y = position.y;
-        if x >= self.sprite.width() as u32 || y >= self.sprite.height() as u32 {
+        if !self.clip.current().contains(position) {
+            return;
+        }
+        let x = position.x as u32;
+        let y = position.y as u32;
+
+        let width = self.sprite.width;
+        if let Some(buffer) = self.layers.active_buffer() {
+            let idx = y as usize * width + x as usize;
+            // layers are stored like in the opengl renderer
+            buffer[idx] = color.blend(buffer[idx], self.blend_mode).quantized();
             return;
         }
 
+        let color = match self.blend_mode {
+            BlendMode::Replace => color,
+            mode => color.blend(self.sprite[(x, y)].to_floats(), mode),
+        };
         self.sprite[(x, y)] = Pixel::from_floats(color.r, color.g, color.b, color.a);
     }
 
+    fn capture_frame(&self) -> Image {
+        if !self.layers.is_empty() {
+            let (width, height) = (self.sprite.width as u32, self.sprite.height as u32);
+            return Image::from_colors(width, height, &self.composited());
+        }
+        let data = self
+            .sprite
+            .data
+            .iter()
+            .flat_map(|p| [p.r, p.g, p.b, p.a])
+            .collect();
+        Image::new(
+            self.sprite.width() as u32,
+            self.sprite.height() as u32,
+            data,
+        )
+    }
+
+    fn layer(&self) -> i32 {
+        self.layers.active()
+    }
+
+    fn set_layer(&mut self, layer: i32) {
+        self.layers.set_active(layer);
+    }
+
+    fn draw_target(
+        &mut self,
+        target: &RenderTarget,
+        position: crate::render::PixelPosition,
+        mode: BlendMode,
+    ) {
+        let (width, clip) = (self.sprite.width as u32, self.clip.current());
+        match self.layers.active_buffer() {
+            Some(buffer) => target::draw_into_buffer(buffer, width, clip, target, position, mode),
+            None => target::draw_target(self, target, position, mode),
+        }
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        &self.clip
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        &mut self.clip
+    }
+
+    fn fill_triangle(
+        &mut self,
+        v1: crate::render::PixelPosition,
+        v2: crate::render::PixelPosition,
+        v3: crate::render::PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::triangle_spans(v1, v2, v3, self.clip.current());
+        self.fill_spans(spans, color);
+    }
+
+    fn fill_rectangle(
+        &mut self,
+        top_left: crate::render::PixelPosition,
+        bottom_right: crate::render::PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::rectangle_spans(top_left, bottom_right, self.clip.current());
+        self.fill_spans(spans, color);
+    }
+
     fn set_title(&mut self, title: &str) {
         self.window.set_title(title);
     }
@@ -423,11 +683,23 @@ impl VNERendererWindow for SpriteBased {
     fn window(&mut self) -> &Window {
         &self.window
     }
+
+    fn resize(&mut self, window_width: u32, window_height: u32, viewport: Viewport) {
+        resize_surface(&self.surface, &self.context, window_width, window_height, viewport);
+    }
 }
 
 impl VNERendererCommit for SpriteBased {
     fn commit(&mut self) {
-        self.sprite.make_current();
+        if self.layers.is_empty() {
+            self.sprite.make_current();
+        } else {
+            let frame = self.composited();
+            for (pixel, color) in self.frame.data.iter_mut().zip(frame) {
+                *pixel = Pixel::from_floats(color.r, color.g, color.b, color.a);
+            }
+            self.frame.make_current();
+        }
 
         #[rustfmt::skip]
         let vec: Vec<TexturedPixel> = vec![
@@ -445,6 +717,8 @@ impl VNERendererCommit for SpriteBased {
                 vec.as_ptr().cast(),
                 gl::STREAM_DRAW,
             );
+            // the bars around the viewport
+            gl::Clear(gl::COLOR_BUFFER_BIT);
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, vec.len() as GLint);
         }
 
@@ -0,0 +1,294 @@
+use crate::color::{BlendMode, RGBA, TRANSPARENT};
+use crate::image::Image;
+use crate::render::{ClipRect, ClipStack, PixelPosition, VNERenderer};
+use crate::shapes;
+use std::collections::BTreeMap;
+
+/// An off-screen image that can be drawn like the screen and then drawn onto any renderer with
+/// [VNERenderer::draw_target], e.g. to draw a static background once instead of every frame.
+/// All pixels start out transparent.
+pub struct RenderTarget {
+    width: u32,
+    height: u32,
+    color_buffer: Vec<RGBA>,
+    blend_mode: BlendMode,
+    clip: ClipStack,
+}
+
+impl RenderTarget {
+    pub fn new(width: u32, height: u32) -> RenderTarget {
+        RenderTarget {
+            width,
+            height,
+            color_buffer: vec![TRANSPARENT; width as usize * height as usize],
+            blend_mode: BlendMode::Replace,
+            clip: ClipStack::new(width, height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// the pixel at (x, y), panics if it is out of bounds
+    pub fn pixel(&self, x: u32, y: u32) -> RGBA {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "Index {:?} out of range of {:?}",
+                (x, y),
+                (self.width, self.height)
+            )
+        }
+        self.color_buffer[self.xy_index(x, y)]
+    }
+
+    /// all pixels row by row, starting at the top left
+    pub fn pixels(&self) -> &[RGBA] {
+        &self.color_buffer
+    }
+
+    fn xy_index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// the pixels a renderer with the given clip rectangle gets from the target at `position`,
+    /// `None` if there are none
+    fn area(&self, position: PixelPosition, clip: ClipRect) -> Option<ClipRect> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let area = ClipRect::new(
+            position,
+            PixelPosition {
+                x: position.x + self.width as i32 - 1,
+                y: position.y + self.height as i32 - 1,
+            },
+        )
+        .intersect(&clip);
+        (!area.is_empty()).then_some(area)
+    }
+}
+
+impl VNERenderer for RenderTarget {
+    fn set_title(&mut self, _title: &str) {}
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    fn clear_screen(&mut self, color: RGBA) {
+        self.color_buffer.fill(color.quantized());
+    }
+
+    fn draw_pixel(&mut self, position: PixelPosition, color: RGBA) {
+        if !self.clip.current().contains(position) {
+            return;
+        }
+        let idx = self.xy_index(position.x as u32, position.y as u32);
+        self.color_buffer[idx] = color
+            .blend(self.color_buffer[idx], self.blend_mode)
+            .quantized();
+    }
+
+    fn clip_stack(&self) -> &ClipStack {
+        &self.clip
+    }
+
+    fn clip_stack_mut(&mut self) -> &mut ClipStack {
+        &mut self.clip
+    }
+
+    fn capture_frame(&self) -> Image {
+        Image::from_colors(self.width, self.height, &self.color_buffer)
+    }
+
+    fn draw_target(&mut self, target: &RenderTarget, position: PixelPosition, mode: BlendMode) {
+        let clip = self.clip.current();
+        draw_into_buffer(
+            &mut self.color_buffer,
+            self.width,
+            clip,
+            target,
+            position,
+            mode,
+        );
+    }
+
+    fn fill_triangle(
+        &mut self,
+        v1: PixelPosition,
+        v2: PixelPosition,
+        v3: PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::triangle_spans(v1, v2, v3, self.clip.current());
+        shapes::fill_color_buffer(
+            &mut self.color_buffer,
+            self.width,
+            spans,
+            color,
+            self.blend_mode,
+        );
+    }
+
+    fn fill_rectangle(
+        &mut self,
+        top_left: PixelPosition,
+        bottom_right: PixelPosition,
+        color: RGBA,
+    ) {
+        let spans = shapes::rectangle_spans(top_left, bottom_right, self.clip.current());
+        shapes::fill_color_buffer(
+            &mut self.color_buffer,
+            self.width,
+            spans,
+            color,
+            self.blend_mode,
+        );
+    }
+}
+
+/// [VNERenderer::draw_target] for any renderer, pixel by pixel
+pub(crate) fn draw_target<T: VNERenderer + ?Sized>(
+    renderer: &mut T,
+    target: &RenderTarget,
+    position: PixelPosition,
+    mode: BlendMode,
+) {
+    let area = match target.area(position, renderer.clip()) {
+        Some(area) => area,
+        None => return,
+    };
+    let previous = renderer.blend_mode();
+    renderer.set_blend_mode(mode);
+    for y in area.top_left.y..=area.bottom_right.y {
+        for x in area.top_left.x..=area.bottom_right.x {
+            let color = target.pixel((x - position.x) as u32, (y - position.y) as u32);
+            renderer.draw_pixel(PixelPosition { x, y }, color);
+        }
+    }
+    renderer.set_blend_mode(previous);
+}
+
+/// [VNERenderer::draw_target] for renderers with an RGBA buffer `width` pixels wide, row by row
+pub(crate) fn draw_into_buffer(
+    buffer: &mut [RGBA],
+    width: u32,
+    clip: ClipRect,
+    target: &RenderTarget,
+    position: PixelPosition,
+    mode: BlendMode,
+) {
+    let area = match target.area(position, clip) {
+        Some(area) => area,
+        None => return,
+    };
+    let (left, right) = (area.top_left.x, area.bottom_right.x);
+    for y in area.top_left.y..=area.bottom_right.y {
+        let row = y as usize * width as usize;
+        let pixels = &mut buffer[row + left as usize..=row + right as usize];
+        let source_row = target.xy_index((left - position.x) as u32, (y - position.y) as u32);
+        let source = &target.color_buffer[source_row..source_row + pixels.len()];
+        match mode {
+            // the target is already quantized
+            BlendMode::Replace => pixels.copy_from_slice(source),
+            mode => {
+                for (pixel, color) in pixels.iter_mut().zip(source) {
+                    *pixel = color.blend(*pixel, mode).quantized();
+                }
+            }
+        }
+    }
+}
+
+/// The layers of a renderer besides the screen, which is layer 0. Drawing goes to the active
+/// layer and the layers are merged in z-order when the frame is shown.
+pub(crate) struct Layers {
+    width: u32,
+    height: u32,
+    targets: BTreeMap<i32, RenderTarget>,
+    active: i32,
+}
+
+impl Layers {
+    pub(crate) fn new(width: u32, height: u32) -> Layers {
+        Layers {
+            width,
+            height,
+            targets: BTreeMap::new(),
+            active: 0,
+        }
+    }
+
+    pub(crate) fn active(&self) -> i32 {
+        self.active
+    }
+
+    /// Makes `layer` the active one, creating it transparent if it is new
+    pub(crate) fn set_active(&mut self, layer: i32) {
+        if layer != 0 {
+            let (width, height) = (self.width, self.height);
+            self.targets
+                .entry(layer)
+                .or_insert_with(|| RenderTarget::new(width, height));
+        }
+        self.active = layer;
+    }
+
+    /// true as long as only the screen was drawn to
+    pub(crate) fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// the pixels of the active layer, `None` for the screen
+    pub(crate) fn active_buffer(&mut self) -> Option<&mut Vec<RGBA>> {
+        self.targets
+            .get_mut(&self.active)
+            .map(|target| &mut target.color_buffer)
+    }
+
+    /// the pixels of the active layer, `screen` if that is the active one
+    pub(crate) fn buffer<'a>(&'a mut self, screen: &'a mut Vec<RGBA>) -> &'a mut Vec<RGBA> {
+        match self.targets.get_mut(&self.active) {
+            Some(target) => &mut target.color_buffer,
+            None => screen,
+        }
+    }
+
+    /// Merges `screen` and all layers into `frame`, from the lowest to the highest with
+    /// [BlendMode::Alpha]. The lowest layer is copied as is.
+    pub(crate) fn composite(&self, screen: &[RGBA], frame: &mut Vec<RGBA>) {
+        let below = self
+            .targets
+            .range(..0)
+            .map(|(_, target)| &target.color_buffer[..]);
+        let above = self
+            .targets
+            .range(1..)
+            .map(|(_, target)| &target.color_buffer[..]);
+        let mut layers = below.chain(Some(screen)).chain(above);
+
+        frame.clear();
+        frame.extend_from_slice(layers.next().expect("Failed to find the screen layer!"));
+        for layer in layers {
+            for (pixel, color) in frame.iter_mut().zip(layer) {
+                *pixel = color.blend(*pixel, BlendMode::Alpha).quantized();
+            }
+        }
+    }
+
+    /// [Layers::composite] into a new buffer, e.g. to capture the frame
+    pub(crate) fn composited(&self, screen: &[RGBA]) -> Vec<RGBA> {
+        let mut frame = Vec::with_capacity(screen.len());
+        self.composite(screen, &mut frame);
+        frame
+    }
+}
@@ -0,0 +1,644 @@
+use crate::color::{BlendMode, BLACK, RGBA, WHITE};
+use crate::font::Font;
+use crate::input::Input;
+use crate::render::{ClipRect, PixelPosition, VNERenderer};
+use crate::Keycode;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use winit::event::MouseButton;
+
+/// Colors and sizes of the widgets
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Style {
+    /// font scale
+    pub scale: u32,
+    /// space between the border of a widget and its content
+    pub padding: i32,
+    /// space between two widgets
+    pub spacing: i32,
+    pub text: RGBA,
+    /// behind all widgets, keeps them readable on top of a busy scene
+    pub background: RGBA,
+    pub widget: RGBA,
+    pub hovered: RGBA,
+    /// filled part of sliders, check marks, held buttons and the border of edited inputs
+    pub accent: RGBA,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            scale: 1,
+            padding: 3,
+            spacing: 2,
+            text: WHITE,
+            background: RGBA { a: 0.6, ..BLACK },
+            widget: RGBA::from_bytes(64, 64, 72, 255),
+            hovered: RGBA::from_bytes(90, 90, 102, 255),
+            accent: RGBA::from_bytes(70, 120, 220, 255),
+        }
+    }
+}
+
+/// keys numeric inputs react to
+const EDIT_KEYS: [Keycode; 4] = [
+    Keycode::Back,
+    Keycode::Return,
+    Keycode::NumpadEnter,
+    Keycode::Escape,
+];
+
+/// Input that arrived since the widgets were drawn last
+#[derive(Debug, Default)]
+struct PendingInput {
+    pressed: bool,
+    released: bool,
+    keys: Vec<Keycode>,
+    text: String,
+    scroll: f32,
+}
+
+/// What the widgets remember between frames, keep one per UI
+#[derive(Debug, Default)]
+pub struct UiState {
+    pub style: Style,
+    /// the widget the mouse button went down on
+    active: Option<u64>,
+    /// the numeric input being edited and its text
+    editing: Option<(u64, String)>,
+    /// open panels
+    panels: HashMap<u64, bool>,
+    pending: PendingInput,
+    /// the last input frame that was collected
+    collected: Option<u64>,
+    /// covered by the widgets of the last frame
+    area: Option<ClipRect>,
+}
+
+impl UiState {
+    pub fn new(style: Style) -> UiState {
+        UiState {
+            style,
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the presses, releases, scrolling and text of the current input frame for the next
+    /// [Ui], every frame is only collected once. [Ui::new] does this on its own. With a
+    /// [crate::engine::FixedTimestep] the first update of a frame consumes these, so call this
+    /// from the update as well to not miss any.
+    pub fn collect_input(&mut self, input: &Input) {
+        if self.collected == Some(input.frame()) {
+            return;
+        }
+        self.collected = Some(input.frame());
+        let pending = &mut self.pending;
+        pending.pressed |= input.button_pressed(MouseButton::Left);
+        pending.released |= input.button_released(MouseButton::Left);
+        pending
+            .keys
+            .extend(EDIT_KEYS.into_iter().filter(|key| input.key_pressed(*key)));
+        pending.text.push_str(input.text());
+        pending.scroll += input.scroll_lines().1;
+    }
+
+    /// true if the widgets of the last frame cover `position`, e.g. to ignore clicks and
+    /// scrolling meant for them
+    pub fn contains(&self, position: (f64, f64)) -> bool {
+        self.area
+            .is_some_and(|area| area.contains(to_pixel(position)))
+    }
+}
+
+fn to_pixel((x, y): (f64, f64)) -> PixelPosition {
+    PixelPosition {
+        x: x.floor() as i32,
+        y: y.floor() as i32,
+    }
+}
+
+/// The widgets of a single frame, placed below each other in a column. Every widget draws itself
+/// right away and returns what the user did with it. Widgets are told apart by their label, so
+/// labels have to be unique within a panel.
+pub struct Ui<'a, R: VNERenderer + ?Sized> {
+    state: &'a mut UiState,
+    renderer: &'a mut R,
+    style: Style,
+    cursor: Option<PixelPosition>,
+    button_down: bool,
+    input: PendingInput,
+    /// top left corner and width of the whole column
+    origin: PixelPosition,
+    column_width: i32,
+    /// left edge and width of the current panel
+    left: i32,
+    width: i32,
+    /// top of the next widget
+    y: i32,
+    /// id of the current panel
+    id: u64,
+    blend_mode: BlendMode,
+}
+
+impl<'a, R: VNERenderer + ?Sized> Ui<'a, R> {
+    /// Starts a column of widgets `width` pixels wide with its top left corner at `position`
+    pub fn new(
+        state: &'a mut UiState,
+        input: &Input,
+        renderer: &'a mut R,
+        position: PixelPosition,
+        width: u32,
+    ) -> Ui<'a, R> {
+        state.collect_input(input);
+        let blend_mode = renderer.blend_mode();
+        renderer.set_blend_mode(BlendMode::Alpha);
+        Ui {
+            style: state.style,
+            input: std::mem::take(&mut state.pending),
+            state,
+            renderer,
+            cursor: input.cursor_position().map(to_pixel),
+            button_down: input.button_down(MouseButton::Left),
+            origin: position,
+            column_width: width as i32,
+            left: position.x,
+            width: width as i32,
+            y: position.y,
+            id: 0,
+            blend_mode,
+        }
+    }
+
+    pub fn renderer(&mut self) -> &mut R {
+        self.renderer
+    }
+
+    /// the top of the next widget
+    pub fn bottom(&self) -> i32 {
+        self.y
+    }
+
+    /// a line of text
+    pub fn label(&mut self, text: &str) {
+        let rect = self.row();
+        self.draw_text(rect, text, false);
+    }
+
+    /// true if the button was clicked
+    pub fn button(&mut self, label: &str) -> bool {
+        let rect = self.row();
+        let id = self.id(label);
+        let (hovered, clicked) = self.interact(id, rect);
+        let color = match self.state.active == Some(id) {
+            true => self.style.accent,
+            false => self.widget_color(hovered),
+        };
+        self.fill(rect, color);
+        self.draw_text(rect, label, true);
+        clicked
+    }
+
+    /// Toggles `value` when clicked, true if it changed
+    pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+        let rect = self.row();
+        let id = self.id(label);
+        let (hovered, clicked) = self.interact(id, rect);
+        if clicked {
+            *value = !*value;
+        }
+
+        let padding = self.style.padding;
+        let size = rect.bottom_right.y - rect.top_left.y - 2 * padding;
+        let top_left = PixelPosition {
+            x: rect.top_left.x + padding,
+            y: rect.top_left.y + padding,
+        };
+        let bottom_right = PixelPosition {
+            x: top_left.x + size,
+            y: top_left.y + size,
+        };
+        let color = self.widget_color(hovered);
+        self.renderer.fill_rectangle(top_left, bottom_right, color);
+        if *value {
+            let inset = PixelPosition { x: 2, y: 2 };
+            let accent = self.style.accent;
+            self.renderer
+                .fill_rectangle(top_left + inset, bottom_right - inset, accent);
+        }
+        let text_area = ClipRect {
+            top_left: PixelPosition {
+                x: bottom_right.x + 1,
+                ..rect.top_left
+            },
+            ..rect
+        };
+        self.draw_text(text_area, label, false);
+        clicked
+    }
+
+    /// Sets `value` to the position the slider is dragged to within `range`, true if it changed
+    pub fn slider(&mut self, label: &str, value: &mut f64, range: RangeInclusive<f64>) -> bool {
+        let rect = self.row();
+        let id = self.id(label);
+        let (hovered, _) = self.interact(id, rect);
+        let (min, max) = (*range.start(), *range.end());
+        let span = (rect.bottom_right.x - rect.top_left.x) as f64;
+
+        let mut changed = false;
+        if let (Some(cursor), Some(active)) = (self.cursor, self.state.active) {
+            if active == id && self.button_down && span > 0.0 {
+                let t = ((cursor.x - rect.top_left.x) as f64 / span).clamp(0.0, 1.0);
+                let dragged = min + (max - min) * t;
+                changed = dragged != *value;
+                *value = dragged;
+            }
+        }
+
+        let color = self.widget_color(hovered);
+        self.fill(rect, color);
+        if max > min {
+            let t = ((*value - min) / (max - min)).clamp(0.0, 1.0);
+            let filled = PixelPosition {
+                x: rect.top_left.x + (span * t).round() as i32,
+                ..rect.bottom_right
+            };
+            let accent = self.style.accent;
+            self.renderer.fill_rectangle(rect.top_left, filled, accent);
+        }
+        // enough decimals to tell the steps of a pixel apart
+        let decimals = (2.0 - (max - min).abs().log10().floor()).clamp(0.0, 10.0) as usize;
+        let text = format!("{}: {:.*}", label, decimals, value);
+        self.draw_text(rect, &text, true);
+        changed
+    }
+
+    /// A number that can be typed in after clicking it and is applied with enter, escape
+    /// discards the change. Scrolling over it changes it by `step` per line. True if it changed.
+    pub fn number(&mut self, label: &str, value: &mut f64, step: f64) -> bool {
+        let rect = self.row();
+        let id = self.id(label);
+        let (hovered, clicked) = self.interact(id, rect);
+
+        let mut changed = false;
+        let clicked_elsewhere = self.input.pressed && !self.hovers(rect);
+        match &mut self.state.editing {
+            Some((editing, text)) if *editing == id => {
+                let typed = self.input.text.chars();
+                text.extend(typed.filter(|c| c.is_ascii_digit() || "+-.eE".contains(*c)));
+                let mut done = clicked_elsewhere;
+                for key in self.input.keys.drain(..) {
+                    match key {
+                        Keycode::Back => {
+                            text.pop();
+                        }
+                        Keycode::Return | Keycode::NumpadEnter => {
+                            match text.parse::<f64>() {
+                                Ok(typed) if typed.is_finite() => {
+                                    changed = typed != *value;
+                                    *value = typed;
+                                }
+                                _ => {}
+                            }
+                            done = true;
+                        }
+                        // escape
+                        _ => done = true,
+                    }
+                }
+                self.input.text.clear();
+                if done {
+                    self.state.editing = None;
+                }
+            }
+            editing if clicked => *editing = Some((id, value.to_string())),
+            _ if hovered && self.input.scroll != 0.0 => {
+                *value += step * self.input.scroll as f64;
+                self.input.scroll = 0.0;
+                changed = true;
+            }
+            _ => {}
+        }
+
+        let color = self.widget_color(hovered);
+        self.fill(rect, color);
+        let text = match &self.state.editing {
+            Some((editing, text)) if *editing == id => {
+                let accent = self.style.accent;
+                self.renderer
+                    .draw_rectangle(rect.top_left, rect.bottom_right, accent);
+                format!("{}: {}_", label, text)
+            }
+            _ => format!("{}: {}", label, value),
+        };
+        self.draw_text(rect, &text, false);
+        changed
+    }
+
+    /// A header that opens and closes the panel when clicked, `contents` adds the widgets of an
+    /// open panel, indented below the header. Panels start closed, true if it is open.
+    pub fn panel(&mut self, title: &str, contents: impl FnOnce(&mut Self)) -> bool {
+        let rect = self.row();
+        let id = self.id(title);
+        let (hovered, clicked) = self.interact(id, rect);
+        let open = self.state.panels.entry(id).or_insert(false);
+        if clicked {
+            *open = !*open;
+        }
+        let open = *open;
+
+        let color = self.widget_color(hovered);
+        self.fill(rect, color);
+        let marker = if open { '-' } else { '+' };
+        self.draw_text(rect, &format!("{} {}", marker, title), false);
+
+        if open {
+            let (parent, left, width) = (self.id, self.left, self.width);
+            let indent = self.style.padding * 2;
+            self.id = id;
+            self.left += indent;
+            self.width -= indent;
+            contents(self);
+            self.id = parent;
+            self.left = left;
+            self.width = width;
+        }
+        open
+    }
+
+    /// Takes the space of the next widget, with the background drawn behind it
+    fn row(&mut self) -> ClipRect {
+        let style = self.style;
+        let height = Font::builtin().height() as i32 * style.scale as i32 + 2 * style.padding;
+        let background = ClipRect::new(
+            PixelPosition {
+                x: self.origin.x,
+                y: self.y,
+            },
+            PixelPosition {
+                x: self.origin.x + self.column_width - 1,
+                y: self.y + height + style.spacing - 1,
+            },
+        );
+        self.fill(background, style.background);
+        let rect = ClipRect::new(
+            PixelPosition {
+                x: self.left,
+                y: self.y,
+            },
+            PixelPosition {
+                x: self.left + self.width - 1,
+                y: self.y + height - 1,
+            },
+        );
+        self.y += height + style.spacing;
+        rect
+    }
+
+    fn id(&self, label: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.id, label).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hovers(&self, rect: ClipRect) -> bool {
+        self.cursor.is_some_and(|cursor| rect.contains(cursor))
+    }
+
+    /// (hovered, clicked), a click has to press and release the button on the same widget
+    fn interact(&mut self, id: u64, rect: ClipRect) -> (bool, bool) {
+        let over = self.hovers(rect);
+        let hovered = over && self.state.active.is_none_or(|active| active == id);
+        if hovered && self.input.pressed {
+            self.state.active = Some(id);
+        }
+        let clicked = over && self.input.released && self.state.active == Some(id);
+        (hovered, clicked)
+    }
+
+    fn widget_color(&self, hovered: bool) -> RGBA {
+        match hovered {
+            true => self.style.hovered,
+            false => self.style.widget,
+        }
+    }
+
+    fn fill(&mut self, rect: ClipRect, color: RGBA) {
+        self.renderer
+            .fill_rectangle(rect.top_left, rect.bottom_right, color);
+    }
+
+    /// draws one line of text inside of `rect`, cut off at its border
+    fn draw_text(&mut self, rect: ClipRect, text: &str, centered: bool) {
+        let style = self.style;
+        let (width, _) = Font::builtin().text_size(text, style.scale);
+        let x = match centered {
+            true => (rect.top_left.x + rect.bottom_right.x + 1 - width as i32) / 2,
+            false => rect.top_left.x + style.padding,
+        };
+        let position = PixelPosition {
+            x: x.max(rect.top_left.x + style.padding),
+            y: rect.top_left.y + style.padding,
+        };
+        self.renderer.push_clip(rect);
+        self.renderer
+            .draw_text(position, text, style.text, style.scale);
+        self.renderer.pop_clip();
+    }
+}
+
+impl<'a, R: VNERenderer + ?Sized> Drop for Ui<'a, R> {
+    fn drop(&mut self) {
+        if !self.button_down {
+            self.state.active = None;
+        }
+        self.state.area = (self.y > self.origin.y).then(|| {
+            ClipRect::new(
+                self.origin,
+                PixelPosition {
+                    x: self.origin.x + self.column_width - 1,
+                    y: self.y - 1,
+                },
+            )
+        });
+        self.renderer.set_blend_mode(self.blend_mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessRenderer;
+    use crate::input::InputEvent;
+
+    /// Widgets on a headless renderer, driven by synthetic input
+    struct Harness {
+        renderer: HeadlessRenderer,
+        state: UiState,
+        input: Input,
+    }
+
+    impl Harness {
+        fn new() -> Harness {
+            Harness {
+                renderer: HeadlessRenderer::new(100, 100),
+                state: UiState::default(),
+                input: Input::default(),
+            }
+        }
+
+        /// Draws the widgets with the input so far, then starts the next input frame
+        fn frame<T>(&mut self, widgets: impl FnOnce(&mut Ui<HeadlessRenderer>) -> T) -> T {
+            let position = PixelPosition { x: 0, y: 0 };
+            let mut ui = Ui::new(
+                &mut self.state,
+                &self.input,
+                &mut self.renderer,
+                position,
+                100,
+            );
+            let result = widgets(&mut ui);
+            drop(ui);
+            self.input.end_frame();
+            result
+        }
+
+        /// Moves the cursor to `x` in the widget of row `index`
+        fn move_to(&mut self, index: i32, x: f64) {
+            let style = self.state.style;
+            let height = Font::builtin().height() as i32 * style.scale as i32 + 2 * style.padding;
+            let y = index * (height + style.spacing) + height / 2;
+            self.input.move_cursor(Some((x, y as f64)));
+        }
+    }
+
+    #[test]
+    fn clicks_press_and_release_on_the_same_widget() {
+        let mut harness = Harness::new();
+        let buttons = |ui: &mut Ui<HeadlessRenderer>| [ui.button("a"), ui.button("b")];
+
+        harness.move_to(0, 50.0);
+        harness.input.press_button(MouseButton::Left);
+        assert_eq!(harness.frame(buttons), [false, false]);
+        harness.input.release_button(MouseButton::Left);
+        assert_eq!(harness.frame(buttons), [true, false]);
+
+        // pressed on a, released on b
+        harness.input.press_button(MouseButton::Left);
+        assert_eq!(harness.frame(buttons), [false, false]);
+        harness.move_to(1, 50.0);
+        assert_eq!(harness.frame(buttons), [false, false]);
+        harness.input.release_button(MouseButton::Left);
+        assert_eq!(harness.frame(buttons), [false, false]);
+
+        // pressed elsewhere, released on b
+        harness.move_to(5, 50.0);
+        harness.input.press_button(MouseButton::Left);
+        harness.frame(buttons);
+        harness.move_to(1, 50.0);
+        harness.input.release_button(MouseButton::Left);
+        assert_eq!(harness.frame(buttons), [false, false]);
+
+        harness.input.press_button(MouseButton::Left);
+        harness.input.release_button(MouseButton::Left);
+        assert_eq!(harness.frame(buttons), [false, true]);
+
+        let mut checked = false;
+        harness.move_to(0, 10.0);
+        harness.input.press_button(MouseButton::Left);
+        harness.input.release_button(MouseButton::Left);
+        assert!(harness.frame(|ui| ui.checkbox("check", &mut checked)));
+        assert!(checked);
+    }
+
+    #[test]
+    fn sliders_follow_the_drag() {
+        let mut harness = Harness::new();
+        let mut value = 5.0;
+        let mut slider = |harness: &mut Harness| {
+            let changed = harness.frame(|ui| ui.slider("s", &mut value, 0.0..=9.9));
+            (changed, value)
+        };
+
+        // the widget spans 99 pixels after its left edge
+        harness.move_to(0, 0.0);
+        harness.input.press_button(MouseButton::Left);
+        assert_eq!(slider(&mut harness), (true, 0.0));
+        harness.move_to(0, 30.0);
+        assert_eq!(slider(&mut harness), (true, 3.0));
+        // dragged beyond the widget, the value stays in range
+        harness.move_to(3, 250.0);
+        assert_eq!(slider(&mut harness), (true, 9.9));
+        assert_eq!(slider(&mut harness), (false, 9.9));
+        harness.input.release_button(MouseButton::Left);
+        slider(&mut harness);
+        harness.move_to(0, 50.0);
+        assert_eq!(slider(&mut harness), (false, 9.9));
+
+        // a drag that starts elsewhere does not move it
+        harness.move_to(3, 50.0);
+        harness.input.press_button(MouseButton::Left);
+        slider(&mut harness);
+        harness.move_to(0, 10.0);
+        assert_eq!(slider(&mut harness), (false, 9.9));
+    }
+
+    #[test]
+    fn numbers_are_applied_with_enter_only() {
+        let mut harness = Harness::new();
+        let mut value = 2.0;
+        let mut number = |harness: &mut Harness| {
+            let changed = harness.frame(|ui| ui.number("n", &mut value, 0.5));
+            (changed, value)
+        };
+        let click = |harness: &mut Harness, number: &mut dyn FnMut(&mut Harness) -> (bool, f64)| {
+            harness.input.press_button(MouseButton::Left);
+            number(harness);
+            harness.input.release_button(MouseButton::Left);
+            number(harness);
+        };
+
+        // editing starts with the current value
+        harness.move_to(0, 50.0);
+        click(&mut harness, &mut number);
+        harness.input.type_text("5x");
+        assert_eq!(number(&mut harness), (false, 2.0));
+        harness.input.press_key(Keycode::Return);
+        assert_eq!(number(&mut harness), (true, 25.0));
+        harness.input.release_key(Keycode::Return);
+
+        click(&mut harness, &mut number);
+        harness.input.press_key(Keycode::Back);
+        harness.input.press_key(Keycode::Back);
+        harness.input.type_text("1.5");
+        number(&mut harness);
+        harness.input.release_key(Keycode::Back);
+        harness.input.press_key(Keycode::Escape);
+        assert_eq!(number(&mut harness), (false, 25.0));
+        harness.input.release_key(Keycode::Escape);
+
+        // a click elsewhere discards the text, so does enter on something that is not a number
+        click(&mut harness, &mut number);
+        harness.input.type_text("7");
+        harness.move_to(4, 50.0);
+        harness.input.press_button(MouseButton::Left);
+        assert_eq!(number(&mut harness), (false, 25.0));
+        harness.input.release_button(MouseButton::Left);
+        number(&mut harness);
+        harness.move_to(0, 50.0);
+        click(&mut harness, &mut number);
+        harness.input.type_text("e999");
+        harness.input.press_key(Keycode::NumpadEnter);
+        assert_eq!(number(&mut harness), (false, 25.0));
+        harness.input.release_key(Keycode::NumpadEnter);
+
+        // typing and enter after editing ended change nothing
+        harness.input.type_text("3");
+        harness.input.press_key(Keycode::Return);
+        assert_eq!(number(&mut harness), (false, 25.0));
+
+        harness.input.apply(&InputEvent::ScrollLines(0.0, -2.0));
+        assert_eq!(number(&mut harness), (true, 24.0));
+    }
+}
@@ -0,0 +1,465 @@
+/////////
+// CLI //
+/////////
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::process::{Command as Process, Stdio};
+use std::{fs, io};
+use voided_world::geometry2d::{Point, Rectangle};
+use voided_world::graphviz::GVGraph;
+use voided_world::r_tree::{RTree, RTreeError};
+
+mod input;
+
+pub type Index = RTree<f64, u64>;
+
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+pub const USAGE: &str = "\
+usage:
+    voided_world build <input|-> <index> [--format csv|geojson] [--max N] [--min N]
+    voided_world query <index> area <x0> <y0> <x1> <y1>
+    voided_world query <index> point <x> <y>
+    voided_world query <index> knn <x> <y> <k>
+    voided_world stats <index>
+    voided_world dot <index> [output]
+    voided_world svg <index> [output]
+
+csv input rows are `id,x0,y0,x1,y1`, results are written as one json object per line";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Csv,
+    GeoJson,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Area(Rectangle<f64>),
+    Point(Point<f64>),
+    Nearest(Point<f64>, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Build {
+        input: String,
+        index: String,
+        format: InputFormat,
+        max: usize,
+        min: usize,
+    },
+    Query {
+        index: String,
+        query: Query,
+    },
+    Stats {
+        index: String,
+    },
+    Dot {
+        index: String,
+        output: Option<String>,
+    },
+    Svg {
+        index: String,
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Input(String),
+    Io(io::Error),
+    Json(serde_json::Error),
+    Tree(RTreeError),
+    IncompatibleIndex(u32),
+    Graphviz(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    tree: Index,
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Input(message) => write!(f, "invalid input: {}", message),
+            CliError::Io(e) => write!(f, "io error: {}", e),
+            CliError::Json(e) => write!(f, "json error: {}", e),
+            CliError::Tree(e) => write!(f, "invalid tree configuration: {:?}", e),
+            CliError::IncompatibleIndex(version) => write!(
+                f,
+                "index file has version {}, expected {}",
+                version, INDEX_FORMAT_VERSION
+            ),
+            CliError::Graphviz(message) => write!(f, "graphviz error: {}", message),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(value: io::Error) -> Self {
+        CliError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(value: serde_json::Error) -> Self {
+        CliError::Json(value)
+    }
+}
+
+/////////////
+// PARSING //
+/////////////
+
+impl Command {
+    /// Parses the arguments following the program name
+    pub fn parse(args: &[String]) -> Result<Self, CliError> {
+        let (command, args) = match args.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => return Err(usage("missing command")),
+        };
+
+        match command {
+            "build" => Self::parse_build(args),
+            "query" => {
+                let (index, kind, values) = match args {
+                    [index, kind, values @ ..] => (index.clone(), kind.as_str(), values),
+                    _ => return Err(usage("query needs an index and a query type")),
+                };
+                let query = match (kind, values) {
+                    ("area", [x0, y0, x1, y1]) => Query::Area(Rectangle {
+                        low: Point::new(number(x0)?, number(y0)?),
+                        high: Point::new(number(x1)?, number(y1)?),
+                    }),
+                    ("point", [x, y]) => Query::Point(Point::new(number(x)?, number(y)?)),
+                    ("knn", [x, y, k]) => {
+                        Query::Nearest(Point::new(number(x)?, number(y)?), count(k)?)
+                    }
+                    _ => return Err(usage(&format!("invalid {} query", kind))),
+                };
+                Ok(Command::Query { index, query })
+            }
+            "stats" => match args {
+                [index] => Ok(Command::Stats {
+                    index: index.clone(),
+                }),
+                _ => Err(usage("stats needs exactly one index")),
+            },
+            "dot" | "svg" => {
+                let (index, output) = match args {
+                    [index] => (index.clone(), None),
+                    [index, output] => (index.clone(), Some(output.clone())),
+                    _ => return Err(usage(&format!("{} needs an index", command))),
+                };
+                if command == "dot" {
+                    Ok(Command::Dot { index, output })
+                } else {
+                    Ok(Command::Svg { index, output })
+                }
+            }
+            _ => Err(usage(&format!("unknown command '{}'", command))),
+        }
+    }
+
+    fn parse_build(args: &[String]) -> Result<Self, CliError> {
+        let mut positional = vec![];
+        let mut format = None;
+        let mut max = 16;
+        let mut min = 4;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| usage(&format!("{} needs a value", arg)))
+            };
+            match arg.as_str() {
+                "--format" => {
+                    format = Some(match value()?.as_str() {
+                        "csv" => InputFormat::Csv,
+                        "geojson" | "json" => InputFormat::GeoJson,
+                        other => return Err(usage(&format!("unknown format '{}'", other))),
+                    })
+                }
+                "--max" => max = count(value()?)?,
+                "--min" => min = count(value()?)?,
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let (input, index) = match positional.as_slice() {
+            [input, index] => (input.clone(), index.clone()),
+            _ => return Err(usage("build needs an input and an index")),
+        };
+        let format = format.unwrap_or(if input.ends_with(".geojson") || input.ends_with(".json") {
+            InputFormat::GeoJson
+        } else {
+            InputFormat::Csv
+        });
+
+        Ok(Command::Build {
+            input,
+            index,
+            format,
+            max,
+            min,
+        })
+    }
+}
+
+fn usage(message: &str) -> CliError {
+    CliError::Usage(message.to_string())
+}
+
+fn number(value: &str) -> Result<f64, CliError> {
+    value
+        .parse()
+        .map_err(|_| usage(&format!("'{}' is not a number", value)))
+}
+
+fn count(value: &str) -> Result<usize, CliError> {
+    value
+        .parse()
+        .map_err(|_| usage(&format!("'{}' is not a non-negative integer", value)))
+}
+
+///////////////
+// EXECUTION //
+///////////////
+
+impl Command {
+    pub fn run(&self, out: &mut impl Write) -> Result<(), CliError> {
+        match self {
+            Command::Build {
+                input,
+                index,
+                format,
+                max,
+                min,
+            } => {
+                let source = if input == "-" {
+                    let mut source = String::new();
+                    io::stdin().read_to_string(&mut source)?;
+                    source
+                } else {
+                    fs::read_to_string(input)?
+                };
+                let records = input::read_records(&source, *format)?;
+                let objects = records.len();
+                let tree = input::build_index(records, *max, *min)?;
+                save_index(index, tree)?;
+                writeln!(out, "{}", json!({ "index": index, "objects": objects }))?;
+            }
+            Command::Query { index, query } => {
+                let tree = load_index(index)?;
+                match query {
+                    Query::Area(area) => {
+                        for id in tree.search_area(area) {
+                            writeln!(out, "{}", json!({ "id": id }))?;
+                        }
+                    }
+                    Query::Point(point) => {
+                        for id in tree.search_point(point) {
+                            writeln!(out, "{}", json!({ "id": id }))?;
+                        }
+                    }
+                    Query::Nearest(point, k) => {
+                        for (id, distance) in tree.search_nearest(point, *k) {
+                            writeln!(out, "{}", json!({ "id": id, "distance": distance.sqrt() }))?;
+                        }
+                    }
+                }
+            }
+            Command::Stats { index } => {
+                let stats = load_index(index)?.stats();
+                writeln!(out, "{}", serde_json::to_string(&stats)?)?;
+            }
+            Command::Dot { index, output } => {
+                let graph: GVGraph<String> = load_index(index)?.into();
+                write_output(output, format!("{}\n", graph).as_bytes(), out)?;
+            }
+            Command::Svg { index, output } => {
+                let graph: GVGraph<String> = load_index(index)?.into();
+                let svg = render_svg(&graph.to_string())?;
+                write_output(output, &svg, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn save_index(path: &str, tree: Index) -> Result<(), CliError> {
+    let file = IndexFile {
+        version: INDEX_FORMAT_VERSION,
+        tree,
+    };
+    let writer = io::BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer(writer, &file)?;
+    Ok(())
+}
+
+fn load_index(path: &str) -> Result<Index, CliError> {
+    let reader = io::BufReader::new(fs::File::open(path)?);
+    let file: IndexFile = serde_json::from_reader(reader)?;
+    if file.version != INDEX_FORMAT_VERSION {
+        return Err(CliError::IncompatibleIndex(file.version));
+    }
+    Ok(file.tree)
+}
+
+fn write_output(path: &Option<String>, data: &[u8], out: &mut impl Write) -> Result<(), CliError> {
+    match path {
+        None => out.write_all(data)?,
+        Some(path) => fs::write(path, data)?,
+    }
+    Ok(())
+}
+
+/// Renders a graph through the graphviz `dot` executable, which has to be on the `PATH`
+fn render_svg(dot: &str) -> Result<Vec<u8>, CliError> {
+    let mut child = Process::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CliError::Graphviz(format!("could not run dot: {}", e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(dot.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(CliError::Graphviz(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A file in the temp directory that is removed again at the end of the test
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let name = format!("voided_world_{}_{}", std::process::id(), name);
+            TempFile(std::env::temp_dir().join(name))
+        }
+
+        fn path(&self) -> String {
+            self.0.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn run(args: &[&str]) -> Result<Vec<String>, CliError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut out = vec![];
+        Command::parse(&args)?.run(&mut out)?;
+        let out = String::from_utf8(out).unwrap();
+        Ok(out.lines().map(str::to_string).collect())
+    }
+
+    fn sorted_ids(lines: &[String]) -> Vec<u64> {
+        let mut ids: Vec<u64> = lines
+            .iter()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn build_and_query_round_trip() {
+        let input = TempFile::new("round_trip.csv");
+        let index = TempFile::new("round_trip.index");
+        let mut csv = String::from("id,x0,y0,x1,y1\n");
+        for id in 0..100 {
+            let (x, y) = ((id % 10) as f64 * 10.0, (id / 10) as f64 * 10.0);
+            csv += &format!("{},{},{},{},{}\n", id, x, y, x + 5.0, y + 5.0);
+        }
+        fs::write(&input.0, csv).unwrap();
+
+        let built = run(&[
+            "build",
+            &input.path(),
+            &index.path(),
+            "--max",
+            "4",
+            "--min",
+            "2",
+        ])
+        .unwrap();
+        assert_eq!(
+            built,
+            vec![json!({ "index": index.path(), "objects": 100 }).to_string()]
+        );
+
+        let area = run(&["query", &index.path(), "area", "3", "3", "21", "12"]).unwrap();
+        assert_eq!(sorted_ids(&area), vec![0, 1, 2, 10, 11, 12]);
+
+        let point = run(&["query", &index.path(), "point", "32", "43"]).unwrap();
+        assert_eq!(sorted_ids(&point), vec![43]);
+        assert!(run(&["query", &index.path(), "point", "37", "43"])
+            .unwrap()
+            .is_empty());
+
+        let nearest = run(&["query", &index.path(), "knn", "57", "62", "3"]).unwrap();
+        assert_eq!(nearest.len(), 3);
+        assert_eq!(nearest[0], json!({ "id": 65, "distance": 2.0 }).to_string());
+        assert_eq!(sorted_ids(&nearest), vec![55, 65, 66]);
+    }
+
+    #[test]
+    fn geojson_ids() {
+        let feature = |id: &str| {
+            format!(
+                r#"{{"type": "Feature", "id": {}, "geometry": {{"type": "Point", "coordinates": [1, 2]}}}}"#,
+                id
+            )
+        };
+        let ids = |source: &str| {
+            input::read_records(source, InputFormat::GeoJson).map(|records| {
+                records
+                    .into_iter()
+                    .map(|record| record.1)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(ids(&feature("7")).unwrap(), vec![7]);
+        assert_eq!(ids(&feature(r#""42""#)).unwrap(), vec![42]);
+        assert_eq!(ids(&feature("null")).unwrap(), vec![0]);
+        for invalid in [r#""abc""#, "-1", "1.5", "[1]"] {
+            assert!(
+                matches!(ids(&feature(invalid)), Err(CliError::Input(_))),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
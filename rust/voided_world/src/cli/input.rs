@@ -0,0 +1,171 @@
+///////////
+// INPUT //
+///////////
+
+use crate::cli::{CliError, Index, InputFormat};
+use serde_json::Value;
+use voided_world::geometry2d::{Point, Rectangle};
+use voided_world::r_tree::ObjectRecord;
+
+type Record = ObjectRecord<f64, u64>;
+
+pub fn read_records(source: &str, format: InputFormat) -> Result<Vec<Record>, CliError> {
+    match format {
+        InputFormat::Csv => read_csv(source),
+        InputFormat::GeoJson => read_geojson(source),
+    }
+}
+
+pub fn build_index(records: Vec<Record>, max: usize, min: usize) -> Result<Index, CliError> {
+    let mut tree = Index::new(max, min).map_err(CliError::Tree)?;
+    for record in records {
+        tree.insert(record);
+    }
+    Ok(tree)
+}
+
+/// Rows of `id,x0,y0,x1,y1`. Empty lines, lines starting with `#` and a header row whose first
+/// column is `id` are skipped.
+fn read_csv(source: &str) -> Result<Vec<Record>, CliError> {
+    let mut records = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let columns: Vec<_> = line.split(',').map(str::trim).collect();
+        if idx == 0 && columns[0].eq_ignore_ascii_case("id") {
+            continue;
+        }
+        if columns.len() != 5 {
+            return Err(csv_error(
+                idx,
+                format!("expected 5 columns (id,x0,y0,x1,y1), got {}", columns.len()),
+            ));
+        }
+
+        let id = columns[0]
+            .parse::<u64>()
+            .map_err(|e| csv_error(idx, format!("invalid id '{}': {}", columns[0], e)))?;
+        let mut coordinates = [0.0; 4];
+        for (coordinate, column) in coordinates.iter_mut().zip(&columns[1..]) {
+            *coordinate = column
+                .parse::<f64>()
+                .map_err(|e| csv_error(idx, format!("invalid coordinate '{}': {}", column, e)))?;
+        }
+        let [x0, y0, x1, y1] = coordinates;
+        records.push(ObjectRecord(normalized(x0, y0, x1, y1), id));
+    }
+    Ok(records)
+}
+
+/// A `FeatureCollection` or a single `Feature`. Each feature is indexed by its `bbox` if present,
+/// otherwise by the bounding box of all coordinates of its geometry. The id is taken from the
+/// feature `id`, then `properties.id`, and falls back to the position of the feature. Ids have to
+/// be non-negative integers or strings of them, anything else could collide with another id.
+fn read_geojson(source: &str) -> Result<Vec<Record>, CliError> {
+    let document: Value = serde_json::from_str(source).map_err(CliError::Json)?;
+    let features = match document.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => match document.get("features").and_then(Value::as_array) {
+            Some(features) => features.iter().collect(),
+            None => return Err(geojson_error(0, "FeatureCollection without features")),
+        },
+        Some("Feature") => vec![&document],
+        _ => {
+            return Err(geojson_error(
+                0,
+                "expected a FeatureCollection or a Feature",
+            ))
+        }
+    };
+
+    let mut records = vec![];
+    for (idx, feature) in features.into_iter().enumerate() {
+        let id = match feature
+            .get("id")
+            .or_else(|| feature.get("properties").and_then(|p| p.get("id")))
+        {
+            None | Some(Value::Null) => idx as u64,
+            Some(id) => feature_id(id)
+                .ok_or_else(|| geojson_error(idx, &format!("id {} is not an integer", id)))?,
+        };
+
+        let bounds = match feature.get("bbox").and_then(Value::as_array) {
+            Some(bbox) if bbox.len() == 4 => {
+                let bbox: Vec<_> = bbox.iter().filter_map(Value::as_f64).collect();
+                if bbox.len() != 4 {
+                    return Err(geojson_error(idx, "bbox must contain numbers"));
+                }
+                Some(normalized(bbox[0], bbox[1], bbox[2], bbox[3]))
+            }
+            _ => feature
+                .get("geometry")
+                .and_then(|geometry| geometry_bounds(geometry, None)),
+        };
+
+        match bounds {
+            Some(bounds) => records.push(ObjectRecord(bounds, id)),
+            None => return Err(geojson_error(idx, "feature has no coordinates")),
+        }
+    }
+    Ok(records)
+}
+
+fn feature_id(id: &Value) -> Option<u64> {
+    match id {
+        Value::String(id) => id.parse().ok(),
+        id => id.as_u64(),
+    }
+}
+
+fn geometry_bounds(geometry: &Value, bounds: Option<Rectangle<f64>>) -> Option<Rectangle<f64>> {
+    if let Some(geometries) = geometry.get("geometries").and_then(Value::as_array) {
+        return geometries
+            .iter()
+            .fold(bounds, |bounds, geometry| geometry_bounds(geometry, bounds));
+    }
+    geometry
+        .get("coordinates")
+        .and_then(|coordinates| coordinate_bounds(coordinates, bounds))
+}
+
+fn coordinate_bounds(
+    coordinates: &Value,
+    bounds: Option<Rectangle<f64>>,
+) -> Option<Rectangle<f64>> {
+    let values = coordinates.as_array()?;
+    match (
+        values.first().and_then(Value::as_f64),
+        values.get(1).and_then(Value::as_f64),
+    ) {
+        (Some(x), Some(y)) => {
+            let point = Rectangle {
+                low: Point::new(x, y),
+                high: Point::new(x, y),
+            };
+            Some(match bounds {
+                None => point,
+                Some(bounds) => bounds.merge(&point),
+            })
+        }
+        _ => values
+            .iter()
+            .fold(bounds, |bounds, value| coordinate_bounds(value, bounds)),
+    }
+}
+
+fn normalized(x0: f64, y0: f64, x1: f64, y1: f64) -> Rectangle<f64> {
+    Rectangle {
+        low: Point::new(x0.min(x1), y0.min(y1)),
+        high: Point::new(x0.max(x1), y0.max(y1)),
+    }
+}
+
+fn csv_error(idx: usize, message: String) -> CliError {
+    CliError::Input(format!("line {}: {}", idx + 1, message))
+}
+
+fn geojson_error(feature: usize, message: &str) -> CliError {
+    CliError::Input(format!("feature {}: {}", feature, message))
+}
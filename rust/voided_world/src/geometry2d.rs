@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::ops::{Add, Div, Mul, Rem, Sub};
 
 // Vector / Point
@@ -29,7 +30,7 @@ impl<
 {
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Vector2D<T: Coordinate> {
     pub(crate) x: T,
     pub(crate) y: T,
@@ -109,7 +110,7 @@ impl Into<Vector2D<f32>> for &Vector2D<f64> {
 
 // Rectangle
 
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Rectangle<T: Coordinate> {
     pub low: Point<T>,
     pub high: Point<T>,
@@ -178,6 +179,126 @@ impl<T: Coordinate> Rectangle<T> {
         let h = self.high.y - self.low.y;
         w * h
     }
+
+    /// Squared distance from `point` to the closest point of the rectangle, zero if inside
+    pub fn min_distance_squared(&self, point: &Point<T>) -> T {
+        let clamp = |value: T, low: T, high: T| {
+            if value < low {
+                low
+            } else if value > high {
+                high
+            } else {
+                value
+            }
+        };
+        let dx = point.x - clamp(point.x, self.low.x, self.high.x);
+        let dy = point.y - clamp(point.y, self.low.y, self.high.y);
+        dx * dx + dy * dy
+    }
+}
+
+// Time-parameterized rectangle
+
+/// A rectangle whose edges move linearly over time. `bounds` is the rectangle at time offset zero,
+/// the low edges move with `velocity_low` and the high edges with `velocity_high`. Used by the
+/// TPR-tree to bound moving objects, where it is conservative for all non-negative offsets.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct TPRectangle<T: Coordinate> {
+    pub bounds: Rectangle<T>,
+    pub velocity_low: Vector2D<T>,
+    pub velocity_high: Vector2D<T>,
+}
+
+impl<T: Coordinate> TPRectangle<T> {
+    /// A rectangle that moves as a whole with `velocity`
+    pub fn moving(bounds: Rectangle<T>, velocity: Vector2D<T>) -> Self {
+        Self {
+            bounds,
+            velocity_low: velocity,
+            velocity_high: velocity,
+        }
+    }
+
+    pub fn at(&self, offset: T) -> Rectangle<T> {
+        Rectangle {
+            low: Vector2D::new(
+                self.bounds.low.x + self.velocity_low.x * offset,
+                self.bounds.low.y + self.velocity_low.y * offset,
+            ),
+            high: Vector2D::new(
+                self.bounds.high.x + self.velocity_high.x * offset,
+                self.bounds.high.y + self.velocity_high.y * offset,
+            ),
+        }
+    }
+
+    /// Smallest time-parameterized rectangle containing both for all non-negative offsets
+    pub fn merge(&self, other: &TPRectangle<T>) -> Self {
+        let min = |l: T, r: T| if r < l { r } else { l };
+        let max = |l: T, r: T| if r > l { r } else { l };
+        Self {
+            bounds: self.bounds.merge(&other.bounds),
+            velocity_low: Vector2D::new(
+                min(self.velocity_low.x, other.velocity_low.x),
+                min(self.velocity_low.y, other.velocity_low.y),
+            ),
+            velocity_high: Vector2D::new(
+                max(self.velocity_high.x, other.velocity_high.x),
+                max(self.velocity_high.y, other.velocity_high.y),
+            ),
+        }
+    }
+
+    pub fn intersects_at(&self, area: &Rectangle<T>, offset: T) -> bool {
+        self.at(offset).intersects(area)
+    }
+}
+
+impl<T: Coordinate + From<u8>> TPRectangle<T> {
+    /// The sub-interval of the offsets `[from, to]` during which the rectangle intersects `area`
+    pub fn intersection_interval(&self, area: &Rectangle<T>, from: T, to: T) -> Option<(T, T)> {
+        let zero = T::from(0);
+        let mut interval = (from, to);
+        // every constraint has the form `offset + slope * t <= 0`
+        let constraints = [
+            (self.bounds.low.x - area.high.x, self.velocity_low.x),
+            (self.bounds.low.y - area.high.y, self.velocity_low.y),
+            (area.low.x - self.bounds.high.x, zero - self.velocity_high.x),
+            (area.low.y - self.bounds.high.y, zero - self.velocity_high.y),
+        ];
+        for (offset, slope) in constraints {
+            if slope > zero {
+                let bound = (zero - offset) / slope;
+                if bound < interval.1 {
+                    interval.1 = bound;
+                }
+            } else if slope < zero {
+                let bound = (zero - offset) / slope;
+                if bound > interval.0 {
+                    interval.0 = bound;
+                }
+            } else if offset > zero {
+                return None;
+            }
+        }
+        if interval.0 <= interval.1 {
+            Some(interval)
+        } else {
+            None
+        }
+    }
+
+    pub fn intersects_during(&self, area: &Rectangle<T>, from: T, to: T) -> bool {
+        self.intersection_interval(area, from, to).is_some()
+    }
+
+    /// Integral of the area over the offsets `[from, to]`. The area is quadratic in time, so
+    /// Simpson's rule is exact.
+    pub fn swept_area(&self, from: T, to: T) -> T {
+        let mid = (from + to) / T::from(2);
+        let areas = self.at(from).area() + T::from(4) * self.at(mid).area() + self.at(to).area();
+        areas * (to - from) / T::from(6)
+    }
 }
 
 pub trait Spacial<T: Coordinate>: Clone {
@@ -12,7 +12,7 @@ pub struct GVNode<T: GVNodeKey>(T);
 #[derive(Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct GVEdge<T: GVNodeKey>(T, T);
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct GVAnnotation {
     pub label: Option<String>,
     pub style: Option<String>,
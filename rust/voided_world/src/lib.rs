@@ -0,0 +1,3 @@
+pub mod geometry2d;
+pub mod graphviz;
+pub mod r_tree;
@@ -1,130 +1,19 @@
-mod geometry2d;
-mod graphviz;
-mod r_tree;
+mod cli;
 
-use crate::geometry2d::{Point, Rectangle};
-use crate::graphviz::GVGraph;
-use crate::r_tree::{ObjectRecord, RTree};
+use crate::cli::Command;
+use std::io;
+use std::process::ExitCode;
 
-fn main() {
-    let records = vec![
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(0.0, 0.0),
-                high: Point::new(10.0, 10.0),
-            },
-            1,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(12.0, 0.0),
-                high: Point::new(15.0, 15.0),
-            },
-            2,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(7.0, 7.0),
-                high: Point::new(14.0, 14.0),
-            },
-            3,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(10.0, 11.0),
-                high: Point::new(11.0, 12.0),
-            },
-            4,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(4.0, 4.0),
-                high: Point::new(5.0, 6.0),
-            },
-            5,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(4.0, 9.0),
-                high: Point::new(5.0, 11.0),
-            },
-            6,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(13.0, 0.0),
-                high: Point::new(14.0, 1.0),
-            },
-            7,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(13.0, 13.0),
-                high: Point::new(16.0, 16.0),
-            },
-            8,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(2.0, 13.0),
-                high: Point::new(4.0, 16.0),
-            },
-            9,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(2.0, 2.0),
-                high: Point::new(3.0, 3.0),
-            },
-            10,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(10.0, 0.0),
-                high: Point::new(12.0, 5.0),
-            },
-            11,
-        ),
-        ObjectRecord(
-            Rectangle {
-                low: Point::new(7.0, 3.0),
-                high: Point::new(8.0, 6.0),
-            },
-            12,
-        ),
-    ];
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    let mut tree = RTree::new(4, 2).unwrap();
+    let result = Command::parse(&args).and_then(|command| command.run(&mut io::stdout().lock()));
 
-    for rec in records.iter() {
-        tree.insert(rec.clone());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
     }
-
-    let graph: GVGraph<String> = tree.clone().into();
-
-    println!("{}", graph);
-
-    println!(
-        "{:?}",
-        tree.search_area(&Rectangle {
-            low: Point::new(5.0, 5.0),
-            high: Point::new(7.0, 7.0)
-        })
-    );
-    println!("{:?}", tree.search_point(&Point::new(7.0, 7.0)));
-
-    tree.delete(records[0].clone());
-    tree.delete(records[1].clone());
-    tree.delete(records[2].clone());
-    tree.delete(records[3].clone());
-    tree.delete(records[4].clone());
-    tree.delete(records[5].clone());
-    tree.delete(records[6].clone());
-    tree.delete(records[7].clone());
-    tree.delete(records[8].clone());
-    tree.delete(records[9].clone());
-    tree.delete(records[10].clone());
-    tree.delete(records[11].clone());
-
-    println!("{}", tree);
 }
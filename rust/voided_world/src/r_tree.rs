@@ -6,6 +6,7 @@ use crate::r_tree::RTreeError::{
     MaxMustBeAtLeastFour, MinMustBeAtLeastTwo, MinMustBeAtMostHalfOfMax,
 };
 use either::{Either, Left, Right};
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::hash::Hash;
 use Entry::NonLeaf;
@@ -14,19 +15,24 @@ mod delete;
 mod display;
 mod insert;
 mod query;
+mod stats;
+mod tpr_tree;
 mod vizgraph;
 
+pub use stats::RTreeStats;
+pub use tpr_tree::{MovingObjectRecord, TPRTree};
+
 pub trait ObjectId: Eq + Hash + Copy + Clone + Debug {}
 
 impl<T: Eq + Hash + Copy + Clone + Debug> ObjectId for T {}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectRecord<T: Coordinate, O: ObjectId>(pub Rectangle<T>, pub O);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct ChildRecord<T: Coordinate, O: ObjectId>(Rectangle<T>, Entry<T, O>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 enum Entry<T: Coordinate, O: ObjectId> {
     Leaf {
         children: Vec<ObjectRecord<T, O>>,
@@ -46,7 +52,7 @@ impl<T: Coordinate, O: ObjectId> Entry<T, O> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Config {
     maximum_entries_per_node: usize,
     minimum_entries_per_node: usize,
@@ -57,9 +63,11 @@ pub enum RTreeError {
     MaxMustBeAtLeastFour,
     MinMustBeAtMostHalfOfMax,
     MinMustBeAtLeastTwo,
+    TimeBeforeReferenceTime,
+    InvalidTimeInterval,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RTree<T: Coordinate, O: ObjectId> {
     root: Option<Entry<T, O>>,
     config: Config,
@@ -24,7 +24,7 @@ struct DimExtremes<T: Coordinate> {
 }
 
 impl<T: Coordinate, O: ObjectId> RTree<T, O> {
-    pub(crate) fn insert(&mut self, record: ObjectRecord<T, O>) {
+    pub fn insert(&mut self, record: ObjectRecord<T, O>) {
         match &mut self.root {
             None => {
                 self.root = Some(Leaf {
@@ -4,7 +4,10 @@
 
 use crate::geometry2d::{Coordinate, Point, Rectangle};
 use crate::r_tree::Entry::{Leaf, NonLeaf};
-use crate::r_tree::{ChildRecord, Entry, ObjectId, ObjectRecord, RTree};
+use crate::r_tree::{ChildRecord, Entry, ObjectId, ObjectRecord, RTree, Spacial};
+use either::{Either, Left, Right};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 
 impl<T: Coordinate, O: ObjectId> RTree<T, O> {
     pub fn search_area(&self, area: &Rectangle<T>) -> Vec<O> {
@@ -23,6 +26,55 @@ impl<T: Coordinate, O: ObjectId> RTree<T, O> {
             }),
         }
     }
+
+    /// Returns up to `k` objects closest to `point`, nearest first, together with their squared
+    /// distance. Uses best-first traversal, so only nodes that could still contain a closer object
+    /// are visited.
+    pub fn search_nearest(&self, point: &Point<T>, k: usize) -> Vec<(O, T)> {
+        let mut result = vec![];
+        let root = match &self.root {
+            None => return result,
+            Some(root) => root,
+        };
+        if k == 0 {
+            return result;
+        }
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate {
+            distance: root.mbb().min_distance_squared(point),
+            item: Left(root),
+        });
+
+        while let Some(Candidate { distance, item }) = queue.pop() {
+            match item {
+                Right(oid) => {
+                    result.push((oid, distance));
+                    if result.len() == k {
+                        break;
+                    }
+                }
+                Left(Leaf { children }) => {
+                    for ObjectRecord(rec, oid) in children {
+                        queue.push(Candidate {
+                            distance: rec.min_distance_squared(point),
+                            item: Right(*oid),
+                        });
+                    }
+                }
+                Left(NonLeaf { children, .. }) => {
+                    for ChildRecord(rec, entry) in children {
+                        queue.push(Candidate {
+                            distance: rec.min_distance_squared(point),
+                            item: Left(entry),
+                        });
+                    }
+                }
+            }
+        }
+
+        result
+    }
 }
 
 impl<T: Coordinate, O: ObjectId> Entry<T, O> {
@@ -41,3 +93,35 @@ impl<T: Coordinate, O: ObjectId> Entry<T, O> {
         }
     }
 }
+
+/// Queue element for the nearest neighbour search, ordered so that the [BinaryHeap] pops the
+/// closest candidate first. Objects win ties against nodes so results are reported as early as
+/// possible.
+struct Candidate<'a, T: Coordinate, O: ObjectId> {
+    distance: T,
+    item: Either<&'a Entry<T, O>, O>,
+}
+
+impl<T: Coordinate, O: ObjectId> PartialEq for Candidate<'_, T, O> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Coordinate, O: ObjectId> Eq for Candidate<'_, T, O> {}
+
+impl<T: Coordinate, O: ObjectId> PartialOrd for Candidate<'_, T, O> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Coordinate, O: ObjectId> Ord for Candidate<'_, T, O> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.item.is_right().cmp(&other.item.is_right()))
+    }
+}
@@ -0,0 +1,67 @@
+///////////
+// STATS //
+///////////
+
+use crate::geometry2d::{Coordinate, Rectangle};
+use crate::r_tree::Entry::{Leaf, NonLeaf};
+use crate::r_tree::{ChildRecord, Entry, ObjectId, RTree, Spacial};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RTreeStats<T: Coordinate> {
+    pub objects: usize,
+    /// number of levels, 0 for an empty tree and 1 if the root is a leaf
+    pub height: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub minimum_entries_per_node: usize,
+    pub maximum_entries_per_node: usize,
+    /// average number of entries per node relative to the maximum
+    pub average_fill: f64,
+    pub bounds: Option<Rectangle<T>>,
+}
+
+impl<T: Coordinate, O: ObjectId> RTree<T, O> {
+    pub fn stats(&self) -> RTreeStats<T> {
+        let mut stats = RTreeStats {
+            objects: 0,
+            height: 0,
+            nodes: 0,
+            leaves: 0,
+            minimum_entries_per_node: self.config.minimum_entries_per_node(),
+            maximum_entries_per_node: self.config.maximum_entries_per_node(),
+            average_fill: 0.0,
+            bounds: None,
+        };
+
+        if let Some(root) = &self.root {
+            let mut entries = 0;
+            root.collect_stats(&mut stats, &mut entries, 1);
+            stats.bounds = Some(root.mbb());
+            stats.average_fill =
+                entries as f64 / (stats.nodes * stats.maximum_entries_per_node) as f64;
+        }
+
+        stats
+    }
+}
+
+impl<T: Coordinate, O: ObjectId> Entry<T, O> {
+    fn collect_stats(&self, stats: &mut RTreeStats<T>, entries: &mut usize, depth: usize) {
+        stats.nodes += 1;
+        stats.height = stats.height.max(depth);
+        match self {
+            Leaf { children } => {
+                stats.leaves += 1;
+                stats.objects += children.len();
+                *entries += children.len();
+            }
+            NonLeaf { children, .. } => {
+                *entries += children.len();
+                for ChildRecord(.., child) in children {
+                    child.collect_stats(stats, entries, depth + 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,589 @@
+//////////////
+// TPR-TREE //
+//////////////
+
+//source: https://dl.acm.org/doi/10.1145/342009.335427 (Saltenis et al., Indexing the Positions of
+//        Continuously Moving Objects)
+
+use crate::geometry2d::{Coordinate, Rectangle, TPRectangle, Vector2D};
+use crate::r_tree::RTreeError::{InvalidTimeInterval, TimeBeforeReferenceTime};
+use crate::r_tree::{Config, ObjectId, RTreeError};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use MovingEntry::{Leaf, NonLeaf};
+
+/// A moving object, its bounds are stored relative to the reference time of the tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovingObjectRecord<T: Coordinate, O: ObjectId>(pub TPRectangle<T>, pub O);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MovingChildRecord<T: Coordinate, O: ObjectId>(TPRectangle<T>, MovingEntry<T, O>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum MovingEntry<T: Coordinate, O: ObjectId> {
+    Leaf {
+        children: Vec<MovingObjectRecord<T, O>>,
+    },
+    NonLeaf {
+        children: Vec<MovingChildRecord<T, O>>,
+    },
+}
+
+/// An R-tree over linearly moving objects. All bounding rectangles are time-parameterized
+/// relative to `reference_time`, so the tree can answer where objects will be at any time at or
+/// after it. The `horizon` is how far into the future the tree is tuned for, insertion keeps the
+/// bounds small over `[now, now + horizon]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TPRTree<T: Coordinate, O: ObjectId> {
+    root: Option<MovingEntry<T, O>>,
+    config: Config,
+    reference_time: T,
+    horizon: T,
+}
+
+enum MovingInsertionResult<T: Coordinate, O: ObjectId> {
+    Split(MovingChildRecord<T, O>, MovingChildRecord<T, O>),
+    NoSplit,
+}
+
+//////////////////
+// CONSTRUCTION //
+//////////////////
+
+impl<T: Coordinate + From<u8>, O: ObjectId> TPRTree<T, O> {
+    pub fn new(max: usize, min: usize, reference_time: T, horizon: T) -> Result<Self, RTreeError> {
+        let config = Config::new(max, min)?;
+        Ok(Self {
+            root: None,
+            config,
+            reference_time,
+            horizon,
+        })
+    }
+
+    pub fn reference_time(&self) -> T {
+        self.reference_time
+    }
+}
+
+////////////
+// INSERT //
+////////////
+
+impl<T: Coordinate + From<u8>, O: ObjectId> TPRTree<T, O> {
+    /// Inserts an object that covers `bounds` at `time` and moves with `velocity`
+    pub fn insert(&mut self, bounds: Rectangle<T>, velocity: Vector2D<T>, time: T, id: O) {
+        let offset = time - self.reference_time;
+        let reference_bounds = Rectangle {
+            low: Vector2D::new(
+                bounds.low.x - velocity.x * offset,
+                bounds.low.y - velocity.y * offset,
+            ),
+            high: Vector2D::new(
+                bounds.high.x - velocity.x * offset,
+                bounds.high.y - velocity.y * offset,
+            ),
+        };
+        let record = MovingObjectRecord(TPRectangle::moving(reference_bounds, velocity), id);
+
+        // optimize the bounds for the horizon starting at the insertion, but never before the
+        // reference time as the tree can not be queried there
+        let zero = T::from(0);
+        let from = if offset < zero { zero } else { offset };
+        let to = from + self.horizon;
+
+        match &mut self.root {
+            None => {
+                self.root = Some(Leaf {
+                    children: vec![record],
+                })
+            }
+            Some(root) => match root.insert(&self.config, record, from, to) {
+                MovingInsertionResult::NoSplit => {}
+                MovingInsertionResult::Split(left, right) => {
+                    self.root = Some(NonLeaf {
+                        children: vec![left, right],
+                    })
+                }
+            },
+        }
+    }
+}
+
+impl<T: Coordinate + From<u8>, O: ObjectId> MovingEntry<T, O> {
+    fn tpbr(&self) -> TPRectangle<T> {
+        match self {
+            Leaf { children } => children
+                .iter()
+                .map(|c| c.0)
+                .reduce(|l, r| l.merge(&r))
+                .unwrap(),
+            NonLeaf { children } => children
+                .iter()
+                .map(|c| c.0)
+                .reduce(|l, r| l.merge(&r))
+                .unwrap(),
+        }
+    }
+
+    fn insert(
+        &mut self,
+        config: &Config,
+        record: MovingObjectRecord<T, O>,
+        from: T,
+        to: T,
+    ) -> MovingInsertionResult<T, O> {
+        match self {
+            Leaf { children } => {
+                children.push(record);
+                if children.len() > config.maximum_entries_per_node() {
+                    let (left, right) = split(std::mem::take(children), config, from, to, |c| c.0);
+                    let left = Leaf { children: left };
+                    let right = Leaf { children: right };
+                    MovingInsertionResult::Split(
+                        MovingChildRecord(left.tpbr(), left),
+                        MovingChildRecord(right.tpbr(), right),
+                    )
+                } else {
+                    MovingInsertionResult::NoSplit
+                }
+            }
+            NonLeaf { children } => {
+                // choose the child whose swept area over the horizon grows the least
+                let mut best_candidate = 0;
+                let mut best_tpbr = children[0].0.merge(&record.0);
+                let mut best_area = best_tpbr.swept_area(from, to);
+                let mut best_diff = best_area - children[0].0.swept_area(from, to);
+                for (candidate, child) in children.iter().enumerate().skip(1) {
+                    let test_tpbr = child.0.merge(&record.0);
+                    let test_area = test_tpbr.swept_area(from, to);
+                    let test_diff = test_area - child.0.swept_area(from, to);
+                    if test_diff < best_diff || (test_diff == best_diff && test_area < best_area) {
+                        best_tpbr = test_tpbr;
+                        best_area = test_area;
+                        best_diff = test_diff;
+                        best_candidate = candidate;
+                    }
+                }
+
+                let candidate = &mut children[best_candidate];
+                candidate.0 = best_tpbr;
+                match candidate.1.insert(config, record, from, to) {
+                    MovingInsertionResult::NoSplit => MovingInsertionResult::NoSplit,
+                    MovingInsertionResult::Split(left, right) => {
+                        children.remove(best_candidate);
+                        children.push(left);
+                        children.push(right);
+                        if children.len() > config.maximum_entries_per_node() {
+                            let (left, right) =
+                                split(std::mem::take(children), config, from, to, |c| c.0);
+                            let left = NonLeaf { children: left };
+                            let right = NonLeaf { children: right };
+                            MovingInsertionResult::Split(
+                                MovingChildRecord(left.tpbr(), left),
+                                MovingChildRecord(right.tpbr(), right),
+                            )
+                        } else {
+                            MovingInsertionResult::NoSplit
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Sorts the entries along each axis by their position in the middle of the horizon and picks the
+/// partition with the smallest total swept area that respects the minimum node size.
+fn split<T: Coordinate + From<u8>, E>(
+    mut entries: Vec<E>,
+    config: &Config,
+    from: T,
+    to: T,
+    tpbr: impl Fn(&E) -> TPRectangle<T>,
+) -> (Vec<E>, Vec<E>) {
+    let mid = (from + to) / T::from(2);
+    let min = config.minimum_entries_per_node();
+
+    let mut best: Option<(T, bool, usize)> = None;
+    for along_x in [true, false] {
+        sort_along(&mut entries, &tpbr, mid, along_x);
+        for at in min..=(entries.len() - min) {
+            let cost = merged(&entries[..at], &tpbr).swept_area(from, to)
+                + merged(&entries[at..], &tpbr).swept_area(from, to);
+            match best {
+                Some((best_cost, ..)) if best_cost <= cost => {}
+                _ => best = Some((cost, along_x, at)),
+            }
+        }
+    }
+
+    let (_, along_x, at) = best.unwrap();
+    sort_along(&mut entries, &tpbr, mid, along_x);
+    let right = entries.split_off(at);
+    (entries, right)
+}
+
+fn sort_along<T: Coordinate, E>(
+    entries: &mut [E],
+    tpbr: &impl Fn(&E) -> TPRectangle<T>,
+    offset: T,
+    along_x: bool,
+) {
+    let center = |entry: &E| {
+        let rect = tpbr(entry).at(offset);
+        if along_x {
+            rect.low.x + rect.high.x
+        } else {
+            rect.low.y + rect.high.y
+        }
+    };
+    entries.sort_by(|l, r| center(l).partial_cmp(&center(r)).unwrap_or(Ordering::Equal));
+}
+
+fn merged<T: Coordinate, E>(entries: &[E], tpbr: &impl Fn(&E) -> TPRectangle<T>) -> TPRectangle<T> {
+    entries.iter().map(tpbr).reduce(|l, r| l.merge(&r)).unwrap()
+}
+
+////////////
+// DELETE //
+////////////
+
+impl<T: Coordinate + From<u8>, O: ObjectId> TPRTree<T, O> {
+    /// Removes the object with the given id. Emptied nodes are removed and the bounds along the
+    /// path are recomputed, underfull nodes are kept as they are.
+    pub fn delete(&mut self, id: &O) -> bool {
+        let root = match &mut self.root {
+            None => return false,
+            Some(root) => root,
+        };
+        let deleted = root.delete(id);
+
+        // shorten tree
+        match root {
+            Leaf { children } if children.is_empty() => self.root = None,
+            NonLeaf { children } if children.is_empty() => self.root = None,
+            NonLeaf { children } if children.len() == 1 => {
+                self.root = Some(children.remove(0).1);
+            }
+            _ => {}
+        }
+        deleted
+    }
+}
+
+impl<T: Coordinate + From<u8>, O: ObjectId> MovingEntry<T, O> {
+    fn delete(&mut self, id: &O) -> bool {
+        match self {
+            Leaf { children } => match children.iter().position(|c| c.1 == *id) {
+                None => false,
+                Some(idx) => {
+                    children.remove(idx);
+                    true
+                }
+            },
+            NonLeaf { children } => {
+                for idx in 0..children.len() {
+                    let child = &mut children[idx];
+                    if child.1.delete(id) {
+                        if child.1.is_empty() {
+                            children.remove(idx);
+                        } else {
+                            child.0 = child.1.tpbr();
+                        }
+                        return true;
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Leaf { children } => children.is_empty(),
+            NonLeaf { children } => children.is_empty(),
+        }
+    }
+}
+
+////////////
+// SEARCH //
+////////////
+
+impl<T: Coordinate + From<u8>, O: ObjectId> TPRTree<T, O> {
+    /// Objects intersecting `area` at `time`
+    pub fn search_timeslice(&self, area: &Rectangle<T>, time: T) -> Result<Vec<O>, RTreeError> {
+        self.search_interval(area, time, time)
+    }
+
+    /// Objects intersecting `area` at any moment during `[from, to]`
+    pub fn search_interval(
+        &self,
+        area: &Rectangle<T>,
+        from: T,
+        to: T,
+    ) -> Result<Vec<O>, RTreeError> {
+        if from < self.reference_time {
+            return Err(TimeBeforeReferenceTime);
+        }
+        if to < from {
+            return Err(InvalidTimeInterval);
+        }
+        let from = from - self.reference_time;
+        let to = to - self.reference_time;
+        Ok(match &self.root {
+            None => vec![],
+            Some(root) => root.search(area, from, to),
+        })
+    }
+}
+
+impl<T: Coordinate + From<u8>, O: ObjectId> MovingEntry<T, O> {
+    fn search(&self, area: &Rectangle<T>, from: T, to: T) -> Vec<O> {
+        match self {
+            Leaf { children } => children
+                .iter()
+                .filter(|MovingObjectRecord(rec, ..)| rec.intersects_during(area, from, to))
+                .map(|MovingObjectRecord(.., oid)| *oid)
+                .collect(),
+            NonLeaf { children } => children
+                .iter()
+                .filter(|MovingChildRecord(rec, ..)| rec.intersects_during(area, from, to))
+                .flat_map(|MovingChildRecord(.., entry)| entry.search(area, from, to))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift, so failures can be reproduced
+    struct Random(u64);
+
+    impl Random {
+        /// A multiple of 1/4 in `[low, high)`, these are exact in floating point
+        fn quarter(&mut self, low: f64, high: f64) -> f64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            let steps = ((high - low) * 4.0) as u64;
+            low + (self.0 % steps) as f64 / 4.0
+        }
+    }
+
+    struct Object {
+        bounds: Rectangle<f64>,
+        velocity: Vector2D<f64>,
+        time: f64,
+        id: u32,
+    }
+
+    impl Object {
+        fn at(&self, time: f64) -> Rectangle<f64> {
+            let offset = time - self.time;
+            let moved = |point: Vector2D<f64>| {
+                Vector2D::new(
+                    point.x + self.velocity.x * offset,
+                    point.y + self.velocity.y * offset,
+                )
+            };
+            Rectangle {
+                low: moved(self.bounds.low),
+                high: moved(self.bounds.high),
+            }
+        }
+    }
+
+    fn brute_force(objects: &[Object], area: &Rectangle<f64>, time: f64) -> Vec<u32> {
+        let mut ids: Vec<u32> = objects
+            .iter()
+            .filter(|object| object.at(time).intersects(area))
+            .map(|object| object.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// `numerator / denominator` with a positive denominator, compared without dividing
+    #[derive(Clone, Copy)]
+    struct Fraction(f64, f64);
+
+    impl Fraction {
+        fn at_most(self, other: Fraction) -> bool {
+            self.0 * other.1 <= other.0 * self.1
+        }
+    }
+
+    /// Whether the object meets `area` at any time in `[from, to]`. Every axis allows a range of
+    /// times, their bounds are compared as exact fractions.
+    fn meets_during(object: &Object, area: &Rectangle<f64>, from: f64, to: f64) -> bool {
+        let (mut first, mut last) = (Fraction(from, 1.0), Fraction(to, 1.0));
+        let (bounds, velocity) = (&object.bounds, &object.velocity);
+        let axes = [
+            (
+                bounds.low.x,
+                bounds.high.x,
+                velocity.x,
+                area.low.x,
+                area.high.x,
+            ),
+            (
+                bounds.low.y,
+                bounds.high.y,
+                velocity.y,
+                area.low.y,
+                area.high.y,
+            ),
+        ];
+        for (low, high, velocity, area_low, area_high) in axes {
+            // area_low <= high + velocity * (t - time) and low + velocity * (t - time) <= area_high
+            // become min <= velocity * t <= max
+            let min = area_low - high + velocity * object.time;
+            let max = area_high - low + velocity * object.time;
+            let (start, end) = if velocity > 0.0 {
+                (Fraction(min, velocity), Fraction(max, velocity))
+            } else if velocity < 0.0 {
+                (Fraction(-max, -velocity), Fraction(-min, -velocity))
+            } else if min <= 0.0 && 0.0 <= max {
+                continue;
+            } else {
+                return false;
+            };
+            if first.at_most(start) {
+                first = start;
+            }
+            if end.at_most(last) {
+                last = end;
+            }
+        }
+        first.at_most(last)
+    }
+
+    fn brute_force_interval(
+        objects: &[Object],
+        area: &Rectangle<f64>,
+        from: f64,
+        to: f64,
+    ) -> Vec<u32> {
+        let mut ids: Vec<u32> = objects
+            .iter()
+            .filter(|object| meets_during(object, area, from, to))
+            .map(|object| object.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Compares timeslice queries at quarter times up to `until` and interval queries starting
+    /// there. The edges of the query areas are never on the quarter grid of the objects, so no
+    /// comparison depends on rounding.
+    fn compare(tree: &TPRTree<f64, u32>, objects: &[Object], random: &mut Random, until: f64) {
+        for _ in 0..200 {
+            let time = random.quarter(tree.reference_time(), until);
+            let (x, y) = (random.quarter(-200.0, 200.0), random.quarter(-200.0, 200.0));
+            let area = Rectangle {
+                low: Vector2D::new(x + 0.125, y + 0.125),
+                high: Vector2D::new(
+                    x + random.quarter(0.0, 80.0) + 0.125,
+                    y + random.quarter(0.0, 80.0) + 0.125,
+                ),
+            };
+            let mut found = tree.search_timeslice(&area, time).unwrap();
+            found.sort();
+            assert_eq!(
+                found,
+                brute_force(objects, &area, time),
+                "{:?} at {}",
+                area,
+                time
+            );
+
+            let to = time + random.quarter(0.0, 20.0);
+            let mut found = tree.search_interval(&area, time, to).unwrap();
+            found.sort();
+            assert_eq!(
+                found,
+                brute_force_interval(objects, &area, time, to),
+                "{:?} during [{}, {}]",
+                area,
+                time,
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let (reference_time, horizon) = (10.0, 20.0);
+        let mut tree = TPRTree::new(8, 3, reference_time, horizon).unwrap();
+        let mut random = Random(0x5eed);
+        let mut objects = vec![];
+        for id in 0..400 {
+            let (x, y) = (random.quarter(-100.0, 100.0), random.quarter(-100.0, 100.0));
+            let object = Object {
+                bounds: Rectangle {
+                    low: Vector2D::new(x, y),
+                    high: Vector2D::new(
+                        x + random.quarter(0.0, 10.0),
+                        y + random.quarter(0.0, 10.0),
+                    ),
+                },
+                velocity: Vector2D::new(random.quarter(-4.0, 4.0), random.quarter(-4.0, 4.0)),
+                // also before the reference time, the bounds are extrapolated back
+                time: random.quarter(0.0, 40.0),
+                id,
+            };
+            tree.insert(object.bounds, object.velocity, object.time, id);
+            objects.push(object);
+
+            if id % 100 == 99 {
+                compare(&tree, &objects, &mut random, reference_time + horizon);
+            }
+        }
+        // far past the horizon the bounds are loose, but still have to be correct
+        compare(
+            &tree,
+            &objects,
+            &mut random,
+            reference_time + 10.0 * horizon,
+        );
+
+        for _ in 0..250 {
+            let idx = random.quarter(0.0, objects.len() as f64) as usize;
+            let object = objects.swap_remove(idx);
+            assert!(tree.delete(&object.id));
+            assert!(!tree.delete(&object.id));
+            if objects.len() % 50 == 0 {
+                compare(&tree, &objects, &mut random, reference_time + 2.0 * horizon);
+            }
+        }
+        while let Some(object) = objects.pop() {
+            assert!(tree.delete(&object.id));
+        }
+        compare(&tree, &objects, &mut random, reference_time + horizon);
+    }
+
+    #[test]
+    fn times_before_the_reference_time_are_rejected() {
+        let tree: TPRTree<f64, u32> = TPRTree::new(4, 2, 10.0, 5.0).unwrap();
+        let area = Rectangle {
+            low: Vector2D::new(0.0, 0.0),
+            high: Vector2D::new(1.0, 1.0),
+        };
+        assert!(matches!(
+            tree.search_timeslice(&area, 9.0),
+            Err(TimeBeforeReferenceTime)
+        ));
+        assert!(matches!(
+            tree.search_interval(&area, 12.0, 11.0),
+            Err(InvalidTimeInterval)
+        ));
+        assert_eq!(
+            tree.search_timeslice(&area, 10.0).unwrap(),
+            Vec::<u32>::new()
+        );
+    }
+}